@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
+    use fast_async_mutex::cache_padded::CachePadded;
     use fast_async_mutex::mutex::Mutex;
+    use fast_async_mutex::wait_strategy::Adaptive;
     use std::sync::Arc;
     use test::Bencher;
 
@@ -31,6 +33,47 @@ mod tests {
         })
     }
 
+    #[bench]
+    fn concurrency_without_waiting_adaptive(b: &mut Bencher) {
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        b.iter(|| {
+            let num = 100;
+            let mutex: Arc<Mutex<_, Adaptive>> = Arc::new(Mutex::with_strategy(0));
+            let ths: Vec<_> = (0..num)
+                .map(|_| {
+                    let mutex = mutex.clone();
+                    runtime.spawn(async move {
+                        let mut lock = mutex.lock().await;
+                        *lock += 1;
+                    })
+                })
+                .collect();
+
+            for thread in ths {
+                runtime.block_on(thread).unwrap();
+            }
+        })
+    }
+
+    #[bench]
+    fn concurrency_without_waiting_delegate(b: &mut Bencher) {
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        b.iter(|| {
+            let num = 100;
+            let mutex = Arc::new(Mutex::new(0));
+            let ths: Vec<_> = (0..num)
+                .map(|_| {
+                    let mutex = mutex.clone();
+                    runtime.spawn(async move { mutex.delegate(|data| *data += 1).await })
+                })
+                .collect();
+
+            for thread in ths {
+                runtime.block_on(thread).unwrap();
+            }
+        })
+    }
+
     #[bench]
     fn step_by_step_without_waiting(b: &mut Bencher) {
         let runtime = tokio::runtime::Builder::new_current_thread()
@@ -48,4 +91,35 @@ mod tests {
             }
         })
     }
+
+    fn write_contention<T: Default + Send + Sync + 'static>(b: &mut Bencher, write: fn(&mut T)) {
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        b.iter(|| {
+            let mutex = Arc::new(Mutex::new(T::default()));
+            let ths: Vec<_> = (0..8)
+                .map(|_| {
+                    let mutex = mutex.clone();
+                    runtime.spawn(async move {
+                        for _ in 0..1000 {
+                            write(&mut *mutex.lock().await);
+                        }
+                    })
+                })
+                .collect();
+
+            for thread in ths {
+                runtime.block_on(thread).unwrap();
+            }
+        })
+    }
+
+    #[bench]
+    fn concurrency_write_contention(b: &mut Bencher) {
+        write_contention::<u64>(b, |data| *data += 1);
+    }
+
+    #[bench]
+    fn concurrency_write_contention_padded_data(b: &mut Bencher) {
+        write_contention::<CachePadded<u64>>(b, |data| **data += 1);
+    }
 }
@@ -31,6 +31,39 @@ mod tests {
         })
     }
 
+    #[bench]
+    fn wake_100_queued_tickets(b: &mut Bencher) {
+        wake_queued_tickets(b, 100)
+    }
+
+    #[bench]
+    fn wake_10000_queued_tickets(b: &mut Bencher) {
+        wake_queued_tickets(b, 10000)
+    }
+
+    /// Every unlock wakes only the next ticket, so the time per ticket shouldn't depend on the queue length.
+    fn wake_queued_tickets(b: &mut Bencher, num: usize) {
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        b.iter(|| {
+            let mutex = Arc::new(OrderedMutex::new(0));
+            let guard = runtime.block_on(mutex.lock_owned());
+            let ths: Vec<_> = (0..num)
+                .map(|_| {
+                    let ticket = mutex.reserve_owned();
+                    runtime.spawn(async move {
+                        let mut lock = ticket.await;
+                        *lock += 1;
+                    })
+                })
+                .collect();
+            drop(guard);
+
+            for thread in ths {
+                runtime.block_on(thread).unwrap();
+            }
+        })
+    }
+
     #[bench]
     fn step_by_step_without_waiting(b: &mut Bencher) {
         let runtime = tokio::runtime::Builder::new_current_thread()
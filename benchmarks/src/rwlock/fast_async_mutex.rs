@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod tests {
+    use fast_async_mutex::cache_padded::CachePadded;
     use fast_async_mutex::rwlock::RwLock;
     use std::sync::Arc;
     use test::Bencher;
@@ -69,6 +70,32 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn concurrency_read_32_threads(b: &mut Bencher) {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(32)
+            .build()
+            .unwrap();
+        b.iter(|| {
+            let num = 1000;
+            let mutex = Arc::new(RwLock::new(0));
+            let ths: Vec<_> = (0..num)
+                .map(|_| {
+                    let mutex = mutex.clone();
+                    runtime.spawn(async move {
+                        for _ in 0..100 {
+                            let _lock = mutex.read().await;
+                        }
+                    })
+                })
+                .collect();
+
+            for thread in ths {
+                runtime.block_on(thread).unwrap();
+            }
+        });
+    }
+
     #[bench]
     fn step_by_step_read(b: &mut Bencher) {
         let runtime = tokio::runtime::Builder::new_current_thread()
@@ -84,4 +111,35 @@ mod tests {
             }
         });
     }
+
+    fn write_contention<T: Default + Send + Sync + 'static>(b: &mut Bencher, write: fn(&mut T)) {
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        b.iter(|| {
+            let mutex = Arc::new(RwLock::new(T::default()));
+            let ths: Vec<_> = (0..8)
+                .map(|_| {
+                    let mutex = mutex.clone();
+                    runtime.spawn(async move {
+                        for _ in 0..1000 {
+                            write(&mut *mutex.write().await);
+                        }
+                    })
+                })
+                .collect();
+
+            for thread in ths {
+                runtime.block_on(thread).unwrap();
+            }
+        })
+    }
+
+    #[bench]
+    fn concurrency_write_contention(b: &mut Bencher) {
+        write_contention::<u64>(b, |data| *data += 1);
+    }
+
+    #[bench]
+    fn concurrency_write_contention_padded_data(b: &mut Bencher) {
+        write_contention::<CachePadded<u64>>(b, |data| **data += 1);
+    }
 }
@@ -1,4 +1,5 @@
 mod fast_async_mutex;
 mod fast_async_mutex_ordered;
+mod fast_async_mutex_sharded;
 mod smol;
 mod tokio;
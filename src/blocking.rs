@@ -0,0 +1,292 @@
+use crate::raw::{RawAsyncMutex, RawAsyncRwLock, RawMutex, RawRwLock, RawWaiter, UPGRADING};
+use crate::wait_strategy::WaitStrategy;
+use lock_api::GuardSend;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// The number of spins of the upgrading reader, before it yields the thread to the leaving readers.
+const UPGRADE_SPINS: usize = 64;
+
+/// Unparks the blocked thread, when the lock wakes its locker.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+thread_local! {
+    static WAKER: Waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+}
+
+/// Polls the locker by the same algorithm as the lock futures, but parks the thread instead of returning to the runtime.
+/// The waiter lives on the stack of the blocked thread, so it isn't moved until the lock is acquired.
+fn block_on(mut poll: impl FnMut(Pin<&mut RawWaiter>, &mut Context<'_>) -> Poll<()>) {
+    let mut waiter = RawWaiter::default();
+    let mut waiter = unsafe { Pin::new_unchecked(&mut waiter) };
+    WAKER.with(|waker| {
+        let mut cx = Context::from_waker(waker);
+        // The unpark may be spurious, so the locker polls the lock again.
+        while poll(waiter.as_mut(), &mut cx).is_pending() {
+            thread::park();
+        }
+    })
+}
+
+unsafe impl<W: WaitStrategy> lock_api::RawMutex for RawMutex<W> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = <RawMutex<W> as RawAsyncMutex>::INIT;
+
+    type GuardMarker = GuardSend;
+
+    #[inline]
+    fn lock(&self) {
+        block_on(|waiter, cx| RawAsyncMutex::poll_lock(self, waiter, cx))
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        RawAsyncMutex::try_lock(self)
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        RawAsyncMutex::unlock(self)
+    }
+}
+
+unsafe impl<W: WaitStrategy> lock_api::RawRwLock for RawRwLock<W> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = <RawRwLock<W> as RawAsyncRwLock>::INIT;
+
+    type GuardMarker = GuardSend;
+
+    #[inline]
+    fn lock_shared(&self) {
+        block_on(|waiter, cx| self.poll_lock_shared(waiter, cx))
+    }
+
+    #[inline]
+    fn try_lock_shared(&self) -> bool {
+        RawAsyncRwLock::try_lock_shared(self)
+    }
+
+    #[inline]
+    unsafe fn unlock_shared(&self) {
+        RawAsyncRwLock::unlock_shared(self)
+    }
+
+    #[inline]
+    fn lock_exclusive(&self) {
+        block_on(|waiter, cx| self.poll_lock_exclusive(waiter, cx))
+    }
+
+    #[inline]
+    fn try_lock_exclusive(&self) -> bool {
+        RawAsyncRwLock::try_lock_exclusive(self)
+    }
+
+    #[inline]
+    unsafe fn unlock_exclusive(&self) {
+        RawAsyncRwLock::unlock_exclusive(self)
+    }
+}
+
+/// The upgradable lock is the read lock, whose holder also holds the `upgradable` mutex.
+unsafe impl<W: WaitStrategy> lock_api::RawRwLockUpgrade for RawRwLock<W> {
+    #[inline]
+    fn lock_upgradable(&self) {
+        lock_api::RawMutex::lock(&self.upgradable);
+        lock_api::RawRwLock::lock_shared(self);
+    }
+
+    #[inline]
+    fn try_lock_upgradable(&self) -> bool {
+        if !lock_api::RawMutex::try_lock(&self.upgradable) {
+            return false;
+        }
+        if RawAsyncRwLock::try_lock_shared(self) {
+            true
+        } else {
+            unsafe { RawAsyncMutex::unlock(&self.upgradable) };
+            false
+        }
+    }
+
+    #[inline]
+    unsafe fn unlock_upgradable(&self) {
+        RawAsyncRwLock::unlock_shared(self);
+        RawAsyncMutex::unlock(&self.upgradable)
+    }
+
+    /// Stops the new readers from joining and waits for the rest of the readers to leave.
+    /// The lock stays held by the readers all the time, so the writers can't come in between.
+    unsafe fn upgrade(&self) {
+        self.readers.count.fetch_or(UPGRADING, Ordering::AcqRel);
+
+        let mut spins = 0;
+        while self.readers.count.load(Ordering::Acquire) != UPGRADING | 1 {
+            if spins < UPGRADE_SPINS {
+                spins += 1;
+                std::hint::spin_loop();
+            } else {
+                thread::yield_now();
+            }
+        }
+
+        self.readers.count.store(0, Ordering::Release);
+        RawAsyncMutex::unlock(&self.upgradable)
+    }
+
+    unsafe fn try_upgrade(&self) -> bool {
+        let is_upgraded = self
+            .readers
+            .count
+            .compare_exchange(1, 0, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+        if is_upgraded {
+            RawAsyncMutex::unlock(&self.upgradable)
+        }
+        is_upgraded
+    }
+}
+
+unsafe impl<W: WaitStrategy> lock_api::RawRwLockDowngrade for RawRwLock<W> {
+    /// The writer becomes the first reader of the held lock, which starts the read phase, and the parked readers are woken to join it.
+    unsafe fn downgrade(&self) {
+        self.readers.start_phase();
+        if W::IS_PARKING {
+            self.queue.wake_oldest_shared();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{RawAsyncMutex, RawMutex, RawRwLock};
+    use crate::raw_mutex;
+    use crate::wait_strategy::{Adaptive, ImmediatePark, SpinThenPark};
+    use lock_api::{Mutex, RwLock, RwLockUpgradableReadGuard, RwLockWriteGuard};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn check_mutex<R: lock_api::RawMutex + Send + Sync + 'static>() {
+        let c = Arc::new(Mutex::<R, _>::new(0));
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let c = c.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        *c.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(*c.lock(), 8000);
+        let co = c.try_lock().unwrap();
+        assert!(c.try_lock().is_none());
+        drop(co);
+    }
+
+    fn check_rwlock<R: lock_api::RawRwLockUpgrade + Send + Sync + 'static>() {
+        let c = Arc::new(RwLock::<R, _>::new(0));
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let c = c.clone();
+                thread::spawn(move || {
+                    for _ in 0..500 {
+                        if i % 2 == 0 {
+                            *c.write() += 1;
+                        } else {
+                            let co = c.upgradable_read();
+                            let mut co = RwLockUpgradableReadGuard::upgrade(co);
+                            *co += 1;
+                        }
+                        assert!(*c.read() > 0);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(*c.read(), 4000);
+    }
+
+    #[test]
+    fn test_mutex() {
+        check_mutex::<RawMutex>();
+        check_mutex::<RawMutex<ImmediatePark>>();
+        check_mutex::<RawMutex<SpinThenPark>>();
+        check_mutex::<RawMutex<Adaptive>>();
+    }
+
+    #[test]
+    fn test_rwlock() {
+        check_rwlock::<RawRwLock>();
+        check_rwlock::<RawRwLock<ImmediatePark>>();
+        check_rwlock::<RawRwLock<Adaptive>>();
+    }
+
+    #[test]
+    fn test_upgrade_and_downgrade() {
+        let c: RwLock<RawRwLock<ImmediatePark>, _> = RwLock::new(0);
+
+        let co = c.upgradable_read();
+        let r = c.read();
+        assert!(c.try_upgradable_read().is_none());
+        assert!(c.try_write().is_none());
+        let co = RwLockUpgradableReadGuard::try_upgrade(co).unwrap_err();
+        drop(r);
+
+        let mut co = RwLockUpgradableReadGuard::try_upgrade(co).unwrap();
+        *co += 1;
+        assert!(c.try_read().is_none());
+        assert!(c.try_upgradable_read().is_none());
+
+        let co = RwLockWriteGuard::downgrade(co);
+        assert_eq!(*c.read(), 1);
+        assert!(c.try_upgradable_read().is_some());
+        assert!(c.try_write().is_none());
+        drop(co);
+        assert!(c.try_write().is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_shared_with_async() {
+        let c = Arc::new(raw_mutex::Mutex::<RawMutex<SpinThenPark>, _>::new(0));
+
+        let blocking = {
+            let c = c.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    let raw = unsafe { c.raw() };
+                    lock_api::RawMutex::lock(raw);
+                    *unsafe { &mut *c.data_ptr() } += 1;
+                    unsafe { RawAsyncMutex::unlock(raw) };
+                }
+            })
+        };
+        for _ in 0..1000 {
+            *c.lock().await += 1;
+        }
+        blocking.join().unwrap();
+
+        assert_eq!(*c.lock().await, 2000);
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+
+/// Pads and aligns the value to the length of the cache line, so it never shares the line with other values.
+///
+/// The spatial prefetcher of the modern x86_64 and aarch64 processors pulls the pairs of 64-byte lines, so the value is aligned to 128 bytes on them.
+/// It may be used to separate the data of the lock from its state, like `Mutex<CachePadded<T>>`,
+/// or the `padded` feature pads the contended state of the public locks of the crate.
+/// The internal spin locks, like the waker slots of the lock futures, aren't padded, so the futures stay small.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::cache_padded::CachePadded;
+/// use fast_async_mutex::mutex::Mutex;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mutex = Mutex::new(CachePadded::new(10));
+///     let mut guard = mutex.lock().await;
+///     **guard += 1;
+///     assert_eq!(**guard, 11);
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "powerpc64"
+    ),
+    repr(align(128))
+)]
+#[cfg_attr(
+    not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "powerpc64"
+    )),
+    repr(align(64))
+)]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+impl<T> CachePadded<T> {
+    /// Create a new `CachePadded`
+    #[inline]
+    pub const fn new(value: T) -> CachePadded<T> {
+        CachePadded { value }
+    }
+
+    /// Returns the padded value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<T> From<T> for CachePadded<T> {
+    fn from(value: T) -> Self {
+        CachePadded::new(value)
+    }
+}
+
+/// The hot atomic of the lock, which is padded with the `padded` feature.
+#[cfg(feature = "padded")]
+pub(crate) type Hot<T> = CachePadded<T>;
+
+#[cfg(not(feature = "padded"))]
+pub(crate) type Hot<T> = T;
+
+#[cfg(feature = "padded")]
+#[inline]
+pub(crate) const fn hot<T>(value: T) -> Hot<T> {
+    CachePadded::new(value)
+}
+
+#[cfg(not(feature = "padded"))]
+#[inline]
+pub(crate) const fn hot<T>(value: T) -> Hot<T> {
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache_padded::CachePadded;
+    use crate::inner::{HotInner, Inner};
+    use crate::mutex::MutexGuardFuture;
+    use std::mem::{align_of, size_of};
+
+    #[test]
+    fn test_layout() {
+        assert!(align_of::<CachePadded<u8>>() >= 64);
+        assert_eq!(size_of::<CachePadded<u8>>(), align_of::<CachePadded<u8>>());
+        assert_eq!(
+            size_of::<CachePadded<[u8; 200]>>() % align_of::<CachePadded<u8>>(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_padded_state() {
+        // The padded flag takes the whole line, so the data is placed on the next one.
+        if cfg!(feature = "padded") {
+            assert_eq!(size_of::<HotInner<u8>>(), 2 * align_of::<CachePadded<u8>>());
+        } else {
+            assert_eq!(size_of::<HotInner<u8>>(), 2);
+        }
+        // The spin locks of the bookkeeping aren't padded, so the lock futures stay small.
+        assert_eq!(size_of::<Inner<u8>>(), 2);
+        assert!(size_of::<MutexGuardFuture<u8>>() < 128);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::inner::Inner;
+use std::cell::UnsafeCell;
+use std::fmt::Debug;
+use std::marker::PhantomPinned;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+use std::task::Waker;
+
+/// The number of delegated operations, which the holder of the lock runs before the lock is released.
+pub(crate) const MAX_BATCH: usize = 64;
+
+/// The operation waits in the queue.
+const QUEUED: u8 = 0;
+/// The operation is taken by the holder of the lock, which runs it now.
+const RUNNING: u8 = 1;
+/// The result of the operation, or the panic of its closure, is stored.
+const DONE: u8 = 2;
+
+/// The operations, which were delegated to the holder of the lock, in the order of submission.
+///
+/// The queue is allocated by the first submission, so the lock, which nobody delegates to, keeps only the null pointer.
+/// The `Operation` nodes live inside the pinned delegating futures, so the submission itself doesn't allocate.
+/// The holder takes them from the head, while the submitter may withdraw its own operation from any place.
+#[derive(Debug)]
+pub(crate) struct Delegations<T: ?Sized> {
+    queue: AtomicPtr<Queue<T>>,
+}
+
+/// The chain of the submitted operations with its length, which is read without the lock to skip the empty queue.
+#[derive(Debug)]
+struct Queue<T: ?Sized> {
+    operations: Inner<Operations<T>>,
+    len: AtomicUsize,
+}
+
+/// The ends of the intrusive list of the operations and the operation, which is run now.
+#[derive(Debug)]
+struct Operations<T: ?Sized> {
+    head: *const Operation<T>,
+    tail: *const Operation<T>,
+    /// The operation, which is run by the holder of the lock now.
+    /// The dropped submitter resets it to mark the operation abandoned, so the holder no longer accesses it.
+    running: *const Operation<T>,
+}
+
+/// The type-erased header of the `Delegation`, which is linked into the queue.
+pub(crate) struct Operation<T: ?Sized> {
+    /// Runs the closure of the `Delegation` with the data of the lock and stores its result, unless it's abandoned.
+    run: unsafe fn(*const Operation<T>, &mut T, &Queue<T>),
+    state: AtomicU8,
+    node: UnsafeCell<Node<T>>,
+}
+
+/// The part of the `Operation`, which is accessed only under the lock of the list.
+struct Node<T: ?Sized> {
+    waker: Option<Waker>,
+    prev: *const Operation<T>,
+    next: *const Operation<T>,
+    is_linked: bool,
+}
+
+/// The closure, which is delegated to the holder of the lock, and its result.
+/// The operation is the first field, so the pointer to the operation is the pointer to the delegation.
+#[repr(C)]
+pub(crate) struct Delegation<T: ?Sized, F, R> {
+    operation: Operation<T>,
+    is_submitted: AtomicBool,
+    f: UnsafeCell<Option<F>>,
+    /// The result or the panic of the closure, which was run by another holder.
+    result: UnsafeCell<Option<std::thread::Result<R>>>,
+    _pinned: PhantomPinned,
+}
+
+/// The progress of the submitted delegation.
+#[derive(Debug)]
+pub(crate) enum Progress<R> {
+    Done(R),
+    /// The delegation is run by the holder of the lock now, so the submitter waits for the result.
+    Running,
+    /// The delegation waits in the queue, so the submitter may acquire the lock and run it itself.
+    Queued,
+}
+
+impl<T: ?Sized> Operation<T> {
+    /// The node must be accessed only under the lock of the list.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn node(&self) -> &mut Node<T> {
+        &mut *self.node.get()
+    }
+}
+
+impl<T: ?Sized> Debug for Operation<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Operation")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<T: ?Sized, F, R> Debug for Delegation<T, F, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Delegation")
+            .field("operation", &self.operation)
+            .field("is_submitted", &self.is_submitted)
+            .finish()
+    }
+}
+
+// The node is accessed only under the lock of the list, the closure is taken by one thread,
+// and the result is read only after it was stored.
+unsafe impl<T: ?Sized, F: Send, R: Send> Send for Delegation<T, F, R> {}
+unsafe impl<T: ?Sized, F: Send, R: Send> Sync for Delegation<T, F, R> {}
+
+impl<T: ?Sized> Operations<T> {
+    /// Links the operation to the end of the list.
+    unsafe fn push_back(&mut self, operation: &Operation<T>) {
+        let node = operation.node();
+        node.prev = self.tail;
+        node.next = ptr::null();
+        node.is_linked = true;
+        match self.tail.as_ref() {
+            Some(tail) => tail.node().next = operation,
+            None => self.head = operation,
+        }
+        self.tail = operation;
+    }
+
+    /// Unlinks the operation from the list.
+    unsafe fn unlink(&mut self, operation: &Operation<T>) {
+        let node = operation.node();
+        match node.prev.as_ref() {
+            Some(prev) => prev.node().next = node.next,
+            None => self.head = node.next,
+        }
+        match node.next.as_ref() {
+            Some(next) => next.node().prev = node.prev,
+            None => self.tail = node.prev,
+        }
+        node.prev = ptr::null();
+        node.next = ptr::null();
+        node.is_linked = false;
+    }
+}
+
+impl<T: ?Sized> Delegations<T> {
+    pub(crate) const fn new() -> Delegations<T> {
+        Delegations {
+            queue: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the queue, if anybody submitted an operation.
+    #[inline]
+    fn queue(&self) -> Option<&Queue<T>> {
+        unsafe { self.queue.load(Ordering::Acquire).as_ref() }
+    }
+
+    /// Returns the queue of the submitted operation, it's allocated by the submission.
+    #[inline]
+    fn submitted(&self) -> &Queue<T> {
+        self.queue()
+            .expect("the queue is allocated by the submission")
+    }
+
+    /// Allocates the queue, if it wasn't allocated yet. The queue of the concurrent submitter wins the race.
+    #[cold]
+    fn allocate(&self) -> &Queue<T> {
+        let queue = Box::into_raw(Box::new(Queue {
+            operations: Inner::new(Operations {
+                head: ptr::null(),
+                tail: ptr::null(),
+                running: ptr::null(),
+            }),
+            len: AtomicUsize::new(0),
+        }));
+        match self.queue.compare_exchange(
+            ptr::null_mut(),
+            queue,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => unsafe { &*queue },
+            Err(allocated) => unsafe {
+                drop(Box::from_raw(queue));
+                &*allocated
+            },
+        }
+    }
+
+    /// Returns `true` if the operations wait in the queue, so the lock, which nobody delegates to, checks only the null pointer.
+    #[inline]
+    pub(crate) fn is_queued(&self) -> bool {
+        self.queue()
+            .map_or(false, |queue| queue.len.load(Ordering::SeqCst) != 0)
+    }
+
+    /// Runs the queued operations by the holder of the lock, at most `MAX_BATCH` of them.
+    #[inline]
+    pub(crate) fn run_batch(&self, data: &mut T) {
+        if let Some(queue) = self.queue() {
+            queue.run(data, MAX_BATCH)
+        }
+    }
+
+    /// Runs all operations, which were queued before the call, by the holder of the lock in the order of submission.
+    #[inline]
+    pub(crate) fn run_pending(&self, data: &mut T) {
+        if let Some(queue) = self.queue() {
+            let len = queue.len.load(Ordering::SeqCst);
+            if len != 0 {
+                queue.run(data, len)
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for Delegations<T> {
+    fn drop(&mut self) {
+        let queue = *self.queue.get_mut();
+        if !queue.is_null() {
+            drop(unsafe { Box::from_raw(queue) });
+        }
+    }
+}
+
+impl<T: ?Sized> Queue<T> {
+    /// Runs at most `limit` queued operations.
+    /// Every submitter is woken, when the result of its operation is stored.
+    /// The panic of the operation is stored as its result, so it's resumed by its submitter instead of the holder.
+    fn run(&self, data: &mut T, limit: usize) {
+        for _ in 0..limit {
+            if self.len.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+
+            let operation = self.operations.spin_lock(|operations| unsafe {
+                let operation = operations.head.as_ref()?;
+                operations.unlink(operation);
+                self.len.fetch_sub(1, Ordering::SeqCst);
+                operation.state.store(RUNNING, Ordering::Relaxed);
+                operations.running = operation;
+                Some((operation as *const Operation<T>, operation.run))
+            });
+            let (operation, run) = match operation {
+                Some(operation) => operation,
+                None => return,
+            };
+
+            // The submitter may abandon the running operation, so it's accessed only under the lock of the list.
+            unsafe { run(operation, data, self) };
+        }
+    }
+}
+
+impl<T: ?Sized, F: FnOnce(&mut T) -> R + 'static, R: 'static> Delegation<T, F, R> {
+    // The closure and its result are `'static`, because the holder of the lock may run and drop them after the submitter is dropped.
+    #[inline]
+    pub(crate) fn new(f: F) -> Delegation<T, F, R> {
+        Delegation {
+            operation: Operation {
+                run: Self::run,
+                state: AtomicU8::new(QUEUED),
+                node: UnsafeCell::new(Node {
+                    waker: None,
+                    prev: ptr::null(),
+                    next: ptr::null(),
+                    is_linked: false,
+                }),
+            },
+            is_submitted: AtomicBool::new(false),
+            f: UnsafeCell::new(Some(f)),
+            result: UnsafeCell::new(None),
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Takes the closure and stores its result under the lock of the list, while the operation isn't abandoned.
+    /// The result of the abandoned operation is dropped by the holder of the lock.
+    unsafe fn run(operation: *const Operation<T>, data: &mut T, queue: &Queue<T>) {
+        let delegation = operation as *const Delegation<T, F, R>;
+        let f = queue.operations.spin_lock(|operations| {
+            if operations.running == operation {
+                (*(*delegation).f.get()).take()
+            } else {
+                None
+            }
+        });
+        let f = match f {
+            Some(f) => f,
+            None => return,
+        };
+
+        // The data is left as the panicked closure left it, like the data of the panicked holder.
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(data)));
+        let finished = queue.operations.spin_lock(|operations| {
+            if operations.running != operation {
+                return Err(result);
+            }
+            operations.running = ptr::null();
+            let delegation = &*delegation;
+            *delegation.result.get() = Some(result);
+            delegation.operation.state.store(DONE, Ordering::Release);
+            Ok(delegation.operation.node().waker.take())
+        });
+        match finished {
+            Ok(Some(waker)) => waker.wake(),
+            Ok(None) => {}
+            Err(abandoned) => drop(abandoned),
+        }
+    }
+
+    /// Runs the closure by the submitter itself. It must be called only by the holder of the lock,
+    /// and only if the delegation wasn't submitted or it was withdrawn.
+    #[inline]
+    pub(crate) fn run_by_holder(&self, data: &mut T) -> R {
+        let f = unsafe { (*self.f.get()).take() }.expect("the delegated operation is run twice");
+        f(data)
+    }
+}
+
+impl<T: ?Sized, F, R> Delegation<T, F, R> {
+    #[inline]
+    pub(crate) fn is_submitted(&self) -> bool {
+        self.is_submitted.load(Ordering::Relaxed)
+    }
+
+    /// Links the delegation to the queue, so the holder of the lock runs it.
+    /// The delegation is linked into the queue, so it must be pinned.
+    pub(crate) fn submit(&self, delegations: &Delegations<T>, waker: &Waker) {
+        let queue = match delegations.queue() {
+            Some(queue) => queue,
+            None => delegations.allocate(),
+        };
+        self.is_submitted.store(true, Ordering::Relaxed);
+        queue.operations.spin_lock(|operations| unsafe {
+            self.operation.node().waker = Some(waker.clone());
+            operations.push_back(&self.operation);
+            queue.len.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    /// Takes the result of the submitted delegation, or keeps the waker of the submitter up to date.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of the delegated closure, which was run by another holder.
+    pub(crate) fn poll(&self, delegations: &Delegations<T>, waker: &Waker) -> Progress<R> {
+        let state = delegations.submitted().operations.spin_lock(|_| unsafe {
+            let state = self.operation.state.load(Ordering::Acquire);
+            if state == QUEUED || state == RUNNING {
+                let node = self.operation.node();
+                if !matches!(&node.waker, Some(old) if old.will_wake(waker)) {
+                    node.waker = Some(waker.clone());
+                }
+            }
+            state
+        });
+
+        match state {
+            QUEUED => Progress::Queued,
+            RUNNING => Progress::Running,
+            _ => match unsafe { (*self.result.get()).take() } {
+                Some(Ok(result)) => Progress::Done(result),
+                Some(Err(panic)) => std::panic::resume_unwind(panic),
+                None => panic!("the delegated result is taken twice"),
+            },
+        }
+    }
+
+    /// Unlinks the queued delegation, so the holder of the lock can run it by `run_by_holder`.
+    /// Returns `false` if it was already run.
+    pub(crate) fn withdraw(&self, delegations: &Delegations<T>) -> bool {
+        let queue = delegations.submitted();
+        queue.operations.spin_lock(|operations| unsafe {
+            let node = self.operation.node();
+            if node.is_linked {
+                operations.unlink(&self.operation);
+                queue.len.fetch_sub(1, Ordering::SeqCst);
+                node.waker = None;
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Gives up the submitted delegation, so it can be dropped.
+    /// If it's running now, it's marked abandoned, so the holder of the lock drops its result instead of storing it.
+    pub(crate) fn cancel(&self, delegations: &Delegations<T>) {
+        if !self.is_submitted() {
+            return;
+        }
+
+        let queue = delegations.submitted();
+        queue.operations.spin_lock(|operations| unsafe {
+            if self.operation.node().is_linked {
+                operations.unlink(&self.operation);
+                queue.len.fetch_sub(1, Ordering::SeqCst);
+            } else if ptr::eq(operations.running, &self.operation) {
+                operations.running = ptr::null();
+            }
+        })
+    }
+}
@@ -1,55 +1,973 @@
+use crate::cache_padded::{hot, CachePadded, Hot};
+use crate::wait_strategy::WaitStrategy;
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::VecDeque;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
 use std::task::Waker;
+use std::time::{Duration, Instant};
 
+/// The spin lock over the data. The flag is plain for the bookkeeping of the lock internals,
+/// and `HotInner` pads it for the state of the public locks.
 #[derive(Debug)]
-pub(crate) struct Inner<T: ?Sized> {
-    is_acquired: AtomicBool,
+pub(crate) struct Inner<T: ?Sized, F = AtomicBool> {
+    is_acquired: F,
     pub(crate) data: UnsafeCell<T>,
 }
 
-impl<T> Inner<T> {
+/// The state of the public lock, whose flag is padded with the `padded` feature, so it doesn't share the line with the data.
+pub(crate) type HotInner<T> = Inner<T, Hot<AtomicBool>>;
+
+/// The flag of the `Inner`, which may be padded.
+pub(crate) trait Flag {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const RELEASED: Self;
+
+    fn flag(&self) -> &AtomicBool;
+}
+
+impl Flag for AtomicBool {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const RELEASED: Self = AtomicBool::new(false);
+
+    #[inline]
+    fn flag(&self) -> &AtomicBool {
+        self
+    }
+}
+
+impl Flag for CachePadded<AtomicBool> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const RELEASED: Self = CachePadded::new(AtomicBool::new(false));
+
+    #[inline]
+    fn flag(&self) -> &AtomicBool {
+        self
+    }
+}
+
+impl<T, F: Flag> Inner<T, F> {
     #[inline]
-    pub const fn new(data: T) -> Inner<T> {
+    pub const fn new(data: T) -> Inner<T, F> {
         Inner {
-            is_acquired: AtomicBool::new(false),
+            is_acquired: F::RELEASED,
             data: UnsafeCell::new(data),
         }
     }
 }
 
-impl<T: ?Sized> Inner<T> {
+impl<T: ?Sized, F: Flag> Inner<T, F> {
     #[inline]
     pub(crate) fn unlock(&self) {
-        self.is_acquired.store(false, Ordering::Release);
-    }
-
-    #[inline]
-    pub(crate) fn store_waker(&self, waker: &Waker) {
-        waker.wake_by_ref();
+        self.is_acquired.flag().store(false, Ordering::Release);
     }
 
     #[inline]
     pub(crate) fn try_acquire(&self) -> bool {
         self.is_acquired
+            .flag()
             .compare_exchange_weak(false, true, Ordering::AcqRel, Ordering::Relaxed)
             .is_ok()
     }
+
+    /// Spins until the lock is acquired and runs `f` with unique access to the data.
+    /// It should be used only for short synchronous sections, like a bookkeeping of the lock internals.
+    #[inline]
+    pub(crate) fn spin_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        while !self.try_acquire() {
+            std::hint::spin_loop();
+        }
+
+        let _unlock = SpinUnlock(self);
+        f(unsafe { &mut *self.data.get() })
+    }
+}
+
+struct SpinUnlock<'a, T: ?Sized, F: Flag>(&'a Inner<T, F>);
+
+impl<T: ?Sized, F: Flag> Drop for SpinUnlock<'_, T, F> {
+    fn drop(&mut self) {
+        self.0.unlock()
+    }
+}
+
+/// The number of acquisitions, which can bypass the oldest waiter of the `WaitQueue`.
+pub(crate) const MAX_BYPASS: usize = 16;
+
+/// The time, after which the oldest waiter of the `WaitQueue` is handed the lock on the next release.
+const MAX_WAIT: Duration = Duration::from_micros(500);
+
+/// The hold time, under which the adaptive locker spins before it's parked.
+const MAX_SPIN_HOLD: Duration = Duration::from_micros(4);
+
+/// The number of spin rounds of the adaptive locker, every round spins twice longer than the previous one.
+const ADAPTIVE_SPIN_ROUNDS: u32 = 8;
+
+/// The fixed point scale of the share of successful spins.
+const SPIN_SCALE: usize = 256;
+
+/// The share of successful spins out of `SPIN_SCALE`, under which the adaptive locker is parked right away.
+const MIN_SPIN_SUCCESS: usize = SPIN_SCALE / 4;
+
+/// Every `PROBE_INTERVAL`-th contended locker spins anyway, so the statistics follow the changed workload.
+const PROBE_INTERVAL: usize = 16;
+
+/// The moving averages of the hold time of the lock and of the successful spins of its lockers,
+/// by which the adaptive locker decides to spin or to be parked right away.
+#[derive(Debug)]
+pub(crate) struct HoldStats {
+    /// The time, when the lock was acquired. It's accessed only by the holder of the lock.
+    acquired_at: UnsafeCell<Option<Instant>>,
+    /// The average hold time in nanoseconds.
+    hold: AtomicUsize,
+    /// The average share of successful spins out of `SPIN_SCALE`.
+    spin_success: AtomicUsize,
+    contended: AtomicUsize,
+}
+
+impl HoldStats {
+    const fn new() -> HoldStats {
+        HoldStats {
+            acquired_at: UnsafeCell::new(None),
+            hold: AtomicUsize::new(0),
+            spin_success: AtomicUsize::new(SPIN_SCALE),
+            contended: AtomicUsize::new(0),
+        }
+    }
+
+    /// Remembers the start of the hold. It must be called only by the holder of the lock.
+    #[inline]
+    fn acquire(&self) {
+        unsafe { *self.acquired_at.get() = Some(Instant::now()) }
+    }
+
+    /// Adds the hold time to the average. It must be called only by the holder of the lock before the release.
+    /// The locks, which aren't acquired by the adaptive lockers, don't remember the start of the hold, so they skip it.
+    #[inline]
+    fn release(&self) {
+        if let Some(acquired_at) = unsafe { (*self.acquired_at.get()).take() } {
+            let hold = acquired_at.elapsed().as_nanos().min(usize::MAX as u128) as usize;
+            self.hold.store(
+                average(self.hold.load(Ordering::Relaxed), hold),
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    /// Returns `true` if the lock is held for a short time usually and the recent spins acquired it.
+    #[inline]
+    pub(crate) fn is_spin_worth(&self) -> bool {
+        self.hold.load(Ordering::Relaxed) <= MAX_SPIN_HOLD.as_nanos() as usize
+            && self.spin_success.load(Ordering::Relaxed) >= MIN_SPIN_SUCCESS
+    }
+
+    /// Spins, if it's worth, or if it's the turn of the probe.
+    /// The averages are updated by the concurrent lockers without synchronization, so some samples may be lost.
+    fn spin(&self, try_acquire: &mut impl FnMut() -> bool) -> bool {
+        let is_worth = self.is_spin_worth();
+        let is_probe = self.contended.fetch_add(1, Ordering::Relaxed) % PROBE_INTERVAL == 0;
+        if !is_worth && !is_probe {
+            return false;
+        }
+
+        let is_acquired = (0..ADAPTIVE_SPIN_ROUNDS).any(|round| {
+            for _ in 0..1 << round {
+                std::hint::spin_loop();
+            }
+            try_acquire()
+        });
+        let success = if is_acquired { SPIN_SCALE } else { 0 };
+        self.spin_success.store(
+            average(self.spin_success.load(Ordering::Relaxed), success),
+            Ordering::Relaxed,
+        );
+        is_acquired
+    }
+}
+
+/// The exponential moving average with the weight of the new sample `1/8`.
+#[inline]
+fn average(average: usize, sample: usize) -> usize {
+    average - average / 8 + sample / 8
+}
+
+/// The waiting lockers of the `Inner` lock in the order of arrival, which provides the eventual fairness.
+///
+/// The released lock can be acquired by any locker, but every acquisition while the oldest waiter waits is counted as its bypass.
+/// When the oldest waiter was bypassed `MAX_BYPASS` times or waits longer than `MAX_WAIT`,
+/// the next release hands the lock directly to it without releasing the flag.
+/// So the waiter with `n` waiters before it is bypassed at most `(n + 1) * MAX_BYPASS + n` times.
+///
+/// The queue is an intrusive list of the `WaitNode`s, which are owned by the queue and reused by the next waiters,
+/// so the waiting doesn't allocate, once the queue has as many nodes as lockers wait at once.
+/// The waiter is appended by the CAS on the link of the last one, and the lock is handed to the oldest waiter
+/// by the CAS on its state, so neither of them takes a lock.
+///
+/// The leaving waiter marks its link, unlinks the marked nodes on the way to the end of the list and retires its node.
+/// The traversals, which started before, may still read the retired node, so it's reused only when the epoch
+/// of the traversals is advanced twice after it, and the epoch is advanced only when the traversals of the previous one are finished.
+/// So neither leaving the queue nor dropping the lock future waits for other threads.
+/// The waker of every waiter is guarded by its own spin lock.
+#[derive(Debug)]
+pub(crate) struct WaitQueue {
+    /// The link to the oldest waiter.
+    head: AtomicUsize,
+    /// The hint to the newest waiter, or `0` if it has to be found from the head.
+    /// It's set only by the waiter, which linked its own node, and it's cleared before the node is retired.
+    tail: AtomicUsize,
+    len: Hot<AtomicUsize>,
+    /// The number of waiting exclusive lockers, so the shared lockers don't join the held lock past them forever.
+    exclusive_len: AtomicUsize,
+    /// The number of acquisitions, which bypassed the oldest waiter.
+    bypassed: AtomicUsize,
+    /// The epoch of the traversals, whose parity selects the counter of the started traversals.
+    epoch: AtomicUsize,
+    traversals: [AtomicUsize; 2],
+    /// The unlinked nodes, which may be still read by the traversals, with the epochs of their retirement.
+    retired: AtomicPtr<WaitNode>,
+    /// The nodes, which no traversal can read anymore, so they are reused by the new waiters.
+    free: Inner<*mut WaitNode>,
+    /// The hold times of the adaptive lockers. They are allocated by the first adaptive acquisition,
+    /// so the locks of other strategies keep only the pointer and don't observe the hold times.
+    stats: AtomicPtr<HoldStats>,
+}
+
+// The free nodes are accessed only under the spin lock, and the rest of the state is atomic.
+unsafe impl Send for WaitQueue {}
+unsafe impl Sync for WaitQueue {}
+
+/// The lowest bit of the link of the waiter, which marks the waiter as removed from the queue.
+const REMOVED: usize = 1;
+
+const WAITING: u8 = 1;
+/// The lock was handed to the waiter, which takes it on the next poll.
+const HANDED: u8 = 2;
+/// The waiter left the queue, but it may be still linked.
+const LEFT: u8 = 3;
+
+/// The node of the intrusive list of the `WaitQueue`, which is owned by the queue and taken by the waiter, while it waits.
+#[derive(Debug)]
+struct WaitNode {
+    /// The link to the next waiter, which is marked by `REMOVED`, when the waiter is removed.
+    next: AtomicUsize,
+    state: AtomicU8,
+    is_exclusive: AtomicBool,
+    /// The start of the waiting, which is written only before the node is linked.
+    since: UnsafeCell<Option<Instant>>,
+    /// The waker of the parked waiter, which is cloned by the unlockers under its spin lock.
+    waker: Inner<Option<Waker>>,
+    /// The next node in the list of the retired or the free nodes.
+    reclaimed: AtomicPtr<WaitNode>,
+    /// The epoch, in which the node was retired.
+    retired_at: AtomicUsize,
+}
+
+/// The state of the locker future, which waits in the `WaitQueue`.
+///
+/// The node of the queue is taken, when the locker starts waiting, and it's retired, when the locker leaves the queue,
+/// so the future isn't pinned by the queue, but it must leave the queue or cancel the waiting before it's dropped.
+#[derive(Debug)]
+pub(crate) struct Waiting {
+    /// The node of the waiter in the queue, or null if it doesn't wait.
+    node: AtomicPtr<WaitNode>,
+    attempt: AtomicUsize,
+    is_exclusive: AtomicBool,
+    /// The read phase of the RW Lock, in which the shared locker arrived, or `0` if it wasn't polled yet.
+    arrival: AtomicUsize,
+}
+
+impl Waiting {
+    #[inline]
+    pub(crate) const fn new() -> Waiting {
+        Waiting {
+            node: AtomicPtr::new(ptr::null_mut()),
+            attempt: AtomicUsize::new(0),
+            is_exclusive: AtomicBool::new(false),
+            arrival: AtomicUsize::new(0),
+        }
+    }
+
+    /// Marks the waiter as the exclusive locker, like the writer of the RW Lock, before it's linked into the queue.
+    #[inline]
+    pub(crate) fn mark_exclusive(&self) {
+        self.is_exclusive.store(true, Ordering::Relaxed)
+    }
+
+    /// Returns the read phase, in which the shared locker arrived, and remembers it on the first call.
+    /// The phases start from `1`, so the remembered phase is never `0`.
+    #[inline]
+    pub(crate) fn arrival(&self, phase: &AtomicUsize) -> usize {
+        match self.arrival.load(Ordering::Relaxed) {
+            0 => {
+                let arrival = phase.load(Ordering::SeqCst);
+                self.arrival.store(arrival, Ordering::Relaxed);
+                arrival
+            }
+            arrival => arrival,
+        }
+    }
+
+    /// Returns the node of the waiter. The node isn't reused, until the waiter retires it.
+    #[inline]
+    fn node(&self) -> Option<&WaitNode> {
+        unsafe { self.node.load(Ordering::Relaxed).as_ref() }
+    }
+}
+
+impl WaitNode {
+    #[inline]
+    fn is_waiting(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == WAITING
+    }
+
+    #[inline]
+    fn waker(&self) -> Option<Waker> {
+        self.waker.spin_lock(|waker| waker.clone())
+    }
+
+    #[inline]
+    fn link(&self) -> usize {
+        self as *const WaitNode as usize
+    }
+}
+
+/// Returns the node, which the link points to.
+///
+/// The link must be loaded in a traversal, which keeps the node from being reused.
+#[inline]
+unsafe fn waiter<'a>(link: usize) -> Option<&'a WaitNode> {
+    ((link & !REMOVED) as *const WaitNode).as_ref()
+}
+
+/// The traversal of the queue, while which no node, which it may read, is reused.
+struct Traversal<'a> {
+    started: &'a AtomicUsize,
+}
+
+impl Drop for Traversal<'_> {
+    fn drop(&mut self) {
+        self.started.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The way, in which the locker got the lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Acquisition {
+    /// The locker acquired the lock itself.
+    Acquired,
+    /// The lock was handed to the waiting locker without releasing, so the locker holds the flag of the lock.
+    HandedOff,
+    Pending,
+}
+
+impl WaitQueue {
+    pub(crate) const fn new() -> WaitQueue {
+        WaitQueue {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            len: hot(AtomicUsize::new(0)),
+            exclusive_len: AtomicUsize::new(0),
+            bypassed: AtomicUsize::new(0),
+            epoch: AtomicUsize::new(0),
+            traversals: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            retired: AtomicPtr::new(ptr::null_mut()),
+            free: Inner::new(ptr::null_mut()),
+            stats: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn stats(&self) -> Option<&HoldStats> {
+        unsafe { self.stats.load(Ordering::Acquire).as_ref() }
+    }
+
+    fn stats_or_init(&self) -> &HoldStats {
+        if let Some(stats) = self.stats() {
+            return stats;
+        }
+
+        let stats = Box::into_raw(Box::new(HoldStats::new()));
+        match self.stats.compare_exchange(
+            ptr::null_mut(),
+            stats,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => unsafe { &*stats },
+            Err(installed) => {
+                drop(unsafe { Box::from_raw(stats) });
+                unsafe { &*installed }
+            }
+        }
+    }
+
+    /// Acquires the lock by `try_acquire`, or takes it if it was handed to the waiter.
+    /// The waiter keeps its place in the queue until it acquires the lock.
+    ///
+    /// The parked waiter is registered before the last attempt, so the release after the attempt wakes it.
+    pub(crate) fn poll_acquire<W: WaitStrategy>(
+        &self,
+        waiting: &Waiting,
+        waker: &Waker,
+        mut try_acquire: impl FnMut() -> bool,
+    ) -> Acquisition {
+        if let Some(acquisition) = self.acquire::<W>(waiting, &mut try_acquire) {
+            return acquisition;
+        }
+
+        let attempt = waiting.attempt.load(Ordering::Relaxed);
+        let is_acquired = if W::IS_ADAPTIVE {
+            self.stats_or_init().spin(&mut try_acquire)
+        } else {
+            W::spin(attempt, &mut try_acquire)
+        };
+        if is_acquired {
+            self.leave(waiting);
+            return Acquisition::Acquired;
+        }
+
+        match waiting.node() {
+            None => self.push(waiting, waker),
+            Some(node) if W::IS_PARKING => self.update(node, waker),
+            Some(_) => {}
+        }
+
+        if W::IS_PARKING {
+            fence(Ordering::SeqCst);
+            if let Some(acquisition) = self.acquire::<W>(waiting, &mut try_acquire) {
+                return acquisition;
+            }
+        }
+
+        W::wait(attempt, waker);
+        waiting.attempt.store(attempt + 1, Ordering::Relaxed);
+        Acquisition::Pending
+    }
+
+    #[inline]
+    fn acquire<W: WaitStrategy>(
+        &self,
+        waiting: &Waiting,
+        try_acquire: &mut impl FnMut() -> bool,
+    ) -> Option<Acquisition> {
+        if waiting
+            .node()
+            .map_or(false, |node| node.state.load(Ordering::SeqCst) == HANDED)
+        {
+            self.unlink(waiting);
+            if W::IS_ADAPTIVE {
+                self.stats_or_init().acquire();
+            }
+            return Some(Acquisition::HandedOff);
+        }
+
+        if try_acquire() {
+            self.leave(waiting);
+            return Some(Acquisition::Acquired);
+        }
+        None
+    }
+
+    /// Acquires the flag of the lock and counts the acquisition as the bypass of the oldest waiter.
+    /// If the acquirer is the oldest waiter, the bypasses are reset, when it leaves the queue.
+    /// The adaptive locker remembers the start of the hold.
+    #[inline]
+    pub(crate) fn try_acquire<W: WaitStrategy, T: ?Sized>(&self, inner: &HotInner<T>) -> bool {
+        if inner.try_acquire() {
+            self.count_bypass();
+            if W::IS_ADAPTIVE {
+                self.stats_or_init().acquire();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Counts the acquisition of the lock, while other lockers wait.
+    #[inline]
+    pub(crate) fn count_bypass(&self) {
+        if self.len.load(Ordering::SeqCst) != 0 {
+            self.bypassed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Counts the shared locker, which joined the held lock in its own read phase, as the bypass, if an exclusive locker waits.
+    #[inline]
+    pub(crate) fn count_join(&self) {
+        if self.exclusive_len.load(Ordering::SeqCst) != 0 {
+            self.bypassed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns `true` if an exclusive locker waits and the oldest waiter was bypassed too many times,
+    /// so the shared lockers, which arrived in the current read phase, stop joining the held lock,
+    /// and it's handed to the oldest waiter on the release.
+    #[inline]
+    pub(crate) fn is_join_exhausted(&self) -> bool {
+        self.exclusive_len.load(Ordering::SeqCst) != 0
+            && self.bypassed.load(Ordering::SeqCst) >= MAX_BYPASS
+    }
+
+    /// Wakes the oldest waiter, so it tries to acquire the lock again.
+    #[inline]
+    pub(crate) fn wake_oldest(&self) {
+        if self.len.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        let traversal = self.traverse();
+        let waker = self
+            .find(&traversal, WaitNode::is_waiting)
+            .1
+            .and_then(WaitNode::waker);
+        drop(traversal);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Wakes the oldest shared locker, which may wait behind the exclusive ones,
+    /// so the parked readers, which arrived before the read phase, join it one by one.
+    #[inline]
+    pub(crate) fn wake_oldest_shared(&self) {
+        if self.len.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        let traversal = self.traverse();
+        let waker = self
+            .find(&traversal, |node| {
+                node.is_waiting() && !node.is_exclusive.load(Ordering::Relaxed)
+            })
+            .1
+            .and_then(WaitNode::waker);
+        drop(traversal);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Starts the traversal, which keeps the nodes, which it may read, from being reused.
+    /// The counter of the traversal is rechecked against the epoch, so `advance` doesn't miss it.
+    #[inline]
+    fn traverse(&self) -> Traversal<'_> {
+        loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let started = &self.traversals[epoch & 1];
+            started.fetch_add(1, Ordering::SeqCst);
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                return Traversal { started };
+            }
+            started.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Advances the epoch, if the traversals of the previous epoch are finished.
+    /// So the traversals, which were started in the epoch `e` or before, are finished, when the epoch is `e + 2`.
+    #[inline]
+    fn advance(&self) {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        if self.traversals[epoch.wrapping_add(1) & 1].load(Ordering::SeqCst) == 0 {
+            let _ = self.epoch.compare_exchange(
+                epoch,
+                epoch.wrapping_add(1),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+        }
+    }
+
+    /// Finds the oldest waiter, which satisfies `is_found`, and unlinks the removed waiters on the way.
+    /// Returns the link to the found waiter, or the link at the end of the list.
+    fn find<'a>(
+        &'a self,
+        _traversal: &'a Traversal<'_>,
+        mut is_found: impl FnMut(&WaitNode) -> bool,
+    ) -> (&'a AtomicUsize, Option<&'a WaitNode>) {
+        'retry: loop {
+            let mut link = &self.head;
+            let mut current = link.load(Ordering::SeqCst);
+            // The nodes, which are reachable in the traversal, aren't reused until it's finished.
+            while let Some(node) = unsafe { waiter(current) } {
+                let next = node.next.load(Ordering::SeqCst);
+                if next & REMOVED != 0 {
+                    // The link of the previous waiter isn't changed only if it isn't removed itself.
+                    if link
+                        .compare_exchange(
+                            current,
+                            next & !REMOVED,
+                            Ordering::SeqCst,
+                            Ordering::SeqCst,
+                        )
+                        .is_err()
+                    {
+                        continue 'retry;
+                    }
+                    current = next & !REMOVED;
+                    continue;
+                }
+
+                if is_found(node) {
+                    return (link, Some(node));
+                }
+                link = &node.next;
+                current = next;
+            }
+            return (link, None);
+        }
+    }
+
+    /// Takes a free node for the new waiter, or allocates it, if every node of the queue may be still read.
+    fn take_node(&self) -> &WaitNode {
+        let node = self.free.spin_lock(|free| {
+            if free.is_null() {
+                self.reclaim(free);
+            }
+            let node = *free;
+            if let Some(node) = unsafe { node.as_ref() } {
+                *free = node.reclaimed.load(Ordering::Relaxed);
+            }
+            node
+        });
+        match unsafe { node.as_ref() } {
+            Some(node) => node,
+            None => Box::leak(Box::new(WaitNode {
+                next: AtomicUsize::new(0),
+                state: AtomicU8::new(LEFT),
+                is_exclusive: AtomicBool::new(false),
+                since: UnsafeCell::new(None),
+                waker: Inner::new(None),
+                reclaimed: AtomicPtr::new(ptr::null_mut()),
+                retired_at: AtomicUsize::new(0),
+            })),
+        }
+    }
+
+    /// Moves the retired nodes, which no traversal can read anymore, to the free nodes.
+    /// The retired nodes are taken at once, so they are never popped concurrently.
+    #[cold]
+    fn reclaim(&self, free: &mut *mut WaitNode) {
+        self.advance();
+        self.advance();
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        let mut retired = self.retired.swap(ptr::null_mut(), Ordering::SeqCst);
+        while let Some(node) = unsafe { retired.as_ref() } {
+            retired = node.reclaimed.load(Ordering::Relaxed);
+            if epoch.wrapping_sub(node.retired_at.load(Ordering::Relaxed)) >= 2 {
+                node.reclaimed.store(*free, Ordering::Relaxed);
+                *free = node as *const WaitNode as *mut WaitNode;
+            } else {
+                self.push_retired(node);
+            }
+        }
+    }
+
+    /// Retires the unlinked node, so it's reused, when the traversals, which could read it, are finished.
+    fn retire(&self, node: &WaitNode) {
+        node.retired_at
+            .store(self.epoch.load(Ordering::SeqCst), Ordering::Relaxed);
+        self.push_retired(node);
+    }
+
+    #[inline]
+    fn push_retired(&self, node: &WaitNode) {
+        let link = node as *const WaitNode as *mut WaitNode;
+        let mut retired = self.retired.load(Ordering::SeqCst);
+        loop {
+            node.reclaimed.store(retired, Ordering::Relaxed);
+            match self.retired.compare_exchange_weak(
+                retired,
+                link,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return,
+                Err(actual) => retired = actual,
+            }
+        }
+    }
+
+    /// Appends the waiter to the queue by the CAS on the empty link of the last waiter.
+    fn push(&self, waiting: &Waiting, waker: &Waker) {
+        let waker = waker.clone();
+        let node = self.take_node();
+        node.next.store(0, Ordering::Relaxed);
+        let is_exclusive = waiting.is_exclusive.load(Ordering::Relaxed);
+        node.is_exclusive.store(is_exclusive, Ordering::Relaxed);
+        // The node isn't linked, so nobody else reads it.
+        unsafe { *node.since.get() = Some(Instant::now()) };
+        let old = node.waker.spin_lock(|slot| slot.replace(waker));
+        drop(old);
+        node.state.store(WAITING, Ordering::SeqCst);
+        waiting
+            .node
+            .store(node as *const WaitNode as *mut WaitNode, Ordering::Relaxed);
+        self.len.fetch_add(1, Ordering::SeqCst);
+        if is_exclusive {
+            self.exclusive_len.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let traversal = self.traverse();
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let link = match unsafe { waiter(tail) } {
+                Some(last) => &last.next,
+                None => self.find(&traversal, |_| false).0,
+            };
+            match link.compare_exchange(0, node.link(), Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => {
+                    let _ = self.tail.compare_exchange(
+                        tail,
+                        node.link(),
+                        Ordering::SeqCst,
+                        Ordering::Relaxed,
+                    );
+                    return;
+                }
+                // The hinted waiter isn't the last one, or it was removed, so the last one is found from the head.
+                // The hint isn't moved to the next waiter, because it may be retired meanwhile.
+                Err(_) if tail != 0 => {
+                    let _ =
+                        self.tail
+                            .compare_exchange(tail, 0, Ordering::SeqCst, Ordering::Relaxed);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Keeps the waker of the parked waiter up to date.
+    /// The new waker is cloned out of the spin lock, and only if the waiter was polled by another task.
+    fn update(&self, node: &WaitNode, waker: &Waker) {
+        if !node.is_waiting() {
+            return;
+        }
+
+        let is_same = node
+            .waker
+            .spin_lock(|slot| slot.as_ref().map_or(false, |old| old.will_wake(waker)));
+        if is_same {
+            return;
+        }
+
+        let waker = waker.clone();
+        let old = node.waker.spin_lock(|slot| slot.replace(waker));
+        // The old waker is dropped out of the spin lock.
+        drop(old);
+    }
+
+    /// Leaves the queue after the waiter acquired the lock itself.
+    #[inline]
+    fn leave(&self, waiting: &Waiting) {
+        if waiting.node().is_some() {
+            let is_handed = self.remove(waiting).0;
+            debug_assert!(
+                !is_handed,
+                "the lock was handed to the waiter, which acquired it"
+            );
+        }
+    }
+
+    /// Removes the waiter from the queue, unless the lock was handed to it, and unlinks it.
+    /// The next oldest waiter starts counting its bypasses from zero.
+    /// Returns whether the lock was handed to the waiter, and whether it was the oldest waiter.
+    fn remove(&self, waiting: &Waiting) -> (bool, bool) {
+        let node = match waiting.node() {
+            Some(node) => node,
+            None => return (false, false),
+        };
+
+        let traversal = self.traverse();
+        let oldest = self.find(&traversal, WaitNode::is_waiting).1;
+        let is_oldest = oldest.map_or(false, |oldest| ptr::eq(oldest, node));
+        drop(traversal);
+
+        let is_handed =
+            match node
+                .state
+                .compare_exchange(WAITING, LEFT, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => {
+                    self.uncount(node);
+                    if is_oldest {
+                        self.bypassed.store(0, Ordering::SeqCst);
+                    }
+                    false
+                }
+                Err(state) => state == HANDED,
+            };
+        self.unlink(waiting);
+        (is_handed, is_oldest && !is_handed)
+    }
+
+    #[inline]
+    fn uncount(&self, node: &WaitNode) {
+        self.len.fetch_sub(1, Ordering::SeqCst);
+        if node.is_exclusive.load(Ordering::Relaxed) {
+            self.exclusive_len.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Unlinks the node of the waiter, which isn't waiting anymore, and retires it.
+    ///
+    /// The pass to the end of the list unlinks every node, which was marked before it, so the node is unreachable
+    /// for the traversals, which start after it. The hint to the node is cleared before the retirement,
+    /// so only the traversals, which are counted by the epoch, can read the node.
+    fn unlink(&self, waiting: &Waiting) {
+        let node = match unsafe {
+            waiting
+                .node
+                .swap(ptr::null_mut(), Ordering::Relaxed)
+                .as_ref()
+        } {
+            Some(node) => node,
+            None => return,
+        };
+
+        node.next.fetch_or(REMOVED, Ordering::SeqCst);
+        let traversal = self.traverse();
+        self.find(&traversal, |_| false);
+        drop(traversal);
+
+        let _ = self
+            .tail
+            .compare_exchange(node.link(), 0, Ordering::SeqCst, Ordering::Relaxed);
+        let waker = node.waker.spin_lock(Option::take);
+        self.retire(node);
+        drop(waker);
+    }
+
+    /// Gives up the place in the queue, so the future can be dropped.
+    /// If the lock was already handed to the waiter, it's handed to the next one.
+    /// If the waiter was the oldest one, the next one is woken instead of it.
+    pub(crate) fn cancel<T: ?Sized>(&self, inner: &HotInner<T>, waiting: &Waiting) {
+        if waiting.node().is_none() {
+            return;
+        }
+
+        match self.remove(waiting) {
+            (true, _) => self.unlock_fair(inner),
+            (false, true) => self.wake_oldest(),
+            (false, false) => {}
+        }
+    }
+
+    /// Releases the lock, or hands it to the oldest waiter, if it was bypassed too many times or waits too long.
+    ///
+    /// The waiter is registered before its last attempt to acquire the lock,
+    /// so the queue is checked again after the release, and the new waiter is woken if it missed the release.
+    #[inline]
+    pub(crate) fn unlock<T: ?Sized>(&self, inner: &HotInner<T>) {
+        if let Some(stats) = self.stats() {
+            stats.release();
+        }
+        if self.len.load(Ordering::SeqCst) == 0 {
+            inner.unlock();
+            fence(Ordering::SeqCst);
+            self.wake_oldest();
+        } else {
+            self.unlock_slow(inner, false)
+        }
+    }
+
+    /// Hands the lock to the oldest waiter without releasing it, or releases it if there are no waiters.
+    #[inline]
+    pub(crate) fn unlock_fair<T: ?Sized>(&self, inner: &HotInner<T>) {
+        if let Some(stats) = self.stats() {
+            stats.release();
+        }
+        self.unlock_slow(inner, true)
+    }
+
+    /// The lock is handed to the oldest waiter by the CAS on its state, which races only with its cancellation.
+    /// If the waiter is gone, the next oldest one is tried. The handed waiter stays linked, until it takes the lock.
+    fn unlock_slow<T: ?Sized>(&self, inner: &HotInner<T>, is_fair: bool) {
+        let waker = loop {
+            let traversal = self.traverse();
+            let node = match self.find(&traversal, WaitNode::is_waiting).1 {
+                Some(node) => node,
+                None => {
+                    inner.unlock();
+                    drop(traversal);
+                    // A new waiter may be linked after the check.
+                    fence(Ordering::SeqCst);
+                    self.wake_oldest();
+                    return;
+                }
+            };
+
+            // The `since` isn't written, while the node is linked.
+            let since = unsafe { *node.since.get() };
+            let is_due = is_fair
+                || self.bypassed.load(Ordering::SeqCst) >= MAX_BYPASS
+                || since.map_or(false, |since| since.elapsed() >= MAX_WAIT);
+            if !is_due {
+                // The oldest waiter may be parked, so it's woken to try again.
+                inner.unlock();
+                break node.waker();
+            }
+
+            if node
+                .state
+                .compare_exchange(WAITING, HANDED, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                self.uncount(node);
+                self.bypassed.store(0, Ordering::SeqCst);
+                node.next.fetch_or(REMOVED, Ordering::SeqCst);
+                break node.waker();
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for WaitQueue {
+    /// Frees the retired and the free nodes. The nodes of the forgotten futures stay linked and leak.
+    fn drop(&mut self) {
+        for mut node in [*self.retired.get_mut(), *self.free.data.get_mut()] {
+            while !node.is_null() {
+                let mut freed = unsafe { Box::from_raw(node) };
+                node = *freed.reclaimed.get_mut();
+            }
+        }
+
+        let stats = *self.stats.get_mut();
+        if !stats.is_null() {
+            drop(unsafe { Box::from_raw(stats) });
+        }
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct OrderedInner<T: ?Sized> {
     pub(crate) state: AtomicUsize,
     pub(crate) current: AtomicUsize,
+    /// The cancelled tickets sorted by ids. It's grown outside of the spin lock, so the cancel doesn't allocate under it.
+    cancelled: Inner<VecDeque<usize>>,
+    cancelled_len: AtomicUsize,
+    wakers: WakerRing,
     pub(crate) data: UnsafeCell<T>,
 }
 
 impl<T> OrderedInner<T> {
     #[inline]
     pub const fn new(data: T) -> OrderedInner<T> {
+        OrderedInner::starting_at(data, 0)
+    }
+
+    #[inline]
+    pub const fn starting_at(data: T, id: usize) -> OrderedInner<T> {
         OrderedInner {
-            state: AtomicUsize::new(0),
-            current: AtomicUsize::new(0),
+            state: AtomicUsize::new(id),
+            current: AtomicUsize::new(id),
+            cancelled: Inner::new(VecDeque::new()),
+            cancelled_len: AtomicUsize::new(0),
+            wakers: WakerRing::new(),
             data: UnsafeCell::new(data),
         }
     }
@@ -63,16 +981,860 @@ impl<T: ?Sized> OrderedInner<T> {
 
     #[inline]
     pub(crate) fn unlock(&self) {
-        self.current.fetch_add(1, Ordering::Release);
+        self.current.fetch_add(1, Ordering::SeqCst);
+        if self.cancelled_len.load(Ordering::SeqCst) != 0 && self.skip_cancelled() {
+            self.wake_passed();
+        }
+        self.wake_current();
+    }
+
+    /// Gives up the ticket, which was not acquired yet.
+    /// If the ticket is current, the lock passes to the next ticket, otherwise the ticket is skipped when its turn comes.
+    /// The tickets, which were already passed, are ignored.
+    #[inline]
+    pub(crate) fn cancel(&self, id: usize) {
+        self.wakers.remove(id);
+        self.skip(id);
+    }
+
+    /// Skips the ticket like `cancel`, but the ticket may be still awaited,
+    /// so its waiters are woken, when the current ticket passes it.
+    pub(crate) fn skip(&self, id: usize) {
+        let mut is_skipped = false;
+        while !self.cancelled.spin_lock(|cancelled| {
+            if !is_ahead(self.current.load(Ordering::SeqCst), id) {
+                match cancelled.binary_search(&id) {
+                    Ok(_) => {}
+                    Err(_) if cancelled.len() == cancelled.capacity() => return false,
+                    Err(index) => {
+                        cancelled.insert(index, id);
+                        self.cancelled_len.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            }
+            is_skipped = self.skip_cancelled_locked(cancelled);
+            true
+        }) {
+            grow(&self.cancelled, WAKER_RING_SIZE);
+        }
+        if is_skipped {
+            self.wake_passed();
+        }
+        self.wake_current();
+    }
+
+    /// Passes the lock from the given ticket to the next one, unless the current ticket was already moved forward.
+    /// Returns `true` if the task of the new current ticket was woken.
+    #[inline]
+    pub(crate) fn unlock_from(&self, id: usize) -> bool {
+        let next = id.wrapping_add(1);
+        if self
+            .current
+            .compare_exchange(id, next, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+            || self.cancelled_len.load(Ordering::SeqCst) != 0
+        {
+            self.skip_to(next)
+        } else {
+            self.wakers.wake(next, next)
+        }
+    }
+
+    /// Moves the current ticket forward to the given one, forgetting the cancelled tickets before it.
+    /// Does nothing if the given ticket is not ahead of the current one.
+    /// Returns `true` if the task of the current ticket was woken.
+    pub(crate) fn skip_to(&self, id: usize) -> bool {
+        let is_skipped = self.cancelled.spin_lock(|cancelled| {
+            let current = self.current.load(Ordering::SeqCst);
+            let is_moved = is_ahead(id, current);
+            if is_moved {
+                self.current.store(id, Ordering::SeqCst);
+                cancelled.retain(|&cancelled| !is_ahead(id, cancelled));
+                self.cancelled_len.store(cancelled.len(), Ordering::SeqCst);
+            }
+            self.skip_cancelled_locked(cancelled) || is_moved
+        });
+        if is_skipped {
+            self.wake_passed();
+        }
+        self.wake_current()
+    }
+
+    /// Moves the current ticket forward while it is cancelled.
+    /// Returns `true` if any ticket was skipped.
+    fn skip_cancelled(&self) -> bool {
+        self.cancelled
+            .spin_lock(|cancelled| self.skip_cancelled_locked(cancelled))
+    }
+
+    /// The cancelled ticket is removed under the lock, so only one thread skips it.
+    fn skip_cancelled_locked(&self, cancelled: &mut VecDeque<usize>) -> bool {
+        let mut is_skipped = false;
+        while let Ok(index) = cancelled.binary_search(&self.current.load(Ordering::SeqCst)) {
+            cancelled.remove(index);
+            self.cancelled_len.fetch_sub(1, Ordering::SeqCst);
+            self.current.fetch_add(1, Ordering::SeqCst);
+            is_skipped = true;
+        }
+        is_skipped
+    }
+
+    /// Wakes the waiters of the skipped tickets, so they notice that their tickets were passed.
+    #[inline]
+    fn wake_passed(&self) {
+        self.wakers.wake_passed(self.current.load(Ordering::SeqCst))
+    }
+
+    /// Stores the waker of the waiter with the given key, which will be woken when the current ticket reaches its ticket.
+    #[inline]
+    pub(crate) fn store_waker(&self, id: usize, key: usize, waker: &Waker) {
+        self.wakers
+            .store_keyed(id, key, self.current.load(Ordering::SeqCst), waker)
+    }
+
+    /// Returns `true` if the task of the current ticket was woken.
+    #[inline]
+    pub(crate) fn wake_current(&self) -> bool {
+        let current = self.current.load(Ordering::SeqCst);
+        self.wakers.wake(current, current)
+    }
+
+    /// Wakes the waiting ticket, even if it's not current, so it checks its state again.
+    #[inline]
+    pub(crate) fn wake(&self, id: usize) {
+        self.wakers.wake(id, self.current.load(Ordering::SeqCst));
+    }
+
+    /// Wakes the closest waiting ticket, even if it's not current, so it notices the gap before it.
+    #[inline]
+    pub(crate) fn wake_closest(&self) {
+        self.wakers
+            .wake_closest(self.current.load(Ordering::SeqCst))
     }
 
+    /// Forgets the waker of the waiter with the given key, which doesn't wait anymore.
     #[inline]
-    pub(crate) fn store_waker(&self, waker: &Waker) {
-        waker.wake_by_ref();
+    pub(crate) fn remove_waker(&self, id: usize, key: usize) {
+        self.wakers.remove_keyed(id, key)
     }
 
     #[inline]
     pub(crate) fn try_acquire(&self, id: usize) -> bool {
         id == self.current.load(Ordering::Acquire)
     }
+
+    /// Tries to acquire the ticket, and stores the waker of the parked ticket if it's not its turn yet.
+    /// The ticket is checked again after the waker is stored, so the wake between them isn't lost.
+    #[inline]
+    pub(crate) fn poll_acquire<W: WaitStrategy>(
+        &self,
+        id: usize,
+        waker: &Waker,
+        attempt: &mut usize,
+    ) -> bool {
+        if self.try_acquire(id) || W::spin(*attempt, || self.try_acquire(id)) {
+            return true;
+        }
+
+        if W::IS_PARKING {
+            self.wakers
+                .store(id, self.current.load(Ordering::SeqCst), waker);
+            if self.try_acquire(id) {
+                return true;
+            }
+        }
+
+        W::wait(*attempt, waker);
+        *attempt += 1;
+        false
+    }
+}
+
+const WAKER_RING_SIZE: usize = 32;
+
+/// The waker of the waiting ticket.
+/// The tickets with the same id, like the duplicates of a sequence number, are told apart by their keys.
+#[derive(Debug)]
+struct TicketWaker {
+    id: usize,
+    key: usize,
+    waker: Waker,
+}
+
+type WakerSlot = Inner<Option<TicketWaker>>;
+
+type WakerSlots = [WakerSlot; WAKER_RING_SIZE];
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_WAKER_SLOT: WakerSlot = Inner::new(None);
+
+/// The wakers of the tasks, which wait for the same event, like the finished initialization of the cell.
+/// Every waiting future keeps its id, so it updates its waker in place and removes it, when it's dropped.
+/// The wakers are cloned and the set is grown outside of the spin lock, so the registration doesn't allocate under it.
+#[derive(Debug)]
+pub(crate) struct WakerSet {
+    wakers: Inner<VecDeque<(usize, Waker)>>,
+    next_id: AtomicUsize,
+    /// The number of registered wakers, so the event without waiters doesn't take the lock.
+    len: AtomicUsize,
+}
+
+// The wakers are accessed only under the lock of the set.
+unsafe impl Send for WakerSet {}
+unsafe impl Sync for WakerSet {}
+
+impl WakerSet {
+    pub(crate) const fn new() -> WakerSet {
+        WakerSet {
+            wakers: Inner::new(VecDeque::new()),
+            next_id: AtomicUsize::new(0),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Registers the waker, if `validate` returns `true` under the lock of the set.
+    /// The event is announced before `wake_all` takes the lock, so the validated waiter isn't missed.
+    pub(crate) fn register(
+        &self,
+        id: &mut Option<usize>,
+        waker: &Waker,
+        validate: impl FnOnce() -> bool,
+    ) -> bool {
+        let key = *id.get_or_insert_with(|| self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut validate = Some(validate);
+        // The replaced waker is dropped after the lock is released.
+        let mut waker = Some(waker.clone());
+        loop {
+            let is_registered = self.wakers.spin_lock(|wakers| {
+                let index = wakers.iter().position(|(waiter, _)| *waiter == key);
+                if index.is_none() && wakers.len() == wakers.capacity() {
+                    return None;
+                }
+
+                // The waiter is counted before it validates the event, and the event is announced before the count is checked,
+                // so either the waiter sees the event, or `wake_all` sees the waiter.
+                self.len.fetch_add(1, Ordering::SeqCst);
+                fence(Ordering::SeqCst);
+                let validate = validate.take().expect("the set is validated once");
+                if !validate() {
+                    self.len.store(wakers.len(), Ordering::Relaxed);
+                    return Some(false);
+                }
+
+                match index {
+                    Some(index) => {
+                        let old = &mut wakers[index].1;
+                        if let Some(new) = waker.as_mut().filter(|new| !old.will_wake(new)) {
+                            std::mem::swap(old, new);
+                        }
+                    }
+                    None => {
+                        wakers.push_back((key, waker.take().expect("the waker is stored once")))
+                    }
+                }
+                self.len.store(wakers.len(), Ordering::Relaxed);
+                Some(true)
+            });
+            match is_registered {
+                Some(is_registered) => return is_registered,
+                None => grow(&self.wakers, 4),
+            }
+        }
+    }
+
+    /// Removes the waker of the dropped waiter.
+    pub(crate) fn remove(&self, id: Option<usize>) {
+        if let Some(id) = id {
+            self.wakers.spin_lock(|wakers| {
+                wakers.retain(|(waiter, _)| *waiter != id);
+                self.len.store(wakers.len(), Ordering::Relaxed);
+            });
+        }
+    }
+
+    /// Wakes all registered waiters.
+    pub(crate) fn wake_all(&self) {
+        fence(Ordering::SeqCst);
+        if self.len.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        let wakers = self.wakers.spin_lock(|wakers| {
+            self.len.store(0, Ordering::Relaxed);
+            std::mem::take(wakers)
+        });
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// The wakers of the waiting tickets, so the unlock wakes only the task of the next ticket.
+///
+/// The ticket is stored in the slot of the ring by its id, and the slot keeps the closest ticket.
+/// Other tickets with the same slot are stored in the `Overflow` table, which is checked only when it's not empty.
+/// The `current` is the lowest ticket, which may still wait. The wakers of the tickets before it are woken
+/// rather than forgotten, because a passed ticket, like a skipped sequence number, may still be awaited.
+///
+/// The slots are allocated by the first parked ticket, so the uncontended lock keeps only the pointer to them.
+/// The allocations never happen under the spin locks, the full overflow is grown outside of them and the store is retried.
+#[derive(Debug)]
+pub(crate) struct WakerRing {
+    slots: AtomicPtr<WakerSlots>,
+    overflow: Inner<Overflow>,
+    overflow_len: AtomicUsize,
+}
+
+/// The tickets, which didn't fit into the slots of the `WakerRing`, in the hash table with the linear probing,
+/// so the tickets are found, stored and removed without shifting the other ones.
+/// The ids are scattered by the Fibonacci hashing, so the consecutive tickets don't form the long probes.
+/// The waiters of the same ticket are told apart by their keys, and they are probed from the same entry.
+#[derive(Debug)]
+struct Overflow {
+    /// The entries, whose number is the power of two, or zero until the first ticket is stored.
+    entries: Vec<Option<TicketWaker>>,
+    len: usize,
+    /// The lower bound of the stored ids, so the passed tickets are searched only if it's before the current ticket.
+    oldest: usize,
+}
+
+impl Overflow {
+    #[inline]
+    fn home(&self, id: usize) -> usize {
+        const FIBONACCI: u64 = 0x9E37_79B9_7F4A_7C15;
+        let bits = self.entries.len().trailing_zeros();
+        id.wrapping_mul(FIBONACCI as usize) >> (usize::BITS - bits)
+    }
+
+    #[inline]
+    fn next(&self, index: usize) -> usize {
+        (index + 1) & (self.entries.len() - 1)
+    }
+
+    /// Returns the entry of the waiter with the given key, or of any waiter of the ticket.
+    fn find(&self, id: usize, key: Option<usize>) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let mut index = self.home(id);
+        while let Some(stored) = &self.entries[index] {
+            if stored.id == id && key.map_or(true, |key| stored.key == key) {
+                return Some(index);
+            }
+            index = self.next(index);
+        }
+        None
+    }
+
+    /// The table is kept at most half full, so the probes stay short.
+    #[inline]
+    fn is_full(&self) -> bool {
+        (self.len + 1) * 2 > self.entries.len()
+    }
+
+    fn insert(&mut self, ticket: TicketWaker) {
+        debug_assert!(!self.is_full(), "the overflow is grown before the insert");
+        if self.len == 0 || is_ahead(self.oldest, ticket.id) {
+            self.oldest = ticket.id;
+        }
+        let mut index = self.home(ticket.id);
+        while self.entries[index].is_some() {
+            index = self.next(index);
+        }
+        self.entries[index] = Some(ticket);
+        self.len += 1;
+    }
+
+    /// Removes the entry, and shifts the following entries of its probe back into the gap.
+    /// The removed oldest ticket moves the lower bound of the ids, so the tickets, which are woken in order, don't search the passed ones.
+    fn remove(&mut self, index: usize) -> Option<TicketWaker> {
+        let removed = self.entries[index].take()?;
+        self.len -= 1;
+        self.shift_back(index);
+        if removed.id == self.oldest && self.find(removed.id, None).is_none() {
+            self.oldest = removed.id.wrapping_add(1);
+        }
+        Some(removed)
+    }
+
+    fn shift_back(&mut self, index: usize) {
+        let mut gap = index;
+        let mut index = self.next(index);
+        while let Some(stored) = &self.entries[index] {
+            // The entry is moved, unless its home is between the gap and the entry.
+            let home = self.home(stored.id);
+            if index.wrapping_sub(home) & (self.entries.len() - 1)
+                >= index.wrapping_sub(gap) & (self.entries.len() - 1)
+            {
+                self.entries[gap] = self.entries[index].take();
+                gap = index;
+            }
+            index = self.next(index);
+        }
+    }
+
+    /// Returns the entry of a ticket before the `current` one.
+    /// If there is none, the lower bound of the ids is moved to the oldest stored ticket, so the next tickets don't search again.
+    fn find_passed(&mut self, current: usize) -> Option<usize> {
+        if self.len == 0 || !is_ahead(current, self.oldest) {
+            return None;
+        }
+
+        let mut oldest = None;
+        for (index, stored) in self.entries.iter().enumerate() {
+            if let Some(stored) = stored {
+                if is_ahead(current, stored.id) {
+                    return Some(index);
+                }
+                if oldest.map_or(true, |oldest| is_ahead(oldest, stored.id)) {
+                    oldest = Some(stored.id);
+                }
+            }
+        }
+        self.oldest = oldest.unwrap_or(current);
+        None
+    }
+}
+
+impl WakerRing {
+    pub(crate) const fn new() -> WakerRing {
+        WakerRing {
+            slots: AtomicPtr::new(ptr::null_mut()),
+            overflow: Inner::new(Overflow {
+                entries: Vec::new(),
+                len: 0,
+                oldest: 0,
+            }),
+            overflow_len: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn slots(&self) -> Option<&WakerSlots> {
+        unsafe { self.slots.load(Ordering::Acquire).as_ref() }
+    }
+
+    fn slots_or_init(&self) -> &WakerSlots {
+        if let Some(slots) = self.slots() {
+            return slots;
+        }
+
+        let slots = Box::into_raw(Box::new([EMPTY_WAKER_SLOT; WAKER_RING_SIZE]));
+        match self.slots.compare_exchange(
+            ptr::null_mut(),
+            slots,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => unsafe { &*slots },
+            Err(installed) => {
+                drop(unsafe { Box::from_raw(slots) });
+                unsafe { &*installed }
+            }
+        }
+    }
+
+    /// Stores the waker of the unique ticket.
+    #[inline]
+    pub(crate) fn store(&self, id: usize, current: usize, waker: &Waker) {
+        self.store_keyed(id, 0, current, waker)
+    }
+
+    /// Stores the waker of the ticket, which may be awaited by several waiters with different keys.
+    pub(crate) fn store_keyed(&self, id: usize, key: usize, current: usize, waker: &Waker) {
+        let slot = &self.slots_or_init()[id % WAKER_RING_SIZE];
+        while !slot.spin_lock(|slot| match slot {
+            Some(stored) if stored.id == id && stored.key == key => {
+                if !stored.waker.will_wake(waker) {
+                    stored.waker = waker.clone();
+                }
+                true
+            }
+            // The slot keeps the closest ticket, and the passed ticket is moved out too, so it's woken by the next wake.
+            Some(stored) if is_ahead(stored.id, id) || is_ahead(current, stored.id) => {
+                // The overflow is changed under the slot lock, so the wake of the displaced ticket isn't lost.
+                if !self.store_overflow(stored.id, stored.key, &stored.waker) {
+                    return false;
+                }
+                *slot = Some(TicketWaker {
+                    id,
+                    key,
+                    waker: waker.clone(),
+                });
+                true
+            }
+            Some(_) => self.store_overflow(id, key, waker),
+            None => {
+                *slot = Some(TicketWaker {
+                    id,
+                    key,
+                    waker: waker.clone(),
+                });
+                true
+            }
+        }) {
+            self.grow_overflow();
+        }
+    }
+
+    /// Doubles the entries of the overflow, or allocates `WAKER_RING_SIZE` entries at first.
+    /// The entries are allocated and the old ones are freed outside of the spin lock, only the stored tickets are moved under it.
+    #[cold]
+    fn grow_overflow(&self) {
+        let capacity = self.overflow.spin_lock(|overflow| overflow.entries.len());
+        let mut entries: Vec<Option<TicketWaker>> = std::iter::repeat_with(|| None)
+            .take((capacity * 2).max(WAKER_RING_SIZE))
+            .collect();
+        self.overflow.spin_lock(|overflow| {
+            // The overflow could be grown by another thread meanwhile.
+            if overflow.entries.len() == capacity {
+                std::mem::swap(&mut overflow.entries, &mut entries);
+                overflow.len = 0;
+                for stored in entries.iter_mut().filter_map(Option::take) {
+                    overflow.insert(stored);
+                }
+            }
+        });
+    }
+
+    /// Returns `false` if the overflow is full, so it must be grown before the ticket is stored.
+    fn store_overflow(&self, id: usize, key: usize, waker: &Waker) -> bool {
+        self.overflow.spin_lock(|overflow| {
+            match overflow.find(id, Some(key)) {
+                Some(index) => {
+                    let stored = overflow.entries[index]
+                        .as_mut()
+                        .expect("the found entry is stored");
+                    if !stored.waker.will_wake(waker) {
+                        stored.waker = waker.clone();
+                    }
+                }
+                None if overflow.is_full() => return false,
+                None => overflow.insert(TicketWaker {
+                    id,
+                    key,
+                    waker: waker.clone(),
+                }),
+            }
+            self.overflow_len.store(overflow.len, Ordering::SeqCst);
+            true
+        })
+    }
+
+    /// Wakes all waiters of the ticket and the passed tickets of the overflow.
+    /// Returns `true` if a waker of the ticket was found.
+    pub(crate) fn wake(&self, id: usize, current: usize) -> bool {
+        let waker = self.slots().and_then(|slots| {
+            slots[id % WAKER_RING_SIZE].spin_lock(|slot| match slot {
+                Some(stored) if stored.id == id => slot.take().map(|stored| stored.waker),
+                _ => None,
+            })
+        });
+        let mut is_found = waker.is_some();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        while let Some(taken) = self.take_overflow(Some(id), current) {
+            is_found |= taken.id == id;
+            taken.waker.wake();
+        }
+        is_found
+    }
+
+    /// Wakes the stored tickets before the `current` one, which were passed without being acquired.
+    pub(crate) fn wake_passed(&self, current: usize) {
+        for slot in self.slots().into_iter().flatten() {
+            let waker = slot.spin_lock(|slot| match slot {
+                Some(stored) if is_ahead(current, stored.id) => {
+                    slot.take().map(|stored| stored.waker)
+                }
+                _ => None,
+            });
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+
+        while let Some(taken) = self.take_overflow(None, current) {
+            taken.waker.wake();
+        }
+    }
+
+    /// Takes the first passed ticket of the overflow, or else a waiter of the given ticket.
+    /// The tickets are taken one by one, so they are woken outside of the spin lock without allocating.
+    fn take_overflow(&self, id: Option<usize>, current: usize) -> Option<TicketWaker> {
+        if self.overflow_len.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+
+        self.overflow.spin_lock(|overflow| {
+            let index = overflow
+                .find_passed(current)
+                .or_else(|| id.and_then(|id| overflow.find(id, None)));
+            let taken = index.and_then(|index| overflow.remove(index));
+            self.overflow_len.store(overflow.len, Ordering::SeqCst);
+            taken
+        })
+    }
+
+    /// Wakes the closest stored ticket, which is not before the `current` one.
+    pub(crate) fn wake_closest(&self, current: usize) {
+        let distance = |id: usize| id.wrapping_sub(current);
+        let mut closest: Option<usize> = None;
+        for slot in self.slots().into_iter().flatten() {
+            slot.spin_lock(|slot| {
+                if let Some(stored) = slot {
+                    if !is_ahead(current, stored.id)
+                        && closest.map_or(true, |closest| distance(stored.id) < distance(closest))
+                    {
+                        closest = Some(stored.id);
+                    }
+                }
+            });
+        }
+        if self.overflow_len.load(Ordering::SeqCst) != 0 {
+            self.overflow.spin_lock(|overflow| {
+                let first = overflow
+                    .entries
+                    .iter()
+                    .flatten()
+                    .map(|stored| stored.id)
+                    .filter(|&id| !is_ahead(current, id))
+                    .min_by_key(|&id| distance(id));
+                if let Some(id) = first {
+                    if closest.map_or(true, |closest| distance(id) < distance(closest)) {
+                        closest = Some(id);
+                    }
+                }
+            });
+        }
+        if let Some(id) = closest {
+            self.wake(id, current);
+        }
+    }
+
+    /// Forgets the waker of the unique ticket.
+    #[inline]
+    pub(crate) fn remove(&self, id: usize) {
+        self.remove_keyed(id, 0)
+    }
+
+    /// Forgets the waker of the waiter with the given key, other waiters of the same ticket are kept.
+    pub(crate) fn remove_keyed(&self, id: usize, key: usize) {
+        if let Some(slots) = self.slots() {
+            let removed = slots[id % WAKER_RING_SIZE].spin_lock(|slot| {
+                if matches!(slot, Some(stored) if stored.id == id && stored.key == key) {
+                    slot.take()
+                } else {
+                    None
+                }
+            });
+            drop(removed);
+        }
+
+        if self.overflow_len.load(Ordering::SeqCst) != 0 {
+            let removed = self.overflow.spin_lock(|overflow| {
+                let removed = overflow
+                    .find(id, Some(key))
+                    .and_then(|index| overflow.remove(index));
+                self.overflow_len.store(overflow.len, Ordering::SeqCst);
+                removed
+            });
+            drop(removed);
+        }
+    }
+}
+
+impl Drop for WakerRing {
+    fn drop(&mut self) {
+        let slots = *self.slots.get_mut();
+        if !slots.is_null() {
+            drop(unsafe { Box::from_raw(slots) });
+        }
+    }
+}
+
+/// Doubles the capacity of the queue, which is guarded by the spin lock, or allocates `min` items at first.
+/// The new queue is allocated and the old one is freed outside of the lock.
+#[cold]
+pub(crate) fn grow<T>(queue: &Inner<VecDeque<T>>, min: usize) {
+    grow_field(queue, |queue| queue, min)
+}
+
+/// Grows the queue like `grow`, when the queue is a field of the state, which is guarded by the spin lock.
+#[cold]
+pub(crate) fn grow_field<S, T>(
+    state: &Inner<S>,
+    field: impl Fn(&mut S) -> &mut VecDeque<T>,
+    min: usize,
+) {
+    let capacity = state.spin_lock(|state| field(state).capacity());
+    let mut grown = VecDeque::with_capacity((capacity * 2).max(min));
+    state.spin_lock(|state| {
+        let queue = field(state);
+        // The queue could be grown by another thread meanwhile.
+        if queue.capacity() == capacity {
+            grown.extend(queue.drain(..));
+            std::mem::swap(queue, &mut grown);
+        }
+    });
+}
+
+/// Returns `true` if the `id` is after the `current` one, taking into account the overflow of ids.
+#[inline]
+pub(crate) fn is_ahead(id: usize, current: usize) -> bool {
+    (id.wrapping_sub(current) as isize) > 0
+}
+
+/// Compares the ids like `is_ahead`, so the waiting tickets keep their order, when the ids wrap around `usize::MAX`.
+#[inline]
+pub(crate) fn cmp_ids(id: usize, other: usize) -> std::cmp::Ordering {
+    (id.wrapping_sub(other) as isize).cmp(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inner::{WakerRing, WakerSet, WAKER_RING_SIZE};
+    use futures::task::{waker, ArcWake};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::Waker;
+
+    struct FlagWaker(AtomicBool);
+
+    impl ArcWake for FlagWaker {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn flag_waker() -> (Arc<FlagWaker>, Waker) {
+        let flag = Arc::new(FlagWaker(AtomicBool::new(false)));
+        (flag.clone(), waker(flag))
+    }
+
+    #[test]
+    fn test_waker_ring_wrapped_ids() {
+        let ring = WakerRing::new();
+        let current = usize::MAX - WAKER_RING_SIZE;
+        // The tickets share the slot, and the last one wraps around `usize::MAX`.
+        let ids = [
+            current,
+            current + WAKER_RING_SIZE,
+            current.wrapping_add(2 * WAKER_RING_SIZE),
+        ];
+        let flags: Vec<_> = ids
+            .iter()
+            .rev()
+            .map(|&id| {
+                let (flag, waker) = flag_waker();
+                ring.store(id, current, &waker);
+                flag
+            })
+            .collect();
+        assert_eq!(ring.overflow_len.load(Ordering::SeqCst), 2);
+
+        // The second ticket was passed without waking, so it's woken with the third one, rather than forgotten.
+        assert!(ring.wake(ids[2], ids[2]));
+        assert!(flags[0].0.load(Ordering::SeqCst));
+        assert!(flags[1].0.load(Ordering::SeqCst));
+        assert_eq!(ring.overflow_len.load(Ordering::SeqCst), 0);
+
+        let ring = WakerRing::new();
+        let flags: Vec<_> = ids
+            .iter()
+            .rev()
+            .map(|&id| {
+                let (flag, waker) = flag_waker();
+                ring.store(id, current, &waker);
+                flag
+            })
+            .collect();
+        for (&id, flag) in ids.iter().zip(flags.iter().rev()) {
+            ring.wake_closest(id);
+            assert!(flag.0.load(Ordering::SeqCst));
+        }
+        assert_eq!(ring.overflow_len.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_waker_ring_overflow_growth() {
+        let ring = WakerRing::new();
+        let current = usize::MAX - 100;
+        let ids: Vec<_> = (0..1000).map(|i| current.wrapping_add(i)).collect();
+        // The overflow is grown several times, and the forgotten tickets leave the gaps in its probes.
+        let flags: Vec<_> = ids
+            .iter()
+            .rev()
+            .map(|&id| {
+                let (flag, waker) = flag_waker();
+                ring.store(id, current, &waker);
+                flag
+            })
+            .collect();
+        for &id in ids.iter().step_by(3) {
+            ring.remove(id);
+        }
+
+        for ((i, &id), flag) in ids.iter().enumerate().zip(flags.iter().rev()) {
+            assert_eq!(ring.wake(id, id), i % 3 != 0);
+            assert_eq!(flag.0.load(Ordering::SeqCst), i % 3 != 0);
+        }
+        assert_eq!(ring.overflow_len.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_waker_ring_duplicates() {
+        let ring = WakerRing::new();
+        let (first, first_waker) = flag_waker();
+        let (second, second_waker) = flag_waker();
+        let (removed, removed_waker) = flag_waker();
+        ring.store_keyed(1, 0, 0, &first_waker);
+        ring.store_keyed(1, 1, 0, &second_waker);
+        ring.store_keyed(1, 2, 0, &removed_waker);
+        ring.remove_keyed(1, 2);
+
+        // Every waiter of the ticket is woken, except the removed one.
+        assert!(ring.wake(1, 1));
+        assert!(first.0.load(Ordering::SeqCst));
+        assert!(second.0.load(Ordering::SeqCst));
+        assert!(!removed.0.load(Ordering::SeqCst));
+        assert_eq!(ring.overflow_len.load(Ordering::SeqCst), 0);
+
+        let (passed, passed_waker) = flag_waker();
+        ring.store_keyed(1, 0, 1, &passed_waker);
+        ring.wake_passed(2);
+        assert!(passed.0.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_waker_set_growth() {
+        let set = WakerSet::new();
+        let mut ids = [None; 10];
+        let flags: Vec<_> = ids
+            .iter_mut()
+            .map(|id| {
+                let (flag, waker) = flag_waker();
+                assert!(set.register(id, &waker, || true));
+                flag
+            })
+            .collect();
+        assert_eq!(set.len.load(Ordering::SeqCst), ids.len());
+
+        // The waiter updates its waker in place.
+        let (updated, waker) = flag_waker();
+        assert!(set.register(&mut ids[0], &waker, || true));
+        assert!(!set.register(&mut ids[1], &waker, || false));
+        assert_eq!(set.len.load(Ordering::SeqCst), ids.len());
+
+        set.remove(ids[2]);
+        set.wake_all();
+        assert!(updated.0.load(Ordering::SeqCst));
+        assert!(!flags[0].0.load(Ordering::SeqCst));
+        assert!(!flags[2].0.load(Ordering::SeqCst));
+        assert!(flags[3..].iter().all(|flag| flag.0.load(Ordering::SeqCst)));
+        assert_eq!(set.len.load(Ordering::SeqCst), 0);
+    }
 }
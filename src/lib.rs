@@ -1,25 +1,103 @@
 //! `fast_async_mutex` it is a lib which provide asynchronous locking mechanisms, which used spinlock algorithm.
 //! It's maybe very efficient because when mutex tries to acquire data unsuccessfully, these returning control to an async runtime back.
-//! This lib built only on atomics and don't use others std synchronous data structures, which make this lib so fast.
+//! This lib is built on atomics and its own spin locks, which guard only the short bookkeeping of the waiters, so the async locks don't block the threads of the runtime.
+//! Only the blocking locks of the `lock_api` feature park the calling thread by std.
 
 /// The simple Mutex, which will provide unique access to you data between multiple threads/futures.
+/// The released mutex can be acquired by any locker, until the oldest waiter was bypassed too many times, then it's handed to the waiter.
+/// The fair unlock hands the mutex directly to the oldest waiter, instead of letting any locker acquire it.
+/// The waiters are queued by the nodes, which the queue reuses, so waiting for the mutex doesn't allocate once the queue has grown.
+/// The tiny critical sections may be delegated to the holder of the mutex, which runs them in a batch on its own core.
 pub mod mutex;
 
+/// The Compact Mutex keeps only one byte of the state, and its waiting lockers are parked in the global table keyed by the address of the mutex.
+/// It fits for the millions of small locks, which are rarely contended.
+pub mod mutex_compact;
+
+/// The Cohort Mutex hands the released mutex to the waiting lockers of the same group as the holder, like the same CPU socket, up to a bound.
+/// It decreases the traffic between the caches of the sockets on the big boxes.
+pub mod mutex_cohort;
+
 /// The Ordered Mutex has its mechanism of locking order when you have concurrent access to data.
 /// It will work well when you needed step by step data locking like sending UDP packages in a specific order.
 pub mod mutex_ordered;
 
+/// The Sequenced Mutex is acquired in the order of the sequence numbers, which are passed by the callers, like sequence numbers of UDP packages.
+/// The missing sequence numbers may be skipped manually or by the gap timeout.
+pub mod mutex_sequenced;
+
 /// The RW Lock mechanism accepts you get concurrent shared access to your data without waiting.
 /// And get unique access with locks like a Mutex.
+/// The waiting writer is handed the lock, when it was bypassed too many times.
 pub mod rwlock;
 
 /// The RW Lock mechanism accepts you get shared access to your data without locking.
 /// The Ordered RW Lock will be locking all reads, which starting after write and unlocking them only when write will realize.
 /// It may be slow down the reads speed, but decrease time to write on systems, where it is critical.
 ///
-/// **BUT RW Lock has some limitations. You should avoid acquiring the second reading before realizing first inside the one future.
-/// Because it can happen that between your readings a write from another thread will acquire the mutex, and you will get a deadlock.**
+/// You should avoid acquiring the second reading before realizing first inside the one future,
+/// because a write from another thread can take a ticket between your readings, and you will get a deadlock.
+/// Clone the read guard instead, the clone joins the batch of the original guard.
+///
+/// The upgradable read keeps its place in the queue, so the upgrade waits only for the readers before it.
 pub mod rwlock_ordered;
 
+/// The Sharded RW Lock keeps the readers counters per thread shards, so concurrent reads don't touch the same cache line.
+/// It will work well for the read-mostly data, but the write is slower than the write of the `RwLock`.
+pub mod rwlock_sharded;
+
+/// The SeqLock provides optimistic reads of the small `Copy` data without any locking.
+/// Readers retry when a write raced with them, and never block writers.
+pub mod seqlock;
+
+/// The Snapshot Lock keeps the data in the `Arc` and replaces it on every write.
+/// Readers load the current snapshot without locking and never wait on writers.
+pub mod snapshot_lock;
+
+/// The OnceCell and Lazy provide one-time asynchronous initialization of a value.
+/// Only one initializer runs at the same time, and others wait on it.
+pub mod once_cell;
+
+/// The watch channel keeps the latest value in the RW Lock and notifies receivers about its changes.
+pub mod watch;
+
+/// The Lock Maps provide locks per key, which are created on demand and removed when they are not used anymore.
+pub mod lock_map;
+
+/// The Wait Strategies define, how the contended lock future waits for the lock:
+/// it may spin before returning control to the async runtime, and then either park until the lock wakes it or wake itself.
+/// `Mutex`, `RwLock` and the ordered locks are generic over the strategy.
+/// The adaptive strategy decides to spin or to park by the hold times, which are observed by the lock.
+pub mod wait_strategy;
+
+/// The lock traits abstract the mutexes and the RW Locks, so the code may be generic over the type of the lock.
+/// The `test-suite` feature exports the behaviour suites, which check the implementations of the traits.
+pub mod traits;
+
+/// The Raw Lock traits define the algorithm of the asynchronous lock, so the users can plug their own algorithms into the guards of the crate.
+/// The algorithms of the `Mutex` and the `RwLock` are provided as the `RawMutex` and the `RawRwLock`.
+/// The `lock_api` feature implements the `lock_api` raw lock traits on them, so the same algorithm backs the blocking `lock_api::Mutex` and `lock_api::RwLock`.
+pub mod raw;
+
+/// The Mutex over the `RawAsyncMutex` algorithm, which provides the guards, the owned guards and the mapped guards for any algorithm.
+pub mod raw_mutex;
+
+/// The RW Lock over the `RawAsyncRwLock` algorithm, which provides the guards, the owned guards and the mapped guards for any algorithm.
+pub mod raw_rwlock;
+
+/// The CachePadded aligns the value to the length of the cache line, so it doesn't share the line with other values.
+/// The `padded` feature pads the hot atomics of the locks, so they don't share the line with the data and with each other.
+pub mod cache_padded;
+
+#[cfg(feature = "lock_api")]
+mod blocking;
+pub(crate) mod delegation;
 pub(crate) mod inner;
+pub(crate) mod parking;
+pub(crate) mod readers;
 pub(crate) mod utils;
+
+pub(crate) use utils::{
+    impl_deref, impl_deref_mut, impl_drop_guard, impl_drop_guard_self, impl_send_sync_mutex,
+    impl_send_sync_rwlock,
+};
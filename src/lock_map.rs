@@ -0,0 +1,762 @@
+use crate::inner::Inner;
+use crate::mutex::{Mutex, MutexOwnedGuard, MutexOwnedGuardFuture};
+use crate::mutex_ordered::{OrderedMutex, OrderedMutexOwnedGuard, OrderedMutexOwnedGuardFuture};
+use crate::rwlock::{
+    RwLock, RwLockReadOwnedGuard, RwLockReadOwnedGuardFuture, RwLockWriteOwnedGuard,
+    RwLockWriteOwnedGuardFuture,
+};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The Lock Map provides a mutual exclusion per key, which is based on the `Mutex`.
+///
+/// Locks are created on demand and removed automatically, when the key has no holders and no waiters.
+#[derive(Debug)]
+pub struct LockMap<K> {
+    locks: Arc<Locks<K, Mutex<()>>>,
+}
+
+/// The Lock Map provides a read and write access per key, which is based on the `RwLock`.
+///
+/// Locks are created on demand and removed automatically, when the key has no holders and no waiters.
+///
+/// The guards of the same key share it, so the map is `Send` and `Sync` only if the key is `Sync`:
+///
+/// ```compile_fail
+/// use fast_async_mutex::lock_map::RwLockMap;
+/// use std::cell::Cell;
+/// use std::hash::{Hash, Hasher};
+///
+/// #[derive(PartialEq, Eq)]
+/// struct Key(Cell<usize>);
+///
+/// impl Hash for Key {
+///     fn hash<H: Hasher>(&self, state: &mut H) {
+///         self.0.get().hash(state)
+///     }
+/// }
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+///
+/// assert_sync(&RwLockMap::<Key>::new());
+/// ```
+#[derive(Debug)]
+pub struct RwLockMap<K> {
+    locks: Arc<Locks<K, RwLock<()>>>,
+}
+
+/// The Lock Map provides an ordered mutual exclusion per key, which is based on the `OrderedMutex`.
+///
+/// Locks are created on demand and removed automatically, when the key has no holders and no waiters.
+/// Lockers of the same key acquire the lock in order of the `lock` calls.
+#[derive(Debug)]
+pub struct OrderedLockMap<K> {
+    locks: Arc<Locks<K, OrderedMutex<()>>>,
+}
+
+pub type LockMapGuard<K> = KeyGuard<K, Mutex<()>, MutexOwnedGuard<()>>;
+pub type LockMapGuardFuture<K> = KeyGuardFuture<K, Mutex<()>, MutexOwnedGuardFuture<()>>;
+
+pub type RwLockMapReadGuard<K> = KeyGuard<K, RwLock<()>, RwLockReadOwnedGuard<()>>;
+pub type RwLockMapReadGuardFuture<K> =
+    KeyGuardFuture<K, RwLock<()>, RwLockReadOwnedGuardFuture<()>>;
+pub type RwLockMapWriteGuard<K> = KeyGuard<K, RwLock<()>, RwLockWriteOwnedGuard<()>>;
+pub type RwLockMapWriteGuardFuture<K> =
+    KeyGuardFuture<K, RwLock<()>, RwLockWriteOwnedGuardFuture<()>>;
+
+pub type OrderedLockMapGuard<K> = KeyGuard<K, OrderedMutex<()>, OrderedMutexOwnedGuard<()>>;
+pub type OrderedLockMapGuardFuture<K> =
+    KeyGuardFuture<K, OrderedMutex<()>, OrderedMutexOwnedGuardFuture<()>>;
+
+impl<K: Hash + Eq> LockMap<K> {
+    /// Create a new empty `LockMap`
+    #[inline]
+    pub fn new() -> LockMap<K> {
+        LockMap {
+            locks: Arc::new(Locks::new()),
+        }
+    }
+
+    /// Acquires the lock of the key.
+    ///
+    /// Returns an owned guard that releases the lock and removes it from the map, if nobody else waits for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::lock_map::LockMap;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let locks = LockMap::new();
+    ///     let guard = locks.lock("user").await;
+    ///     assert_eq!(*guard.key(), "user");
+    ///     assert_eq!(locks.len(), 1);
+    ///     drop(guard);
+    ///     assert!(locks.is_empty());
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock(&self, key: K) -> LockMapGuardFuture<K> {
+        let (key, lock) = self.locks.acquire(key, || Mutex::new(()));
+        KeyGuardFuture::new(self.locks.clone(), key, lock.lock_owned())
+    }
+
+    /// Returns the number of keys, which are locked or waited for.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.locks.len()
+    }
+
+    /// Returns `true` if no keys are locked or waited for.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Hash + Eq> RwLockMap<K> {
+    /// Create a new empty `RwLockMap`
+    #[inline]
+    pub fn new() -> RwLockMap<K> {
+        RwLockMap {
+            locks: Arc::new(Locks::new()),
+        }
+    }
+
+    /// Acquires the lock of the key for are read.
+    ///
+    /// Returns an owned guard that releases the lock and removes it from the map, if nobody else waits for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::lock_map::RwLockMap;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let locks = RwLockMap::new();
+    ///     let guard = locks.read("/etc/hosts").await;
+    ///     let guard2 = locks.read("/etc/hosts").await;
+    ///     assert_eq!(locks.len(), 1);
+    /// }
+    /// ```
+    #[inline]
+    pub fn read(&self, key: K) -> RwLockMapReadGuardFuture<K> {
+        let (key, lock) = self.locks.acquire(key, || RwLock::new(()));
+        KeyGuardFuture::new(self.locks.clone(), key, lock.read_owned())
+    }
+
+    /// Acquires the lock of the key for are write.
+    ///
+    /// Returns an owned guard that releases the lock and removes it from the map, if nobody else waits for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::lock_map::RwLockMap;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let locks = RwLockMap::new();
+    ///     let guard = locks.write("/etc/hosts").await;
+    ///     drop(guard);
+    ///     assert!(locks.is_empty());
+    /// }
+    /// ```
+    #[inline]
+    pub fn write(&self, key: K) -> RwLockMapWriteGuardFuture<K> {
+        let (key, lock) = self.locks.acquire(key, || RwLock::new(()));
+        KeyGuardFuture::new(self.locks.clone(), key, lock.write_owned())
+    }
+
+    /// Returns the number of keys, which are locked or waited for.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.locks.len()
+    }
+
+    /// Returns `true` if no keys are locked or waited for.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Hash + Eq> OrderedLockMap<K> {
+    /// Create a new empty `OrderedLockMap`
+    #[inline]
+    pub fn new() -> OrderedLockMap<K> {
+        OrderedLockMap {
+            locks: Arc::new(Locks::new()),
+        }
+    }
+
+    /// Acquires the lock of the key.
+    ///
+    /// Returns an owned guard that releases the lock and removes it from the map, if nobody else waits for it.
+    /// The place in the queue of the key is taken when this method is called, not when the future is polled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::lock_map::OrderedLockMap;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let locks = OrderedLockMap::new();
+    ///     let guard = locks.lock(1).await;
+    ///     assert_eq!(*guard.key(), 1);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock(&self, key: K) -> OrderedLockMapGuardFuture<K> {
+        let (key, lock) = self.locks.acquire(key, || OrderedMutex::new(()));
+        KeyGuardFuture::new(self.locks.clone(), key, lock.lock_owned())
+    }
+
+    /// Returns the number of keys, which are locked or waited for.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.locks.len()
+    }
+
+    /// Returns `true` if no keys are locked or waited for.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Hash + Eq> Default for LockMap<K> {
+    fn default() -> Self {
+        LockMap::new()
+    }
+}
+
+impl<K: Hash + Eq> Default for RwLockMap<K> {
+    fn default() -> Self {
+        RwLockMap::new()
+    }
+}
+
+impl<K: Hash + Eq> Default for OrderedLockMap<K> {
+    fn default() -> Self {
+        OrderedLockMap::new()
+    }
+}
+
+/// The number of the maps, which the keys are spread over, so the lockers of different keys rarely contend for the same spin lock.
+const SHARDS: usize = 16;
+
+/// The locks storage. Every lock is referenced by the map itself and by every holder and waiter of the key,
+/// so the lock can be removed when the map holds the last reference.
+/// The key is shared by the map and the guards of its lock, so it isn't cloned.
+///
+/// The key is hashed outside of the spin lock, so the `Hash` of the key never runs under it,
+/// only the `Eq` of the keys with the same hash does.
+#[derive(Debug)]
+struct Locks<K, L> {
+    hasher: RandomState,
+    shards: [Inner<Shard<K, L>>; SHARDS],
+}
+
+type Shard<K, L> = HashMap<HashedKey<K>, Arc<L>, BuildHasherDefault<KeyHasher>>;
+
+/// The shared key with its hash, which is computed once, when the key is locked.
+#[derive(Debug)]
+struct HashedKey<K> {
+    hash: u64,
+    key: Arc<K>,
+}
+
+impl<K> Clone for HashedKey<K> {
+    #[inline]
+    fn clone(&self) -> Self {
+        HashedKey {
+            hash: self.hash,
+            key: self.key.clone(),
+        }
+    }
+}
+
+/// The borrowed form of the `HashedKey`, so the map is searched by the key, which isn't shared yet.
+trait KeyRef<K> {
+    fn key_hash(&self) -> u64;
+    fn key(&self) -> &K;
+}
+
+impl<K> KeyRef<K> for HashedKey<K> {
+    #[inline]
+    fn key_hash(&self) -> u64 {
+        self.hash
+    }
+
+    #[inline]
+    fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K> KeyRef<K> for (u64, &K) {
+    #[inline]
+    fn key_hash(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn key(&self) -> &K {
+        self.1
+    }
+}
+
+impl<'a, K: 'a> Borrow<dyn KeyRef<K> + 'a> for HashedKey<K> {
+    #[inline]
+    fn borrow(&self) -> &(dyn KeyRef<K> + 'a) {
+        self
+    }
+}
+
+impl<K> Hash for dyn KeyRef<K> + '_ {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key_hash())
+    }
+}
+
+impl<K: Eq> PartialEq for dyn KeyRef<K> + '_ {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.key_hash() == other.key_hash() && self.key() == other.key()
+    }
+}
+
+impl<K: Eq> Eq for dyn KeyRef<K> + '_ {}
+
+impl<K> Hash for HashedKey<K> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash)
+    }
+}
+
+impl<K: Eq> PartialEq for HashedKey<K> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.key == other.key
+    }
+}
+
+impl<K: Eq> Eq for HashedKey<K> {}
+
+/// Passes the hash of the `HashedKey` to the map as is, because it's already computed.
+#[derive(Debug, Default)]
+struct KeyHasher(u64);
+
+impl Hasher for KeyHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
+impl<K: Hash + Eq, L> Locks<K, L> {
+    #[inline]
+    fn new() -> Locks<K, L> {
+        Locks {
+            hasher: RandomState::new(),
+            shards: std::array::from_fn(|_| Inner::new(HashMap::default())),
+        }
+    }
+
+    #[inline]
+    fn hash(&self, key: &K) -> u64 {
+        let mut state = self.hasher.build_hasher();
+        key.hash(&mut state);
+        state.finish()
+    }
+
+    /// Returns the stored key and its lock. The new key and lock are allocated outside of the map lock.
+    #[inline]
+    fn acquire(&self, key: K, create: impl FnOnce() -> L) -> (HashedKey<K>, Arc<L>) {
+        let hash = self.hash(&key);
+        let shard = self.shard(hash);
+        let stored = shard.spin_lock(|map| {
+            map.get_key_value(&(hash, &key) as &dyn KeyRef<K>)
+                .map(|(key, lock)| (key.clone(), lock.clone()))
+        });
+        if let Some(stored) = stored {
+            return stored;
+        }
+
+        let key = HashedKey {
+            hash,
+            key: Arc::new(key),
+        };
+        let lock = Arc::new(create());
+        loop {
+            let stored = shard.spin_lock(|map| match map.get_key_value(&key) {
+                Some((key, lock)) => Some(Some((key.clone(), lock.clone()))),
+                // The map is grown outside of the lock, so the insert never reallocates under it.
+                None if map.len() == map.capacity() => None,
+                None => {
+                    map.insert(key.clone(), lock.clone());
+                    Some(None)
+                }
+            });
+            match stored {
+                // The lock of the key could be created by another locker meanwhile, so the new one is dropped.
+                Some(stored) => return stored.unwrap_or((key, lock)),
+                None => grow(shard),
+            }
+        }
+    }
+}
+
+/// Doubles the capacity of the map, which is guarded by the spin lock.
+/// The new map is allocated and the old one is freed outside of the lock, the stored hashes are moved without hashing the keys.
+#[cold]
+fn grow<K: Eq, L>(shard: &Inner<Shard<K, L>>) {
+    let capacity = shard.spin_lock(|map| map.capacity());
+    let mut grown = HashMap::with_capacity_and_hasher((capacity * 2).max(4), Default::default());
+    shard.spin_lock(|map| {
+        // The map could be grown by another thread meanwhile.
+        if map.capacity() == capacity {
+            grown.extend(map.drain());
+            std::mem::swap(map, &mut grown);
+        }
+    });
+}
+
+impl<K: Eq, L> Locks<K, L> {
+    /// The map picks the buckets by the low bits of the hash, so the shard is picked by the mixed hash,
+    /// and the keys of the same shard are still spread over the buckets.
+    #[inline]
+    fn shard(&self, hash: u64) -> &Inner<Shard<K, L>> {
+        let mixed = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - SHARDS.trailing_zeros());
+        &self.shards[mixed as usize]
+    }
+
+    /// Removes the lock of the key, if it has no holders and no waiters.
+    /// New references are created only under the map lock, so the reference count can't grow during the check.
+    /// The removed key and lock are freed outside of the map lock.
+    #[inline]
+    fn release(&self, key: &HashedKey<K>) {
+        let removed = self.shard(key.hash).spin_lock(|map| {
+            if matches!(map.get(key), Some(lock) if Arc::strong_count(lock) == 1) {
+                map.remove_entry(key)
+            } else {
+                None
+            }
+        });
+        drop(removed);
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.spin_lock(|map| map.len()))
+            .sum()
+    }
+}
+
+/// The guard of the key lock.
+/// The lock is automatically released whenever the guard is dropped,
+/// and removed from the map if nobody else holds or waits for it.
+#[derive(Debug)]
+pub struct KeyGuard<K: Hash + Eq, L, G> {
+    locks: Arc<Locks<K, L>>,
+    key: HashedKey<K>,
+    guard: Option<G>,
+}
+
+impl<K: Hash + Eq, L, G> KeyGuard<K, L, G> {
+    /// Returns the locked key.
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key.key
+    }
+}
+
+impl<K: Hash + Eq, L, G: Deref> Deref for KeyGuard<K, L, G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        self.guard.as_ref().expect("guard exists until drop")
+    }
+}
+
+impl<K: Hash + Eq, L, G: DerefMut> DerefMut for KeyGuard<K, L, G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.as_mut().expect("guard exists until drop")
+    }
+}
+
+impl<K: Hash + Eq, L, G> Drop for KeyGuard<K, L, G> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        self.locks.release(&self.key);
+    }
+}
+
+#[derive(Debug)]
+pub struct KeyGuardFuture<K: Hash + Eq, L, F> {
+    locks: Arc<Locks<K, L>>,
+    key: Option<HashedKey<K>>,
+    future: Option<F>,
+}
+
+impl<K: Hash + Eq, L, F> KeyGuardFuture<K, L, F> {
+    #[inline]
+    fn new(locks: Arc<Locks<K, L>>, key: HashedKey<K>, future: F) -> KeyGuardFuture<K, L, F> {
+        KeyGuardFuture {
+            locks,
+            key: Some(key),
+            future: Some(future),
+        }
+    }
+}
+
+// The key is never pinned, so the future is movable when the inner future is.
+impl<K: Hash + Eq, L, F: Unpin> Unpin for KeyGuardFuture<K, L, F> {}
+
+impl<K: Hash + Eq, L, F: Future> Future for KeyGuardFuture<K, L, F> {
+    type Output = KeyGuard<K, L, F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The inner future is pinned structurally: it's never moved out, and it's dropped in place.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = this
+            .future
+            .as_mut()
+            .expect("future polled after completion");
+        match unsafe { Pin::new_unchecked(future) }.poll(cx) {
+            Poll::Ready(guard) => {
+                this.future = None;
+                Poll::Ready(KeyGuard {
+                    locks: this.locks.clone(),
+                    key: this.key.take().expect("key exists until completion"),
+                    guard: Some(guard),
+                })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<K: Hash + Eq, L, F> Drop for KeyGuardFuture<K, L, F> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            // The inner future is dropped in place before the lock is released.
+            self.future = None;
+            self.locks.release(&key);
+        }
+    }
+}
+
+// The keys are shared by the guards, which give out `&K`, so they must be `Sync` too.
+unsafe impl<K: Send + Sync, L: Send + Sync> Send for Locks<K, L> {}
+unsafe impl<K: Send + Sync, L: Send + Sync> Sync for Locks<K, L> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::lock_map::{LockMap, LockMapGuard, OrderedLockMap, RwLockMap};
+    use futures::StreamExt;
+    use std::hash::{Hash, Hasher};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_lock_map() {
+        let locks = LockMap::new();
+        let counters: Vec<_> = (0..10).map(|_| AtomicUsize::new(0)).collect();
+
+        futures::stream::iter(0..10000usize)
+            .for_each_concurrent(None, |i| {
+                let locks = &locks;
+                let counters = &counters;
+                async move {
+                    let key = i % 10;
+                    let _guard: LockMapGuard<usize> = locks.lock(key).await;
+                    // Not atomic increment, which is protected by the key lock.
+                    let value = counters[key].load(Ordering::Relaxed);
+                    tokio::time::sleep(Duration::from_micros(1)).await;
+                    counters[key].store(value + 1, Ordering::Relaxed);
+                }
+            })
+            .await;
+
+        for counter in counters {
+            assert_eq!(counter.load(Ordering::Relaxed), 1000);
+        }
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_independent_keys() {
+        let locks = LockMap::new();
+
+        let guard = locks.lock("lol").await;
+        let guard2 = locks.lock("kek").await;
+        assert_eq!(locks.len(), 2);
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(1), locks.lock("lol"))
+                .await
+                .is_err()
+        );
+        assert_eq!(locks.len(), 2);
+
+        drop(guard);
+        assert_eq!(locks.len(), 1);
+        drop(guard2);
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_key_without_clone() {
+        #[derive(Debug, Hash, PartialEq, Eq)]
+        struct Key(usize);
+
+        let locks = LockMap::new();
+        let guard = locks.lock(Key(1)).await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(1), locks.lock(Key(1)))
+                .await
+                .is_err()
+        );
+        assert_eq!(*guard.key(), Key(1));
+
+        drop(guard);
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hashed_once() {
+        static HASHES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Key(usize);
+
+        impl Hash for Key {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                HASHES.fetch_add(1, Ordering::SeqCst);
+                self.0.hash(state)
+            }
+        }
+
+        // The map is grown many times, while the keys are held, without hashing the stored keys again.
+        let locks = LockMap::new();
+        let mut guards = Vec::new();
+        for i in 0..1000 {
+            guards.push(locks.lock(Key(i)).await);
+        }
+        assert_eq!(locks.len(), 1000);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(1), locks.lock(Key(500)))
+                .await
+                .is_err()
+        );
+        assert_eq!(HASHES.load(Ordering::SeqCst), 1001);
+
+        drop(guards);
+        assert!(locks.is_empty());
+        assert_eq!(HASHES.load(Ordering::SeqCst), 1001);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_removed_with_waiters() {
+        let locks = Arc::new(LockMap::new());
+
+        let guard = locks.lock(1).await;
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move {
+                let _guard = locks.lock(1).await;
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(locks.len(), 1);
+        drop(guard);
+
+        waiter.await.unwrap();
+        assert!(locks.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_rwlock_map() {
+        let locks = RwLockMap::new();
+
+        let read = locks.read(1).await;
+        let read2 = locks.read(1).await;
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(1), locks.write(1))
+                .await
+                .is_err()
+        );
+        let write = locks.write(2).await;
+        assert_eq!(locks.len(), 2);
+
+        drop(read);
+        drop(read2);
+        drop(write);
+        assert!(locks.is_empty());
+
+        let _write = locks.write(1).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_ordered_lock_map() {
+        let locks = OrderedLockMap::new();
+        let order = std::sync::Mutex::new(Vec::new());
+
+        let futures: Vec<_> = (0..100).map(|i| (i, locks.lock("key"))).collect();
+
+        futures::stream::iter(futures.into_iter().rev())
+            .for_each_concurrent(None, |(i, future)| {
+                let order = &order;
+                async move {
+                    let _guard = future.await;
+                    order.lock().unwrap().push(i);
+                }
+            })
+            .await;
+
+        assert_eq!(*order.lock().unwrap(), (0..100).collect::<Vec<_>>());
+        assert!(locks.is_empty());
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<LockMap<String>>();
+        assert_send_sync::<RwLockMap<String>>();
+        assert_send_sync::<OrderedLockMap<String>>();
+    }
+}
@@ -1,27 +1,90 @@
-use crate::inner::Inner;
+use crate::delegation::{Delegation, Delegations, Progress};
+use crate::inner::{Acquisition, HotInner, Inner, WaitQueue, Waiting};
+use crate::wait_strategy::{WaitStrategy, WakeImmediately};
 use std::fmt::Debug;
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::task::{Context, Poll, Waker};
 
 /// The simple Mutex, which will provide unique access to you data between multiple threads/futures.
+///
+/// By default, the released mutex can be acquired by any locker, even if other lockers wait longer,
+/// until the oldest waiting locker was bypassed too many times or waits too long, then the mutex is handed to it.
+/// The fair unlock always hands the mutex directly to the oldest waiting locker, so the mutex is never observed free between them.
+///
+/// The contended lockers wait for the mutex by the `WaitStrategy`, they wake themselves immediately by default.
+/// The waiting lockers are queued by the nodes, which are owned by the queue and reused by the next lockers,
+/// so the lock futures aren't pinned, and neither leaving the queue nor dropping the future waits for other threads.
+///
+/// The tiny critical sections may be delegated to the holder of the mutex by `delegate`, so the data isn't moved between the cores.
 #[derive(Debug)]
-pub struct Mutex<T: ?Sized> {
-    inner: Inner<T>,
+pub struct Mutex<T: ?Sized, W = WakeImmediately> {
+    is_fair: bool,
+    queue: WaitQueue,
+    strategy: PhantomData<fn() -> W>,
+    delegations: Delegations<T>,
+    inner: HotInner<T>,
 }
 
 impl<T> Mutex<T> {
     /// Create a new `Mutex`
     #[inline]
     pub const fn new(data: T) -> Mutex<T> {
+        Mutex::with_strategy(data)
+    }
+}
+
+impl<T, W> Mutex<T, W> {
+    /// Create a new `Mutex`, whose contended lockers wait by the given `WaitStrategy`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex::Mutex;
+    /// use fast_async_mutex::wait_strategy::SpinThenPark;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex: Mutex<_, SpinThenPark> = Mutex::with_strategy(10);
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub const fn with_strategy(data: T) -> Mutex<T, W> {
         Mutex {
+            is_fair: false,
+            queue: WaitQueue::new(),
+            strategy: PhantomData,
+            delegations: Delegations::new(),
             inner: Inner::new(data),
         }
     }
+
+    /// Makes every unlock of the mutex fair, see `MutexGuard::unlock_fair`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex::Mutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Mutex::new(10).fair();
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn fair(mut self) -> Mutex<T, W> {
+        self.is_fair = true;
+        self
+    }
 }
 
-impl<T: ?Sized> Mutex<T> {
+impl<T: ?Sized, W: WaitStrategy> Mutex<T, W> {
     /// Acquires the mutex.
     ///
     /// Returns a guard that releases the mutex and wake the next locker when dropped.
@@ -39,10 +102,10 @@ impl<T: ?Sized> Mutex<T> {
     /// }
     /// ```
     #[inline]
-    pub const fn lock(&self) -> MutexGuardFuture<T> {
+    pub const fn lock(&self) -> MutexGuardFuture<'_, T, W> {
         MutexGuardFuture {
-            mutex: &self,
-            is_realized: false,
+            mutex: self,
+            waiting: Waiting::new(),
         }
     }
 
@@ -64,10 +127,87 @@ impl<T: ?Sized> Mutex<T> {
     /// }
     /// ```
     #[inline]
-    pub fn lock_owned(self: &Arc<Self>) -> MutexOwnedGuardFuture<T> {
+    pub fn lock_owned(self: &Arc<Self>) -> MutexOwnedGuardFuture<T, W> {
         MutexOwnedGuardFuture {
             mutex: self.clone(),
-            is_realized: false,
+            waiting: Waiting::new(),
+        }
+    }
+
+    /// Runs the closure with unique access to the data, and returns its result.
+    ///
+    /// If the mutex is held, the closure is queued, and the holder, however it acquired the mutex, runs a batch of the queued closures
+    /// on its own core before it releases the mutex, so the data isn't moved to the cores of the waiting lockers (flat combining).
+    /// If no holder runs the closure, the locker acquires the mutex and runs it itself, with the closures of others.
+    /// So it fits for the tiny critical sections, which are cheaper than the transfer of the data between the cores.
+    ///
+    /// The panic of the closure, which was run by another holder, is caught and resumed in the task, which delegated it,
+    /// so it doesn't unwind the holder. The data is left as the panicked closure left it.
+    ///
+    /// Dropping the future, while another holder runs the closure, doesn't wait for it.
+    /// The holder finishes the abandoned closure and drops its result, so the closure and the result must be `'static`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex::Mutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Mutex::new(10);
+    ///     let result = mutex.delegate(|data| {
+    ///         *data += 1;
+    ///         *data
+    ///     }).await;
+    ///     assert_eq!(result, 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn delegate<F, R>(&self, f: F) -> MutexDelegateFuture<'_, T, F, R, W>
+    where
+        F: FnOnce(&mut T) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        MutexDelegateFuture {
+            mutex: self,
+            waiting: Waiting::new(),
+            delegation: Delegation::new(f),
+        }
+    }
+
+    #[inline]
+    fn poll_lock(&self, waiting: &Waiting, waker: &Waker) -> bool {
+        self.queue.poll_acquire::<W>(waiting, waker, || {
+            self.queue.try_acquire::<W, _>(&self.inner)
+        }) != Acquisition::Pending
+    }
+}
+
+impl<T: ?Sized, W> Mutex<T, W> {
+    #[inline]
+    fn unlock(&self) {
+        self.run_delegations();
+        if self.is_fair {
+            self.queue.unlock_fair(&self.inner)
+        } else {
+            self.queue.unlock(&self.inner)
+        }
+    }
+
+    #[inline]
+    fn unlock_fair(&self) {
+        self.run_delegations();
+        self.queue.unlock_fair(&self.inner)
+    }
+
+    /// Runs a batch of the queued delegations before the mutex is released, however the holder acquired it.
+    /// The guard, which is dropped while panicking, leaves them to their submitters.
+    /// The queue is checked first, so the release without delegations doesn't ask whether the thread is panicking.
+    #[inline]
+    fn run_delegations(&self) {
+        if self.delegations.is_queued() && !std::thread::panicking() {
+            self.delegations
+                .run_batch(unsafe { &mut *self.inner.data.get() });
         }
     }
 }
@@ -76,14 +216,14 @@ impl<T: ?Sized> Mutex<T> {
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the Mutex, so the mutex will not be dropped while a guard exists.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct MutexGuard<'a, T: ?Sized> {
-    mutex: &'a Mutex<T>,
+pub struct MutexGuard<'a, T: ?Sized, W = WakeImmediately> {
+    mutex: &'a Mutex<T, W>,
 }
 
 #[derive(Debug)]
-pub struct MutexGuardFuture<'a, T: ?Sized> {
-    mutex: &'a Mutex<T>,
-    is_realized: bool,
+pub struct MutexGuardFuture<'a, T: ?Sized, W = WakeImmediately> {
+    mutex: &'a Mutex<T, W>,
+    waiting: Waiting,
 }
 
 /// An owned handle to a held Mutex.
@@ -91,63 +231,179 @@ pub struct MutexGuardFuture<'a, T: ?Sized> {
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `Mutex`, so even if the lock goes away, the guard remains valid.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct MutexOwnedGuard<T: ?Sized> {
-    mutex: Arc<Mutex<T>>,
+pub struct MutexOwnedGuard<T: ?Sized, W = WakeImmediately> {
+    mutex: Arc<Mutex<T, W>>,
 }
 
 #[derive(Debug)]
-pub struct MutexOwnedGuardFuture<T: ?Sized> {
-    mutex: Arc<Mutex<T>>,
-    is_realized: bool,
+pub struct MutexOwnedGuardFuture<T: ?Sized, W = WakeImmediately> {
+    mutex: Arc<Mutex<T, W>>,
+    waiting: Waiting,
 }
 
-impl<'a, T: ?Sized> Future for MutexGuardFuture<'a, T> {
-    type Output = MutexGuard<'a, T>;
+/// The future of the delegated closure, which resolves into its result.
+/// The closure is queued in the pinned future, so the delegation doesn't allocate.
+/// The drop of the future abandons the running closure to the holder, which drops its result.
+#[derive(Debug)]
+pub struct MutexDelegateFuture<'a, T: ?Sized, F, R, W = WakeImmediately> {
+    mutex: &'a Mutex<T, W>,
+    waiting: Waiting,
+    delegation: Delegation<T, F, R>,
+}
+
+impl<'a, T: ?Sized, W> MutexGuard<'a, T, W> {
+    /// Releases the mutex and hands it directly to the oldest waiting locker.
+    /// The mutex isn't observed free, so no other locker can acquire it before the oldest one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex::Mutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Mutex::new(10);
+    ///     let mut guard = mutex.lock().await;
+    ///     *guard += 1;
+    ///     guard.unlock_fair();
+    ///     assert_eq!(*mutex.lock().await, 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn unlock_fair(self) {
+        self.mutex.unlock_fair();
+        std::mem::forget(self);
+    }
+}
+
+impl<T: ?Sized, W> MutexOwnedGuard<T, W> {
+    /// Releases the mutex and hands it directly to the oldest waiting locker.
+    /// The mutex isn't observed free, so no other locker can acquire it before the oldest one.
+    #[inline]
+    pub fn unlock_fair(self) {
+        let guard = std::mem::ManuallyDrop::new(self);
+        guard.mutex.unlock_fair();
+        // The guard isn't dropped, so the `Arc` is dropped separately.
+        drop(unsafe { std::ptr::read(&guard.mutex) });
+    }
+}
+
+impl<'a, T: ?Sized, W: WaitStrategy> Future for MutexGuardFuture<'a, T, W> {
+    type Output = MutexGuard<'a, T, W>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire() {
-            self.is_realized = true;
-            Poll::Ready(MutexGuard { mutex: self.mutex })
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &*self;
+        if this.mutex.poll_lock(&this.waiting, cx.waker()) {
+            Poll::Ready(MutexGuard { mutex: this.mutex })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<T: ?Sized> Future for MutexOwnedGuardFuture<T> {
-    type Output = MutexOwnedGuard<T>;
+impl<T: ?Sized, W: WaitStrategy> Future for MutexOwnedGuardFuture<T, W> {
+    type Output = MutexOwnedGuard<T, W>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire() {
-            self.is_realized = true;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &*self;
+        if this.mutex.poll_lock(&this.waiting, cx.waker()) {
             Poll::Ready(MutexOwnedGuard {
-                mutex: self.mutex.clone(),
+                mutex: this.mutex.clone(),
             })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-crate::impl_send_sync_mutex!(Mutex, MutexGuard, MutexOwnedGuard);
+impl<T: ?Sized, F, R, W> Future for MutexDelegateFuture<'_, T, F, R, W>
+where
+    F: FnOnce(&mut T) -> R + 'static,
+    R: 'static,
+    W: WaitStrategy,
+{
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The delegation is linked into the queue of the lock, so the pinned future is never moved or borrowed mutably.
+        let this = self.into_ref().get_ref();
+        let mutex = this.mutex;
+        if this.delegation.is_submitted() {
+            match this.delegation.poll(&mutex.delegations, cx.waker()) {
+                Progress::Done(result) => {
+                    mutex.queue.cancel(&mutex.inner, &this.waiting);
+                    return Poll::Ready(result);
+                }
+                Progress::Running => return Poll::Pending,
+                Progress::Queued => {}
+            }
+        }
+
+        if !mutex.poll_lock(&this.waiting, cx.waker()) {
+            if !this.delegation.is_submitted() {
+                this.delegation.submit(&mutex.delegations, cx.waker());
+            }
+            return Poll::Pending;
+        }
+
+        let mut guard = MutexGuard { mutex };
+        // The holder runs its own closure first, unless another holder ran it before the mutex was acquired.
+        // The queued closures of others are run by the guard, when it releases the mutex.
+        let result =
+            if !this.delegation.is_submitted() || this.delegation.withdraw(&mutex.delegations) {
+                this.delegation.run_by_holder(&mut guard)
+            } else {
+                match this.delegation.poll(&mutex.delegations, cx.waker()) {
+                    Progress::Done(result) => result,
+                    _ => unreachable!("the delegated operation isn't run by the holder"),
+                }
+            };
+        Poll::Ready(result)
+    }
+}
+
+crate::impl_send_sync_mutex!(Mutex<W>, MutexGuard, MutexOwnedGuard);
+
+crate::impl_deref_mut!(MutexGuard<W>, 'a);
+crate::impl_deref_mut!(MutexOwnedGuard<W>);
+
+crate::impl_drop_guard_self!(MutexGuard<W>, 'a, unlock);
+crate::impl_drop_guard_self!(MutexOwnedGuard<W>, unlock);
+
+impl<T: ?Sized, W> Drop for MutexGuardFuture<'_, T, W> {
+    fn drop(&mut self) {
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
 
-crate::impl_deref_mut!(MutexGuard, 'a);
-crate::impl_deref_mut!(MutexOwnedGuard);
+impl<T: ?Sized, F, R, W> Drop for MutexDelegateFuture<'_, T, F, R, W> {
+    fn drop(&mut self) {
+        self.delegation.cancel(&self.mutex.delegations);
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
 
-crate::impl_drop_guard!(MutexGuard, 'a, unlock);
-crate::impl_drop_guard!(MutexOwnedGuard, unlock);
-crate::impl_drop_guard_future!(MutexGuardFuture, 'a, unlock);
-crate::impl_drop_guard_future!(MutexOwnedGuardFuture, unlock);
+impl<T: ?Sized, W> Drop for MutexOwnedGuardFuture<T, W> {
+    fn drop(&mut self) {
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use crate::delegation::Delegations;
+    use crate::inner::MAX_BYPASS;
     use crate::mutex::{Mutex, MutexGuard, MutexOwnedGuard};
+    use crate::wait_strategy::{Adaptive, ImmediatePark, SpinThenPark, WaitStrategy, YieldBackoff};
     use futures::executor::block_on;
     use futures::{FutureExt, StreamExt, TryStreamExt};
+    use std::future::Future;
+    use std::mem::size_of;
     use std::ops::AddAssign;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
+    use std::task::{Context, Poll};
     use tokio::time::{sleep, Duration};
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
@@ -165,6 +421,71 @@ mod tests {
         assert_eq!(*co, 10000)
     }
 
+    async fn check_strategy<W: WaitStrategy>() {
+        let c: Mutex<_, W> = Mutex::with_strategy(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co = c.lock().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_wait_strategies() {
+        check_strategy::<ImmediatePark>().await;
+        check_strategy::<SpinThenPark>().await;
+        check_strategy::<YieldBackoff>().await;
+        check_strategy::<Adaptive>().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_parked_owned_mutex() {
+        let c: Arc<Mutex<_, ImmediatePark>> = Arc::new(Mutex::with_strategy(0));
+
+        futures::stream::iter(0..1000)
+            .for_each_concurrent(None, |_| async {
+                let mut co = c.lock_owned().await;
+                sleep(Duration::from_micros(10)).await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, 1000)
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_hold_times() {
+        let c: Mutex<_, Adaptive> = Mutex::with_strategy(0);
+        assert!(c.queue.stats().is_none());
+        drop(c.lock().await);
+        assert!(c.queue.stats().unwrap().is_spin_worth());
+
+        for _ in 0..10 {
+            let mut co = c.lock().await;
+            std::thread::sleep(Duration::from_millis(1));
+            *co += 1;
+        }
+        assert!(!c.queue.stats().unwrap().is_spin_worth());
+
+        for _ in 0..100 {
+            *c.lock().await += 1;
+        }
+        assert!(c.queue.stats().unwrap().is_spin_worth());
+    }
+
+    #[tokio::test]
+    async fn test_hold_times_only_adaptive() {
+        let c = Mutex::new(0);
+        *c.lock().await += 1;
+        assert!(c.queue.stats().is_none());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
     async fn test_mutex_delay() {
         let expected_result = 100;
@@ -227,6 +548,338 @@ mod tests {
         assert_eq!(*co, "lollol");
     }
 
+    #[tokio::test]
+    async fn test_cancelled_lock() {
+        let c = Mutex::new(0);
+
+        let co: MutexGuard<i32> = c.lock().await;
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.lock())
+            .await
+            .is_err());
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.lock())
+            .await
+            .is_err());
+
+        drop(co);
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.lock())
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_dropped_pending_lock() {
+        let c = Mutex::new(0);
+        let co = block_on(c.lock());
+
+        // The dropped future, which never acquired the mutex, leaves it held.
+        assert!(c.lock().now_or_never().is_none());
+        assert!(c.lock().now_or_never().is_none());
+
+        drop(co);
+        assert!(c.lock().now_or_never().is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_fair_mutex() {
+        let c = Mutex::new(0).fair();
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: MutexGuard<i32> = c.lock().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test]
+    async fn test_unlock_fair() {
+        let c = Mutex::new(0);
+
+        let mut co = c.lock().await;
+        let mut first = Box::pin(c.lock());
+        let mut second = Box::pin(c.lock());
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut first)
+            .await
+            .is_err());
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut second)
+            .await
+            .is_err());
+
+        *co += 1;
+        co.unlock_fair();
+
+        // The mutex is handed to the first waiter, so neither a new locker nor the second waiter can barge in.
+        assert!(!c.inner.try_acquire());
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.lock())
+            .await
+            .is_err());
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut second)
+            .await
+            .is_err());
+
+        let mut co = first.await;
+        *co += 1;
+        co.unlock_fair();
+
+        assert_eq!(*second.await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_handoff() {
+        let c = Arc::new(Mutex::new(0));
+
+        let co = c.lock_owned().await;
+        let mut first = Box::pin(c.lock_owned());
+        let mut second = Box::pin(c.lock_owned());
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut first)
+            .await
+            .is_err());
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut second)
+            .await
+            .is_err());
+
+        co.unlock_fair();
+        drop(first);
+
+        let co = second.await;
+        co.unlock_fair();
+        assert!(c.inner.try_acquire());
+    }
+
+    #[tokio::test]
+    async fn test_bounded_bypass() {
+        let tasks = 4;
+        let c = Arc::new(Mutex::new(()));
+        let acquisitions = Arc::new(AtomicUsize::new(0));
+        let max_bypass = Arc::new(AtomicUsize::new(0));
+
+        // Every task locks the mutex again right after the release, so it would bypass others without the handoff.
+        let handles: Vec<_> = (0..tasks)
+            .map(|_| {
+                let c = c.clone();
+                let acquisitions = acquisitions.clone();
+                let max_bypass = max_bypass.clone();
+                tokio::spawn(async move {
+                    for _ in 0..200 {
+                        let before = acquisitions.load(Ordering::SeqCst);
+                        let co = c.lock().await;
+                        let bypass = acquisitions.fetch_add(1, Ordering::SeqCst) - before;
+                        max_bypass.fetch_max(bypass, Ordering::SeqCst);
+                        let _ = tokio::task::yield_now().await;
+                        drop(co);
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // The waiter has at most `tasks - 1` waiters before it.
+        let max_bypass = max_bypass.load(Ordering::SeqCst);
+        assert!(max_bypass < tasks * (MAX_BYPASS + 1), "{}", max_bypass);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_delegate() {
+        let c = Mutex::new(0);
+
+        let mut results: Vec<_> = futures::stream::iter(0..10000)
+            .map(|_| {
+                c.delegate(|co| {
+                    *co += 1;
+                    *co
+                })
+            })
+            .buffer_unordered(10000)
+            .collect()
+            .await;
+
+        assert_eq!(*c.lock().await, 10000);
+        // Every delegated closure is run once.
+        results.sort_unstable();
+        assert!(results.into_iter().eq(1..=10000));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_delegate_with_lockers() {
+        let c: Mutex<_, ImmediatePark> = Mutex::with_strategy(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |i| {
+                let c = &c;
+                async move {
+                    if i % 2 == 0 {
+                        c.delegate(|co| *co += 1).await;
+                    } else {
+                        *c.lock().await += 1;
+                    }
+                }
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[test]
+    fn test_delegation_batch() {
+        let c = Mutex::new(0);
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let co = block_on(c.lock());
+        let mut delegations: Vec<_> = (0..10)
+            .map(|i| Box::pin(c.delegate(move |co| std::mem::replace(co, i))))
+            .collect();
+        for delegation in delegations.iter_mut() {
+            assert!(delegation.as_mut().poll(&mut cx).is_pending());
+        }
+        // The cancelled closure is never run.
+        delegations.remove(5);
+        drop(co);
+
+        // The first locker runs its own closure and the queued closures of others before it releases the mutex.
+        let results =
+            delegations
+                .iter_mut()
+                .map(|delegation| match delegation.as_mut().poll(&mut cx) {
+                    Poll::Ready(result) => result,
+                    Poll::Pending => panic!("the delegated closure isn't run"),
+                });
+        assert!(results.eq([0, 0, 1, 2, 3, 4, 6, 7, 8]));
+        assert_eq!(*block_on(c.lock()), 9);
+    }
+
+    #[test]
+    fn test_delegation_by_lock_holder() {
+        let c = Arc::new(Mutex::new(0));
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // The holders, which took the mutex by `lock` and `lock_owned`, run the queued closures before they release it.
+        for is_fair in [false, true] {
+            let mut co = block_on(c.lock_owned());
+            let mut delegations: Vec<_> = (1..=3)
+                .map(|i| {
+                    Box::pin(c.delegate(move |co| {
+                        *co += i;
+                        *co
+                    }))
+                })
+                .collect();
+            for delegation in delegations.iter_mut() {
+                assert!(delegation.as_mut().poll(&mut cx).is_pending());
+            }
+            *co = 10;
+            if is_fair {
+                co.unlock_fair();
+            } else {
+                drop(co);
+            }
+
+            let results =
+                delegations
+                    .iter_mut()
+                    .map(|delegation| match delegation.as_mut().poll(&mut cx) {
+                        Poll::Ready(result) => result,
+                        Poll::Pending => panic!("the delegated closure isn't run by the holder"),
+                    });
+            assert!(results.eq([11, 13, 16]));
+        }
+
+        let co = block_on(c.lock());
+        let mut delegation = Box::pin(c.delegate(|co| std::mem::replace(co, 0)));
+        assert!(delegation.as_mut().poll(&mut cx).is_pending());
+        drop(co);
+        assert_eq!(delegation.as_mut().poll(&mut cx), Poll::Ready(16));
+    }
+
+    #[test]
+    fn test_delegation_panic() {
+        let c = Mutex::new(0);
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let co = block_on(c.lock());
+        let mut panicked = Box::pin(c.delegate(|_| panic!("the delegated closure panics")));
+        let mut other = Box::pin(c.delegate(|co| {
+            *co += 1;
+            *co
+        }));
+        assert!(panicked.as_mut().poll(&mut cx).is_pending());
+        assert!(other.as_mut().poll(&mut cx).is_pending());
+        drop(co);
+
+        // The closure of the panicked submitter is run by the holder, but the panic is resumed only by its submitter.
+        assert_eq!(other.as_mut().poll(&mut cx), Poll::Ready(1));
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| panicked.as_mut().poll(&mut cx)));
+        assert!(panic.is_err());
+        drop(panicked);
+        assert_eq!(*block_on(c.lock()), 1);
+    }
+
+    #[test]
+    fn test_drop_running_delegation() {
+        struct Result(Arc<AtomicUsize>);
+
+        impl Drop for Result {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let c = Arc::new(Mutex::new(0));
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (started, is_started) = std::sync::mpsc::channel();
+        let (release, is_released) = std::sync::mpsc::channel::<()>();
+
+        let co = block_on(c.lock_owned());
+        let result = Result(dropped.clone());
+        let mut delegation = Box::pin(c.delegate(move |co| {
+            *co += 1;
+            started.send(()).unwrap();
+            is_released.recv().unwrap();
+            result
+        }));
+        assert!(delegation.as_mut().poll(&mut cx).is_pending());
+        let holder = std::thread::spawn(move || drop(co));
+
+        // The drop doesn't wait for the holder, which runs the closure, and the holder drops the abandoned result.
+        is_started.recv().unwrap();
+        drop(delegation);
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        release.send(()).unwrap();
+        holder.join().unwrap();
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        assert_eq!(*block_on(c.lock()), 1);
+    }
+
+    #[test]
+    fn test_delegations_size() {
+        // The mutex, which nobody delegates to, keeps only the pointer to the queue of the delegated closures.
+        assert_eq!(size_of::<Delegations<[u8; 64]>>(), size_of::<usize>());
+    }
+
+    #[test]
+    fn test_unpin_futures() {
+        fn assert_unpin<T: Unpin>(_: &T) {}
+
+        // The waiting node is owned by the queue, so the lock futures can be polled without pinning.
+        let mutex = Arc::new(Mutex::<_, ImmediatePark>::with_strategy(0));
+        assert_unpin(&mutex.lock());
+        assert_unpin(&mutex.lock_owned());
+        assert_unpin(&Mutex::new(0).lock());
+    }
+
     #[test]
     fn multithreading_test() {
         let num = 100;
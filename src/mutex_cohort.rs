@@ -0,0 +1,824 @@
+use crate::cache_padded::{hot, Hot};
+use crate::inner::{cmp_ids, grow_field, HotInner, Inner};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+/// The number of consecutive handoffs inside the group of the holder, after which the mutex is handed to other groups.
+pub const MAX_LOCAL_HANDOFFS: usize = 64;
+
+/// The group of the current thread, like the socket of its CPU.
+/// The `CohortMutex` prefers to hand the released mutex to the waiting lockers of the same group.
+pub trait CohortGroup {
+    /// Returns the group of the current thread. It's called on every contended lock and unlock, so it must be cheap.
+    fn current() -> usize;
+}
+
+/// Groups the threads by the socket of the CPU, which they are running on.
+///
+/// The sockets are read from the `/sys/devices/system/cpu` once, and the current CPU is asked by `sched_getcpu` on every call.
+/// On the targets without `sched_getcpu`, or if the topology isn't available, all threads are in the same group.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuSocket;
+
+impl CohortGroup for CpuSocket {
+    #[inline]
+    fn current() -> usize {
+        match current_cpu() {
+            Some(cpu) => sockets().get(cpu).copied().unwrap_or(0),
+            None => 0,
+        }
+    }
+}
+
+/// The targets, whose C library provides `sched_getcpu`.
+#[cfg(any(
+    target_os = "android",
+    all(target_os = "linux", any(target_env = "gnu", target_env = "musl"))
+))]
+#[inline]
+fn current_cpu() -> Option<usize> {
+    use std::convert::TryFrom;
+
+    extern "C" {
+        fn sched_getcpu() -> i32;
+    }
+
+    usize::try_from(unsafe { sched_getcpu() }).ok()
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    all(target_os = "linux", any(target_env = "gnu", target_env = "musl"))
+)))]
+#[inline]
+fn current_cpu() -> Option<usize> {
+    None
+}
+
+/// Returns the sockets of the CPUs, which are read by the first call.
+/// The threads, which call it first concurrently, read them each, and the sockets of the first one are kept.
+fn sockets() -> &'static [usize] {
+    static SOCKETS: AtomicPtr<Vec<usize>> = AtomicPtr::new(ptr::null_mut());
+
+    if let Some(sockets) = unsafe { SOCKETS.load(Ordering::Acquire).as_ref() } {
+        return sockets;
+    }
+
+    let sockets = Box::into_raw(Box::new(read_sockets()));
+    match SOCKETS.compare_exchange(
+        ptr::null_mut(),
+        sockets,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => unsafe { &*sockets },
+        Err(installed) => {
+            drop(unsafe { Box::from_raw(sockets) });
+            unsafe { &*installed }
+        }
+    }
+}
+
+/// Reads the physical package ids of the CPUs, which are indexed by the CPU ids.
+fn read_sockets() -> Vec<usize> {
+    let mut sockets = Vec::new();
+    let entries = match std::fs::read_dir("/sys/devices/system/cpu") {
+        Ok(entries) => entries,
+        Err(_) => return sockets,
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let cpu = match name
+            .to_str()
+            .and_then(|name| name.strip_prefix("cpu"))
+            .and_then(|cpu| cpu.parse::<usize>().ok())
+        {
+            Some(cpu) => cpu,
+            None => continue,
+        };
+        let socket = std::fs::read_to_string(entry.path().join("topology/physical_package_id"))
+            .ok()
+            .and_then(|socket| socket.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if sockets.len() <= cpu {
+            sockets.resize(cpu + 1, 0);
+        }
+        sockets[cpu] = socket;
+    }
+    sockets
+}
+
+/// Groups the threads by the thread-local value, which is `0` until it's set by `ThreadGroup::set`.
+/// It fits for the thread pools, whose threads are pinned to the sockets, and for simulating the groups on any box.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::mutex_cohort::{CohortGroup, ThreadGroup};
+///
+/// ThreadGroup::set(1);
+/// assert_eq!(ThreadGroup::current(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadGroup;
+
+thread_local! {
+    static THREAD_GROUP: Cell<usize> = const { Cell::new(0) };
+}
+
+impl ThreadGroup {
+    /// Sets the group of the current thread.
+    #[inline]
+    pub fn set(group: usize) {
+        THREAD_GROUP.with(|current| current.set(group))
+    }
+}
+
+impl CohortGroup for ThreadGroup {
+    #[inline]
+    fn current() -> usize {
+        THREAD_GROUP.with(|current| current.get())
+    }
+}
+
+/// The Cohort Mutex hands the released mutex to the waiting lockers of the same group as the holder, like the same socket,
+/// so the data and the state of the mutex don't ping-pong between the caches of the sockets.
+///
+/// While lockers wait, the mutex is never released, it's handed to the oldest waiting locker of the group of the releasing thread.
+/// After `MAX_LOCAL_HANDOFFS` consecutive handoffs inside the group, or when the group has no waiting lockers,
+/// the mutex is handed to the oldest waiting locker of other groups, so the lockers of other groups aren't starved.
+///
+/// The groups are assigned by the `CohortGroup`, it's the socket of the CPU by default.
+/// The group of the waiting locker is taken, when it's polled, so the task, which moves between threads, is counted in the group of its last poll.
+#[derive(Debug)]
+pub struct CohortMutex<T: ?Sized, G = CpuSocket> {
+    max_local_handoffs: usize,
+    /// The `CohortGroup::current` of the `G`, so the guards don't require the bound of the group.
+    current_group: fn() -> usize,
+    len: Hot<AtomicUsize>,
+    cohort: Inner<Cohort>,
+    group: PhantomData<fn() -> G>,
+    inner: HotInner<T>,
+}
+
+/// The waiting lockers of every group, and the state of the handoffs. It's accessed only under the spin lock.
+#[derive(Debug)]
+struct Cohort {
+    /// The groups are never removed, so their queues are reused, and their indexes stay valid outside of the spin lock.
+    groups: Vec<GroupWaiters>,
+    next_id: usize,
+    /// The locker, which the mutex was handed to, or `0` if there is no one.
+    handoff: usize,
+    /// The number of consecutive handoffs inside the group.
+    local_handoffs: usize,
+}
+
+/// The waiting lockers of the group in the order of arrival, so they are sorted by their ids.
+#[derive(Debug)]
+struct GroupWaiters {
+    group: usize,
+    waiters: VecDeque<Waiter>,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    id: usize,
+    /// The waker is taken, when the locker is woken to try again, and it's stored again by its next poll.
+    waker: Option<Waker>,
+}
+
+/// The capacity of the queue of the group, which is allocated, when the first locker of the group waits.
+const MIN_WAITERS: usize = 4;
+
+/// The number of groups, which is allocated, when the first locker waits.
+const MIN_GROUPS: usize = 2;
+
+impl Cohort {
+    /// Takes the next locker, which the mutex is handed to by the thread of the `group`.
+    fn next(&mut self, group: usize, max_local_handoffs: usize) -> Option<Waiter> {
+        if self.local_handoffs < max_local_handoffs {
+            let local = self
+                .index(group)
+                .and_then(|i| self.groups[i].waiters.pop_front());
+            if local.is_some() {
+                self.local_handoffs += 1;
+                return local;
+            }
+        }
+
+        self.local_handoffs = 0;
+        let i = self
+            .oldest(|other| other != group)
+            .or_else(|| self.oldest(|_| true))?;
+        self.groups[i].waiters.pop_front()
+    }
+
+    /// The index of the group, whose first locker is the oldest one of the matching groups.
+    fn oldest(&self, matches: impl Fn(usize) -> bool) -> Option<usize> {
+        self.groups
+            .iter()
+            .enumerate()
+            .filter(|(_, waiters)| matches(waiters.group))
+            .filter_map(|(i, waiters)| Some((i, waiters.waiters.front()?.id)))
+            .min_by(|(_, id), (_, other)| cmp_ids(*id, *other))
+            .map(|(i, _)| i)
+    }
+
+    #[inline]
+    fn index(&self, group: usize) -> Option<usize> {
+        self.groups
+            .iter()
+            .position(|waiters| waiters.group == group)
+    }
+
+    /// The index of the group, if its queue has room for one more locker without allocating.
+    #[inline]
+    fn room(&self, group: usize) -> Option<usize> {
+        self.index(group).filter(|&i| {
+            let waiters = &self.groups[i].waiters;
+            waiters.len() < waiters.capacity()
+        })
+    }
+
+    /// The index of the group of the locker and its position in the queue of the group.
+    fn find(&self, id: usize) -> Option<(usize, usize)> {
+        self.groups.iter().enumerate().find_map(|(i, waiters)| {
+            let position = waiters
+                .waiters
+                .binary_search_by(|waiter| cmp_ids(waiter.id, id))
+                .ok()?;
+            Some((i, position))
+        })
+    }
+}
+
+impl<T> CohortMutex<T> {
+    /// Create a new `CohortMutex`
+    #[inline]
+    pub const fn new(data: T) -> CohortMutex<T> {
+        CohortMutex::with_group(data)
+    }
+}
+
+impl<T, G: CohortGroup> CohortMutex<T, G> {
+    /// Create a new `CohortMutex`, whose lockers are grouped by the given `CohortGroup`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_cohort::{CohortMutex, ThreadGroup};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex: CohortMutex<_, ThreadGroup> = CohortMutex::with_group(10);
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub const fn with_group(data: T) -> CohortMutex<T, G> {
+        CohortMutex {
+            max_local_handoffs: MAX_LOCAL_HANDOFFS,
+            current_group: G::current,
+            len: hot(AtomicUsize::new(0)),
+            cohort: Inner::new(Cohort {
+                groups: Vec::new(),
+                next_id: 1,
+                handoff: 0,
+                local_handoffs: 0,
+            }),
+            group: PhantomData,
+            inner: Inner::new(data),
+        }
+    }
+
+    /// Sets the number of consecutive handoffs inside the group, after which the mutex is handed to other groups.
+    /// The `0` disables the preference of the group, so the mutex is handed to the lockers of other groups first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_cohort::CohortMutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = CohortMutex::new(10).max_local_handoffs(8);
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn max_local_handoffs(mut self, limit: usize) -> CohortMutex<T, G> {
+        self.max_local_handoffs = limit;
+        self
+    }
+}
+
+impl<T: ?Sized, G> CohortMutex<T, G> {
+    /// Acquires the mutex.
+    ///
+    /// Returns a guard that releases the mutex and hands it to the next locker when dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_cohort::CohortMutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = CohortMutex::new(10);
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock(&self) -> CohortMutexGuardFuture<'_, T, G> {
+        CohortMutexGuardFuture { mutex: self, id: 0 }
+    }
+
+    /// Acquires the mutex.
+    ///
+    /// Returns a guard that releases the mutex and hands it to the next locker when dropped.
+    /// `CohortMutexOwnedGuard` have a `'static` lifetime, but requires the `Arc<CohortMutex<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_cohort::CohortMutex;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(CohortMutex::new(10));
+    ///     let guard = mutex.lock_owned().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock_owned(self: &Arc<Self>) -> CohortMutexOwnedGuardFuture<T, G> {
+        CohortMutexOwnedGuardFuture {
+            mutex: self.clone(),
+            id: 0,
+        }
+    }
+
+    /// Acquires the mutex, takes it if it was handed to the locker with the `id`, or registers the locker.
+    /// The locker is registered before its last attempt, so the release after the attempt wakes it.
+    fn poll_lock(&self, id: &mut usize, waker: &Waker) -> bool {
+        if *id == 0 && self.inner.try_acquire() {
+            return true;
+        }
+
+        let group = (self.current_group)();
+        // The waker is cloned before the spin lock, and the unused clone or the replaced waker is dropped after it.
+        let mut waker = Some(waker.clone());
+        loop {
+            match self
+                .cohort
+                .spin_lock(|cohort| self.poll_locked(cohort, id, group, &mut waker))
+            {
+                Some(is_acquired) => return is_acquired,
+                None => self.grow(group),
+            }
+        }
+    }
+
+    /// Registers the locker in the queue of its group, or moves it there, if it's polled by the thread of another group.
+    /// The `waker` is moved into the queue, or swapped with the outdated one.
+    /// Returns `None` if the queue of the group must be grown first.
+    fn poll_locked(
+        &self,
+        cohort: &mut Cohort,
+        id: &mut usize,
+        group: usize,
+        waker: &mut Option<Waker>,
+    ) -> Option<bool> {
+        if *id != 0 && cohort.handoff == *id {
+            cohort.handoff = 0;
+            *id = 0;
+            return Some(true);
+        }
+
+        if *id == 0 {
+            let i = cohort.room(group)?;
+            *id = cohort.next_id;
+            cohort.next_id = cohort.next_id.wrapping_add(1).max(1);
+            cohort.groups[i].waiters.push_back(Waiter {
+                id: *id,
+                waker: waker.take(),
+            });
+            self.len.fetch_add(1, Ordering::SeqCst);
+        } else if let Some((i, position)) = cohort.find(*id) {
+            let j = if cohort.groups[i].group == group {
+                i
+            } else {
+                cohort.room(group)?
+            };
+            let waiter = &mut cohort.groups[i].waiters[position];
+            let is_outdated = match (&waiter.waker, &*waker) {
+                (Some(stored), Some(waker)) => !stored.will_wake(waker),
+                (None, Some(_)) => true,
+                (_, None) => false,
+            };
+            if is_outdated {
+                std::mem::swap(&mut waiter.waker, waker);
+            }
+            if i != j {
+                let waiter = cohort.groups[i].waiters.remove(position).unwrap();
+                let waiters = &mut cohort.groups[j].waiters;
+                let position =
+                    waiters.partition_point(|other| cmp_ids(other.id, waiter.id).is_lt());
+                waiters.insert(position, waiter);
+            }
+        }
+
+        fence(Ordering::SeqCst);
+        if self.inner.try_acquire() {
+            self.remove(cohort, *id);
+            *id = 0;
+            return Some(true);
+        }
+        Some(false)
+    }
+
+    /// Makes room for one more locker in the queue of the group, or adds the group.
+    /// The queues are allocated and the old ones are freed outside of the spin lock.
+    #[cold]
+    fn grow(&self, group: usize) {
+        if let Some(i) = self.cohort.spin_lock(|cohort| cohort.index(group)) {
+            grow_field(
+                &self.cohort,
+                |cohort| &mut cohort.groups[i].waiters,
+                MIN_WAITERS,
+            );
+            return;
+        }
+
+        let capacity = self.cohort.spin_lock(|cohort| cohort.groups.capacity());
+        let mut grown = Vec::with_capacity((capacity * 2).max(MIN_GROUPS));
+        let mut added = Some(GroupWaiters {
+            group,
+            waiters: VecDeque::with_capacity(MIN_WAITERS),
+        });
+        self.cohort.spin_lock(|cohort| {
+            // The group could be added, or the groups could be grown by another thread meanwhile.
+            if cohort.index(group).is_some() {
+                return;
+            }
+            if cohort.groups.len() == cohort.groups.capacity() {
+                if grown.capacity() <= cohort.groups.len() {
+                    return;
+                }
+                grown.append(&mut cohort.groups);
+                std::mem::swap(&mut cohort.groups, &mut grown);
+            }
+            cohort.groups.extend(added.take());
+        });
+    }
+
+    /// Releases the mutex, or hands it to the next locker, if lockers wait.
+    ///
+    /// The waiting locker is registered before its last attempt to acquire the mutex,
+    /// so the lockers are checked again after the release, and the oldest one is woken if it missed the release.
+    #[inline]
+    fn unlock(&self) {
+        if self.len.load(Ordering::SeqCst) == 0 {
+            self.inner.unlock();
+            fence(Ordering::SeqCst);
+            if self.len.load(Ordering::SeqCst) != 0 {
+                self.wake_oldest();
+            }
+            return;
+        }
+
+        let group = (self.current_group)();
+        let waker = self.cohort.spin_lock(|cohort| self.hand_off(cohort, group));
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Gives up the place of the locker. If the mutex was handed to it already, it's handed to the next locker.
+    fn cancel(&self, id: usize) {
+        if id == 0 {
+            return;
+        }
+
+        let group = (self.current_group)();
+        let waker = self.cohort.spin_lock(|cohort| {
+            if cohort.handoff == id {
+                cohort.handoff = 0;
+                self.hand_off(cohort, group)
+            } else {
+                self.remove(cohort, id);
+                None
+            }
+        });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Hands the mutex to the next locker without releasing it, or releases it if there are no waiting lockers.
+    /// Returns the waker of the locker, which must be woken out of the spin lock.
+    fn hand_off(&self, cohort: &mut Cohort, group: usize) -> Option<Waker> {
+        match cohort.next(group, self.max_local_handoffs) {
+            Some(waiter) => {
+                self.len.fetch_sub(1, Ordering::SeqCst);
+                cohort.handoff = waiter.id;
+                waiter.waker
+            }
+            None => {
+                self.inner.unlock();
+                None
+            }
+        }
+    }
+
+    fn remove(&self, cohort: &mut Cohort, id: usize) {
+        if let Some((i, position)) = cohort.find(id) {
+            cohort.groups[i].waiters.remove(position);
+            self.len.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Wakes the oldest locker, so it tries to acquire the released mutex again.
+    /// Its waker is taken rather than cloned under the spin lock, the locker, which was woken already, isn't woken again.
+    fn wake_oldest(&self) {
+        let waker = self.cohort.spin_lock(|cohort| {
+            let i = cohort.oldest(|_| true)?;
+            cohort.groups[i]
+                .waiters
+                .front_mut()
+                .and_then(|waiter| waiter.waker.take())
+        });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The Cohort Mutex Guard
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the CohortMutex, so the mutex will not be dropped while a guard exists.
+/// The lock is automatically released or handed to the next locker whenever the guard is dropped.
+#[derive(Debug)]
+pub struct CohortMutexGuard<'a, T: ?Sized, G = CpuSocket> {
+    mutex: &'a CohortMutex<T, G>,
+}
+
+#[derive(Debug)]
+pub struct CohortMutexGuardFuture<'a, T: ?Sized, G = CpuSocket> {
+    mutex: &'a CohortMutex<T, G>,
+    id: usize,
+}
+
+/// An owned handle to a held CohortMutex.
+/// This guard is only available from a CohortMutex that is wrapped in an `Arc`. It is identical to `CohortMutexGuard`, except that rather than borrowing the `CohortMutex`, it clones the `Arc`, incrementing the reference count. This means that unlike `CohortMutexGuard`, it will have the `'static` lifetime.
+/// The lock is automatically released or handed to the next locker whenever the guard is dropped.
+#[derive(Debug)]
+pub struct CohortMutexOwnedGuard<T: ?Sized, G = CpuSocket> {
+    mutex: Arc<CohortMutex<T, G>>,
+}
+
+#[derive(Debug)]
+pub struct CohortMutexOwnedGuardFuture<T: ?Sized, G = CpuSocket> {
+    mutex: Arc<CohortMutex<T, G>>,
+    id: usize,
+}
+
+impl<'a, T: ?Sized, G> Future for CohortMutexGuardFuture<'a, T, G> {
+    type Output = CohortMutexGuard<'a, T, G>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_lock(&mut this.id, cx.waker()) {
+            Poll::Ready(CohortMutexGuard { mutex: this.mutex })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized, G> Future for CohortMutexOwnedGuardFuture<T, G> {
+    type Output = CohortMutexOwnedGuard<T, G>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_lock(&mut this.id, cx.waker()) {
+            Poll::Ready(CohortMutexOwnedGuard {
+                mutex: this.mutex.clone(),
+            })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+crate::impl_send_sync_mutex!(CohortMutex<G>, CohortMutexGuard, CohortMutexOwnedGuard);
+
+crate::impl_deref_mut!(CohortMutexGuard<G>, 'a);
+crate::impl_deref_mut!(CohortMutexOwnedGuard<G>);
+
+crate::impl_drop_guard_self!(CohortMutexGuard<G>, 'a, unlock);
+crate::impl_drop_guard_self!(CohortMutexOwnedGuard<G>, unlock);
+
+impl<T: ?Sized, G> Drop for CohortMutexGuardFuture<'_, T, G> {
+    fn drop(&mut self) {
+        self.mutex.cancel(self.id)
+    }
+}
+
+impl<T: ?Sized, G> Drop for CohortMutexOwnedGuardFuture<T, G> {
+    fn drop(&mut self) {
+        self.mutex.cancel(self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mutex_cohort::{CohortMutex, CohortMutexGuard, CohortMutexOwnedGuard, ThreadGroup};
+    use futures::task::noop_waker_ref;
+    use futures::StreamExt;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_mutex() {
+        let c = CohortMutex::new(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: CohortMutexGuard<i32> = c.lock().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_owned_mutex() {
+        let c = Arc::new(CohortMutex::new(0));
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: CohortMutexOwnedGuard<i32> = c.lock_owned().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock_owned().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[test]
+    fn test_simulated_groups() {
+        static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(4)
+            .on_thread_start(|| ThreadGroup::set(THREADS.fetch_add(1, Ordering::Relaxed) % 2))
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let c: Arc<CohortMutex<_, ThreadGroup>> =
+                Arc::new(CohortMutex::with_group(0).max_local_handoffs(4));
+
+            let lockers: Vec<_> = (0..1000)
+                .map(|_| {
+                    let c = c.clone();
+                    tokio::spawn(async move {
+                        let mut co = c.lock_owned().await;
+                        sleep(Duration::from_micros(10)).await;
+                        *co += 1;
+                    })
+                })
+                .collect();
+            for locker in lockers {
+                locker.await.unwrap();
+            }
+
+            assert_eq!(*c.lock().await, 1000);
+        });
+    }
+
+    /// Polls the locker as the thread of the group.
+    fn poll_in<F: Future + Unpin>(group: usize, future: &mut F) -> Poll<F::Output> {
+        ThreadGroup::set(group);
+        Pin::new(future).poll(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    #[test]
+    fn test_local_handoffs() {
+        let c: CohortMutex<_, ThreadGroup> =
+            CohortMutex::with_group(Vec::new()).max_local_handoffs(2);
+
+        let guard = poll_in(0, &mut c.lock());
+        let groups = [0, 1, 0, 1, 0, 0];
+        let mut lockers: Vec<_> = groups.iter().map(|_| Some(c.lock())).collect();
+        for (locker, &group) in lockers.iter_mut().zip(groups.iter()) {
+            assert!(poll_in(group, locker.as_mut().unwrap()).is_pending());
+        }
+
+        ThreadGroup::set(0);
+        drop(guard);
+        for _ in 0..groups.len() {
+            // Only the locker, which the mutex was handed to, acquires it.
+            let mut acquired: Vec<_> = lockers
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(i, locker)| {
+                    let guard = match poll_in(groups[i], locker.as_mut()?) {
+                        Poll::Ready(guard) => guard,
+                        Poll::Pending => return None,
+                    };
+                    *locker = None;
+                    Some((i, guard))
+                })
+                .collect();
+            assert_eq!(acquired.len(), 1);
+
+            let (i, mut guard) = acquired.pop().unwrap();
+            guard.push(i);
+            ThreadGroup::set(groups[i]);
+            drop(guard);
+        }
+
+        // Two handoffs inside the first group, then the oldest locker of the second group takes its turn.
+        let guard = poll_in(0, &mut c.lock());
+        assert!(matches!(guard, Poll::Ready(ref order) if **order == [0, 2, 1, 3, 4, 5]));
+    }
+
+    #[test]
+    fn test_moved_locker() {
+        let c: CohortMutex<_, ThreadGroup> = CohortMutex::with_group(Vec::new());
+
+        let guard = poll_in(0, &mut c.lock());
+        let mut lockers: Vec<_> = (0..6).map(|_| Some(c.lock())).collect();
+        for locker in lockers.iter_mut() {
+            assert!(poll_in(1, locker.as_mut().unwrap()).is_pending());
+        }
+        // The lockers are moved to the first group, and one of them gives up its place.
+        assert!(poll_in(0, lockers[3].as_mut().unwrap()).is_pending());
+        assert!(poll_in(0, lockers[1].as_mut().unwrap()).is_pending());
+        lockers[4] = None;
+
+        let groups = [1, 0, 1, 0, 1, 1];
+        ThreadGroup::set(0);
+        drop(guard);
+        let mut order = Vec::new();
+        while order.len() < 5 {
+            for (i, locker) in lockers.iter_mut().enumerate() {
+                if let Some(Poll::Ready(mut guard)) =
+                    locker.as_mut().map(|locker| poll_in(groups[i], locker))
+                {
+                    guard.push(i);
+                    order.push(i);
+                    *locker = None;
+                    drop(guard);
+                }
+            }
+        }
+
+        // The moved lockers are handed the mutex first in the order of arrival, then the lockers of the second group.
+        assert_eq!(order, [1, 3, 0, 2, 5]);
+        assert!(
+            matches!(poll_in(0, &mut c.lock()), Poll::Ready(ref order) if **order == [1, 3, 0, 2, 5])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_handoff() {
+        let c: Arc<CohortMutex<_, ThreadGroup>> = Arc::new(CohortMutex::with_group(0));
+
+        let co = c.lock().await;
+        let mut cancelled = c.lock();
+        assert!(poll_in(0, &mut cancelled).is_pending());
+        let locker = {
+            let c = c.clone();
+            tokio::spawn(async move { *c.lock_owned().await += 1 })
+        };
+        sleep(Duration::from_millis(1)).await;
+
+        // The mutex is handed to the first locker, which passes it to the next one, when it's dropped.
+        drop(co);
+        drop(cancelled);
+        locker.await.unwrap();
+        assert_eq!(*c.lock().await, 1);
+    }
+}
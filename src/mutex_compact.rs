@@ -0,0 +1,383 @@
+use crate::parking;
+use std::cell::UnsafeCell;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+/// The mutex is held.
+const LOCKED: u8 = 0b01;
+/// Some lockers may be parked in the parking table, so the unlock has to wake one of them.
+const PARKED: u8 = 0b10;
+
+/// The Compact Mutex keeps only one byte of the state, so it fits for the millions of small locks, like the locks of cache entries.
+///
+/// The waiting lockers are parked in the global table, which is keyed by the address of the mutex, like in the `parking_lot`.
+/// So the size of the `CompactMutex<T>` is the size of the `T` with one byte, rounded up to its alignment, the same as the size of the `Inner<T>` spin lock of the crate internals.
+/// For example, the `CompactMutex<()>` takes 1 byte and the `CompactMutex<u64>` takes 16 bytes, while the `Mutex<T>` keeps its wait queue inline.
+///
+/// The unlock wakes the oldest parked locker, but any locker can acquire the released mutex before it,
+/// so unlike the `Mutex`, the compact mutex doesn't provide the eventual fairness.
+/// The state isn't padded by the `padded` feature, because it would defeat the purpose of the compact mutex.
+#[derive(Debug)]
+pub struct CompactMutex<T: ?Sized> {
+    state: AtomicU8,
+    data: UnsafeCell<T>,
+}
+
+impl<T> CompactMutex<T> {
+    /// Create a new `CompactMutex`
+    #[inline]
+    pub const fn new(data: T) -> CompactMutex<T> {
+        CompactMutex {
+            state: AtomicU8::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> CompactMutex<T> {
+    /// Acquires the mutex.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_compact::CompactMutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = CompactMutex::new(10);
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock(&self) -> CompactMutexGuardFuture<'_, T> {
+        CompactMutexGuardFuture { mutex: self, id: 0 }
+    }
+
+    /// Acquires the mutex.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when dropped.
+    /// `CompactMutexOwnedGuard` have a `'static` lifetime, but requires the `Arc<CompactMutex<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_compact::CompactMutex;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(CompactMutex::new(10));
+    ///     let guard = mutex.lock_owned().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock_owned(self: &Arc<Self>) -> CompactMutexOwnedGuardFuture<T> {
+        CompactMutexOwnedGuardFuture {
+            mutex: self.clone(),
+            id: 0,
+        }
+    }
+
+    /// The address of the mutex is the key of its lockers in the parking table.
+    #[inline]
+    fn key(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+
+    #[inline]
+    fn try_acquire(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while state & LOCKED == 0 {
+            match self.state.compare_exchange_weak(
+                state,
+                state | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(current) => state = current,
+            }
+        }
+        false
+    }
+
+    /// Acquires the mutex, or parks the locker with the `id` until the unlock.
+    /// The parked bit is set under the lock of the bucket, so the unlock either sees it or the locker sees the released mutex.
+    fn poll_lock(&self, id: &mut usize, waker: &Waker) -> bool {
+        loop {
+            if self.try_acquire() {
+                if *id != 0 {
+                    // The locker could be polled before it was unparked, so it gives up its place.
+                    parking::remove(self.key(), *id);
+                    *id = 0;
+                }
+                return true;
+            }
+
+            if *id == 0 {
+                *id = parking::next_id();
+            }
+            let is_parked = parking::park(self.key(), *id, waker, || {
+                let mut state = self.state.load(Ordering::Relaxed);
+                loop {
+                    if state & LOCKED == 0 {
+                        return false;
+                    }
+                    if state & PARKED != 0 {
+                        return true;
+                    }
+                    match self.state.compare_exchange_weak(
+                        state,
+                        state | PARKED,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => return true,
+                        Err(current) => state = current,
+                    }
+                }
+            });
+            if is_parked {
+                return false;
+            }
+        }
+    }
+
+    #[inline]
+    fn unlock(&self) {
+        if self
+            .state
+            .compare_exchange(LOCKED, 0, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            self.unlock_slow()
+        }
+    }
+
+    /// The mutex is released under the lock of the bucket, so the parked bit stays only while lockers are parked.
+    fn unlock_slow(&self) {
+        parking::unpark_one(self.key(), |is_parked| {
+            let state = if is_parked { PARKED } else { 0 };
+            self.state.store(state, Ordering::Release);
+        })
+    }
+
+    /// Gives up the place of the locker. If it was unparked already, but didn't acquire the mutex, the next locker is unparked instead of it.
+    fn cancel(&self, id: usize) {
+        if id == 0 || parking::remove(self.key(), id) {
+            return;
+        }
+
+        parking::unpark_one(self.key(), |is_parked| {
+            if !is_parked {
+                self.state.fetch_and(!PARKED, Ordering::Relaxed);
+            }
+        })
+    }
+}
+
+/// The Compact Mutex Guard
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the CompactMutex, so the mutex will not be dropped while a guard exists.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct CompactMutexGuard<'a, T: ?Sized> {
+    mutex: &'a CompactMutex<T>,
+}
+
+#[derive(Debug)]
+pub struct CompactMutexGuardFuture<'a, T: ?Sized> {
+    mutex: &'a CompactMutex<T>,
+    id: usize,
+}
+
+/// An owned handle to a held CompactMutex.
+/// This guard is only available from a CompactMutex that is wrapped in an `Arc`. It is identical to `CompactMutexGuard`, except that rather than borrowing the `CompactMutex`, it clones the `Arc`, incrementing the reference count. This means that unlike `CompactMutexGuard`, it will have the `'static` lifetime.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct CompactMutexOwnedGuard<T: ?Sized> {
+    mutex: Arc<CompactMutex<T>>,
+}
+
+#[derive(Debug)]
+pub struct CompactMutexOwnedGuardFuture<T: ?Sized> {
+    mutex: Arc<CompactMutex<T>>,
+    id: usize,
+}
+
+impl<'a, T: ?Sized> Future for CompactMutexGuardFuture<'a, T> {
+    type Output = CompactMutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_lock(&mut this.id, cx.waker()) {
+            Poll::Ready(CompactMutexGuard { mutex: this.mutex })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized> Future for CompactMutexOwnedGuardFuture<T> {
+    type Output = CompactMutexOwnedGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_lock(&mut this.id, cx.waker()) {
+            Poll::Ready(CompactMutexOwnedGuard {
+                mutex: this.mutex.clone(),
+            })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+crate::impl_send_sync_mutex!(CompactMutex, CompactMutexGuard, CompactMutexOwnedGuard);
+
+crate::impl_drop_guard_self!(CompactMutexGuard, 'a, unlock);
+crate::impl_drop_guard_self!(CompactMutexOwnedGuard, unlock);
+
+impl<T: ?Sized> std::ops::Deref for CompactMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for CompactMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for CompactMutexOwnedGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for CompactMutexOwnedGuard<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for CompactMutexGuardFuture<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.cancel(self.id)
+    }
+}
+
+impl<T: ?Sized> Drop for CompactMutexOwnedGuardFuture<T> {
+    fn drop(&mut self) {
+        self.mutex.cancel(self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inner::Inner;
+    use crate::mutex::Mutex;
+    use crate::mutex_compact::{CompactMutex, CompactMutexGuard, CompactMutexOwnedGuard};
+    use futures::StreamExt;
+    use std::mem::size_of;
+    use std::sync::Arc;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_mutex() {
+        let c = CompactMutex::new(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: CompactMutexGuard<i32> = c.lock().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_owned_mutex() {
+        let c = Arc::new(CompactMutex::new(0));
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: CompactMutexOwnedGuard<i32> = c.lock_owned().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock_owned().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_many_mutexes() {
+        let mutexes: Arc<Vec<_>> = Arc::new((0..1000).map(|_| CompactMutex::new(0)).collect());
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |i| {
+                let mutexes = mutexes.clone();
+                async move {
+                    let mut co = mutexes[i % 1000].lock().await;
+                    sleep(Duration::from_micros(10)).await;
+                    *co += 1;
+                }
+            })
+            .await;
+
+        for mutex in mutexes.iter() {
+            assert_eq!(*mutex.lock().await, 10);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_lockers() {
+        let c = Arc::new(CompactMutex::new(0));
+
+        let co = c.lock().await;
+        let lockers: Vec<_> = (0..10)
+            .map(|_| {
+                let c = c.clone();
+                tokio::spawn(async move { *c.lock_owned().await += 1 })
+            })
+            .collect();
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.lock())
+            .await
+            .is_err());
+        drop(co);
+
+        for locker in lockers {
+            locker.await.unwrap();
+        }
+        assert_eq!(*c.lock().await, 10);
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size_of::<CompactMutex<()>>(), size_of::<Inner<()>>());
+        assert_eq!(size_of::<CompactMutex<u64>>(), size_of::<Inner<u64>>());
+        assert_eq!(
+            size_of::<CompactMutex<[u8; 7]>>(),
+            size_of::<Inner<[u8; 7]>>()
+        );
+        assert_eq!(size_of::<CompactMutex<()>>(), 1);
+        assert_eq!(size_of::<CompactMutex<u8>>(), 2);
+        assert_eq!(size_of::<CompactMutex<u64>>(), 16);
+        assert_eq!(size_of::<CompactMutex<[u8; 7]>>(), 8);
+        assert!(size_of::<CompactMutex<u64>>() < size_of::<Mutex<u64>>());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::inner::OrderedInner;
+use crate::wait_strategy::{ImmediatePark, WaitStrategy};
 use std::fmt::Debug;
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -10,8 +12,11 @@ use std::task::{Context, Poll};
 ///
 /// The main difference with the standard `Mutex` is ordered mutex will check an ordering of blocking.
 /// This way has some guaranties of mutex execution order, but it's a little bit slowly than original mutex.
+///
+/// The waiting lockers are parked until their turn by default, the other `WaitStrategy` may be chosen by `with_strategy`.
 #[derive(Debug)]
-pub struct OrderedMutex<T: ?Sized> {
+pub struct OrderedMutex<T: ?Sized, W = ImmediatePark> {
+    strategy: PhantomData<fn() -> W>,
     inner: OrderedInner<T>,
 }
 
@@ -19,13 +24,36 @@ impl<T> OrderedMutex<T> {
     /// Create a new `OrderedMutex`
     #[inline]
     pub const fn new(data: T) -> OrderedMutex<T> {
+        OrderedMutex::with_strategy(data)
+    }
+}
+
+impl<T, W> OrderedMutex<T, W> {
+    /// Create a new `OrderedMutex`, whose lockers wait for their turn by the given `WaitStrategy`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_ordered::OrderedMutex;
+    /// use fast_async_mutex::wait_strategy::SpinThenPark;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex: OrderedMutex<_, SpinThenPark> = OrderedMutex::with_strategy(10);
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub const fn with_strategy(data: T) -> OrderedMutex<T, W> {
         OrderedMutex {
+            strategy: PhantomData,
             inner: OrderedInner::new(data),
         }
     }
 }
 
-impl<T: ?Sized> OrderedMutex<T> {
+impl<T: ?Sized, W> OrderedMutex<T, W> {
     /// Acquires the mutex.
     ///
     /// Returns a guard that releases the mutex and wake the next locker when dropped.
@@ -43,12 +71,8 @@ impl<T: ?Sized> OrderedMutex<T> {
     /// }
     /// ```
     #[inline]
-    pub fn lock(&self) -> OrderedMutexGuardFuture<T> {
-        OrderedMutexGuardFuture {
-            mutex: &self,
-            id: self.inner.generate_id(),
-            is_realized: false,
-        }
+    pub fn lock(&self) -> OrderedMutexGuardFuture<'_, T, W> {
+        self.reserve()
     }
 
     /// Acquires the mutex.
@@ -69,10 +93,68 @@ impl<T: ?Sized> OrderedMutex<T> {
     /// }
     /// ```
     #[inline]
-    pub fn lock_owned(self: &Arc<Self>) -> OrderedMutexOwnedGuardFuture<T> {
-        OrderedMutexOwnedGuardFuture {
+    pub fn lock_owned(self: &Arc<Self>) -> OrderedMutexOwnedGuardFuture<T, W> {
+        self.reserve_owned()
+    }
+
+    /// Takes a place in the queue of the mutex right now.
+    ///
+    /// Returns a ticket, which acquires the mutex when awaited. The ticket keeps its place in the queue,
+    /// so it may be awaited later or on another task. Dropping or cancelling the ticket gives up its place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_ordered::OrderedMutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = OrderedMutex::new(Vec::new());
+    ///     let first = mutex.reserve();
+    ///     let second = mutex.reserve();
+    ///     let third = mutex.reserve();
+    ///
+    ///     second.cancel();
+    ///     first.await.push(1);
+    ///     third.await.push(3);
+    ///     assert_eq!(*mutex.lock().await, vec![1, 3]);
+    /// }
+    /// ```
+    #[inline]
+    pub fn reserve(&self) -> OrderedMutexTicket<'_, T, W> {
+        OrderedMutexTicket {
+            mutex: self,
+            id: self.inner.generate_id(),
+            attempt: 0,
+            is_realized: false,
+        }
+    }
+
+    /// Takes a place in the queue of the mutex right now.
+    ///
+    /// Returns a ticket, which acquires the mutex when awaited. The ticket keeps its place in the queue,
+    /// so it may be awaited later or on another task. Dropping or cancelling the ticket gives up its place.
+    /// `OrderedMutexOwnedTicket` have a `'static` lifetime, but requires the `Arc<OrderedMutex<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_ordered::OrderedMutex;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(OrderedMutex::new(10));
+    ///     let ticket = mutex.reserve_owned();
+    ///     let value = tokio::spawn(async move { *ticket.await }).await.unwrap();
+    ///     assert_eq!(value, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn reserve_owned(self: &Arc<Self>) -> OrderedMutexOwnedTicket<T, W> {
+        OrderedMutexOwnedTicket {
             mutex: self.clone(),
             id: self.inner.generate_id(),
+            attempt: 0,
             is_realized: false,
         }
     }
@@ -82,76 +164,152 @@ impl<T: ?Sized> OrderedMutex<T> {
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the OrderedMutex, so the mutex will not be dropped while a guard exists.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct OrderedMutexGuard<'a, T: ?Sized> {
-    mutex: &'a OrderedMutex<T>,
+pub struct OrderedMutexGuard<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedMutex<T, W>,
 }
 
+/// The reserved place in the queue of the OrderedMutex.
+/// The ticket acquires the mutex when awaited, and it may be sent to another task before that.
+/// Dropping the ticket before acquiring gives up its place, so the next tickets will not wait for it.
 #[derive(Debug)]
-pub struct OrderedMutexGuardFuture<'a, T: ?Sized> {
-    mutex: &'a OrderedMutex<T>,
+pub struct OrderedMutexTicket<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedMutex<T, W>,
     id: usize,
+    attempt: usize,
     is_realized: bool,
 }
 
+pub type OrderedMutexGuardFuture<'a, T, W = ImmediatePark> = OrderedMutexTicket<'a, T, W>;
+
 /// An owned handle to a held OrderedMutex.
 /// This guard is only available from a OrderedMutex that is wrapped in an `Arc`. It is identical to `OrderedMutexGuard`, except that rather than borrowing the `OrderedMutex`, it clones the `Arc`, incrementing the reference count. This means that unlike `OrderedMutexGuard`, it will have the `'static` lifetime.
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `OrderedMutex`, so even if the lock goes away, the guard remains valid.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct OrderedMutexOwnedGuard<T: ?Sized> {
-    mutex: Arc<OrderedMutex<T>>,
+pub struct OrderedMutexOwnedGuard<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedMutex<T, W>>,
 }
 
+/// The reserved place in the queue of the OrderedMutex, which is wrapped in an `Arc`.
+/// It is identical to `OrderedMutexTicket`, except that rather than borrowing the `OrderedMutex`, it clones the `Arc`. This means that unlike `OrderedMutexTicket`, it will have the `'static` lifetime.
 #[derive(Debug)]
-pub struct OrderedMutexOwnedGuardFuture<T: ?Sized> {
-    mutex: Arc<OrderedMutex<T>>,
+pub struct OrderedMutexOwnedTicket<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedMutex<T, W>>,
     id: usize,
+    attempt: usize,
     is_realized: bool,
 }
 
-impl<'a, T: ?Sized> Future for OrderedMutexGuardFuture<'a, T> {
-    type Output = OrderedMutexGuard<'a, T>;
+pub type OrderedMutexOwnedGuardFuture<T, W = ImmediatePark> = OrderedMutexOwnedTicket<T, W>;
+
+impl<T: ?Sized, W> OrderedMutexTicket<'_, T, W> {
+    /// Returns the position of the ticket in the queue of the mutex.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns `true` if it's the turn of the ticket, so awaiting it will acquire the mutex immediately.
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.mutex.inner.try_acquire(self.id)
+    }
+
+    /// Gives up the place in the queue. It's the same as dropping the ticket.
+    #[inline]
+    pub fn cancel(self) {
+        drop(self)
+    }
+}
+
+impl<T: ?Sized, W> OrderedMutexOwnedTicket<T, W> {
+    /// Returns the position of the ticket in the queue of the mutex.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns `true` if it's the turn of the ticket, so awaiting it will acquire the mutex immediately.
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.mutex.inner.try_acquire(self.id)
+    }
+
+    /// Gives up the place in the queue. It's the same as dropping the ticket.
+    #[inline]
+    pub fn cancel(self) {
+        drop(self)
+    }
+}
+
+impl<'a, T: ?Sized, W: WaitStrategy> Future for OrderedMutexTicket<'a, T, W> {
+    type Output = OrderedMutexGuard<'a, T, W>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire(self.id) {
-            self.is_realized = true;
-            Poll::Ready(OrderedMutexGuard { mutex: self.mutex })
+        let this = &mut *self;
+        if this
+            .mutex
+            .inner
+            .poll_acquire::<W>(this.id, cx.waker(), &mut this.attempt)
+        {
+            this.is_realized = true;
+            Poll::Ready(OrderedMutexGuard { mutex: this.mutex })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<T: ?Sized> Future for OrderedMutexOwnedGuardFuture<T> {
-    type Output = OrderedMutexOwnedGuard<T>;
+impl<T: ?Sized, W: WaitStrategy> Future for OrderedMutexOwnedTicket<T, W> {
+    type Output = OrderedMutexOwnedGuard<T, W>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire(self.id) {
-            self.is_realized = true;
+        let this = &mut *self;
+        if this
+            .mutex
+            .inner
+            .poll_acquire::<W>(this.id, cx.waker(), &mut this.attempt)
+        {
+            this.is_realized = true;
             Poll::Ready(OrderedMutexOwnedGuard {
-                mutex: self.mutex.clone(),
+                mutex: this.mutex.clone(),
             })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-crate::impl_send_sync_mutex!(OrderedMutex, OrderedMutexGuard, OrderedMutexOwnedGuard);
+crate::impl_send_sync_mutex!(OrderedMutex<W>, OrderedMutexGuard, OrderedMutexOwnedGuard);
 
-crate::impl_deref_mut!(OrderedMutexGuard, 'a);
-crate::impl_deref_mut!(OrderedMutexOwnedGuard);
+crate::impl_deref_mut!(OrderedMutexGuard<W>, 'a);
+crate::impl_deref_mut!(OrderedMutexOwnedGuard<W>);
 
-crate::impl_drop_guard!(OrderedMutexGuard, 'a, unlock);
-crate::impl_drop_guard!(OrderedMutexOwnedGuard, unlock);
-crate::impl_drop_guard_future!(OrderedMutexGuardFuture, 'a, unlock);
-crate::impl_drop_guard_future!(OrderedMutexOwnedGuardFuture, unlock);
+crate::impl_drop_guard!(OrderedMutexGuard<W>, 'a, unlock);
+crate::impl_drop_guard!(OrderedMutexOwnedGuard<W>, unlock);
+
+impl<T: ?Sized, W> Drop for OrderedMutexTicket<'_, T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.inner.cancel(self.id)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedMutexOwnedTicket<T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.inner.cancel(self.id)
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use crate::mutex_ordered::{OrderedMutex, OrderedMutexGuard, OrderedMutexOwnedGuard};
+    use crate::wait_strategy::{
+        Adaptive, SpinThenPark, WaitStrategy, WakeImmediately, YieldBackoff,
+    };
     use futures::executor::block_on;
     use futures::{FutureExt, StreamExt, TryStreamExt};
     use std::ops::AddAssign;
@@ -174,6 +332,28 @@ mod tests {
         assert_eq!(*co, 10000)
     }
 
+    async fn check_strategy<W: WaitStrategy>() {
+        let c: OrderedMutex<_, W> = OrderedMutex::with_strategy(Vec::new());
+        let tickets: Vec<_> = (0..100).map(|i| (i, c.reserve())).collect();
+
+        futures::stream::iter(tickets.into_iter().rev())
+            .for_each_concurrent(None, |(i, ticket)| async move {
+                ticket.await.push(i);
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, (0..100).collect::<Vec<_>>())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_wait_strategies() {
+        check_strategy::<WakeImmediately>().await;
+        check_strategy::<SpinThenPark>().await;
+        check_strategy::<YieldBackoff>().await;
+        check_strategy::<Adaptive>().await;
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
     async fn test_mutex_delay() {
         let expected_result = 100;
@@ -220,8 +400,8 @@ mod tests {
     async fn test_overflow() {
         let mut c = OrderedMutex::new(String::from("lol"));
 
-        c.inner.state = AtomicUsize::new(usize::max_value());
-        c.inner.current = AtomicUsize::new(usize::max_value());
+        c.inner.state = AtomicUsize::new(usize::MAX);
+        c.inner.current = AtomicUsize::new(usize::MAX);
 
         let mut co: OrderedMutexGuard<String> = c.lock().await;
         co.add_assign("lol");
@@ -249,6 +429,82 @@ mod tests {
         assert_eq!(*co, "lollol");
     }
 
+    #[tokio::test]
+    async fn test_cancelled_ticket() {
+        let c = OrderedMutex::new(0);
+
+        let first = c.reserve();
+        let second = c.reserve();
+        let third = c.reserve();
+        assert!(first.is_ready());
+
+        drop(second);
+        assert!(!third.is_ready());
+
+        let mut co = first.await;
+        *co += 1;
+        drop(co);
+
+        assert!(third.is_ready());
+        let co = tokio::time::timeout(Duration::from_millis(10), third)
+            .await
+            .expect("cancelled ticket must be skipped");
+        assert_eq!(*co, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_tickets_order() {
+        let c = Arc::new(OrderedMutex::new(Vec::new()));
+
+        let tickets: Vec<_> = (0..100).map(|_| c.reserve_owned()).collect();
+
+        let handles: Vec<_> = tickets
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(i, ticket)| {
+                tokio::spawn(async move {
+                    if i % 3 == 0 {
+                        ticket.cancel();
+                    } else {
+                        ticket.await.push(i);
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let expected: Vec<_> = (0..100).filter(|i| i % 3 != 0).collect();
+        assert_eq!(*c.lock().await, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_many_queued_tickets() {
+        let c = Arc::new(OrderedMutex::new(Vec::new()));
+        let guard = c.lock_owned().await;
+
+        let tickets: Vec<_> = (0..10000).map(|_| c.reserve_owned()).collect();
+        let handles: Vec<_> = tickets
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(i, ticket)| tokio::spawn(async move { ticket.await.push(i) }))
+            .collect();
+
+        // Let all tickets store their wakers, so most of them will overflow the ring.
+        sleep(Duration::from_millis(100)).await;
+        drop(guard);
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(*c.lock().await, (0..10000).collect::<Vec<_>>());
+    }
+
     #[test]
     fn multithreading_test() {
         let num = 100;
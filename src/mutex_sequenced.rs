@@ -0,0 +1,762 @@
+use crate::inner::{is_ahead, Inner, OrderedInner};
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// The Sequenced Mutex is acquired strictly in the order of the sequence numbers, which are passed by the callers.
+/// It will work well when the order comes from the outside, like sequence numbers of UDP packages from the wire.
+///
+/// Unlike the `OrderedMutex`, the order doesn't depend on the order of `lock` calls.
+/// The lock for the sequence number waits until all previous sequence numbers are unlocked or skipped.
+/// The missing sequence numbers may be skipped by `skip`, or by the gap timeout, so they can't stall the stream forever.
+/// Sequence numbers wrap around on overflow.
+///
+/// The same sequence number may be locked several times, like the duplicated packages.
+/// The first lock acquires the mutex, and the rest return an error, when the sequence number is passed.
+#[derive(Debug)]
+pub struct SequencedMutex<T: ?Sized> {
+    is_acquired: AtomicBool,
+    /// The key of the next lock future, which tells its waker apart from the duplicates of its sequence number.
+    next_key: AtomicUsize,
+    gap_timeout: Option<GapTimeout>,
+    stall: Inner<Option<Stall>>,
+    inner: OrderedInner<T>,
+}
+
+type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The gap timeout and the sleep of the runtime, which drives it.
+struct GapTimeout {
+    timeout: Duration,
+    sleep: Box<dyn Fn(Duration) -> Sleep + Send + Sync>,
+}
+
+impl Debug for GapTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GapTimeout")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// The sleep until the end of the gap timeout, which is armed only by the lowest waiting sequence number.
+/// It lives inside the lock future, so it's disarmed, when the future acquires the mutex or is dropped.
+struct GapSleep {
+    deadline: Instant,
+    sleep: Sleep,
+}
+
+impl Debug for GapSleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GapSleep")
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+/// The lowest waiting sequence number, which is blocked by the gap before it.
+/// The key tells apart the future, which arms the sleep, from the duplicates of its sequence number.
+#[derive(Debug, Clone, Copy)]
+struct Stall {
+    current: usize,
+    seq: usize,
+    key: usize,
+    since: Instant,
+}
+
+impl<T> SequencedMutex<T> {
+    /// Create a new `SequencedMutex`, which expects the sequence number `0` first.
+    #[inline]
+    pub const fn new(data: T) -> SequencedMutex<T> {
+        SequencedMutex::with_start(data, 0)
+    }
+
+    /// Create a new `SequencedMutex`, which expects the sequence number `start` first.
+    #[inline]
+    pub const fn with_start(data: T, start: usize) -> SequencedMutex<T> {
+        SequencedMutex {
+            is_acquired: AtomicBool::new(false),
+            next_key: AtomicUsize::new(0),
+            gap_timeout: None,
+            stall: Inner::new(None),
+            inner: OrderedInner::starting_at(data, start),
+        }
+    }
+
+    /// Sets the gap timeout, which is driven by the `sleep` of the async runtime, like `tokio::time::sleep`.
+    ///
+    /// If the expected sequence number doesn't come during the timeout, while later sequence numbers are waiting,
+    /// the mutex skips everything before the lowest waiting sequence number.
+    /// Only the future of the lowest waiting sequence number awaits the sleep, so the mutex doesn't spawn any threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_sequenced::SequencedMutex;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex =
+    ///         SequencedMutex::new(0).with_gap_timeout(Duration::from_millis(10), tokio::time::sleep);
+    ///     // The sequence number 0 is lost
+    ///     let guard = mutex.lock_at(1).await.unwrap();
+    ///     assert_eq!(guard.seq(), 1);
+    /// }
+    /// ```
+    #[inline]
+    pub fn with_gap_timeout<S, F>(mut self, timeout: Duration, sleep: S) -> SequencedMutex<T>
+    where
+        S: Fn(Duration) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.gap_timeout = Some(GapTimeout {
+            timeout,
+            sleep: Box::new(move |duration| Box::pin(sleep(duration))),
+        });
+        self
+    }
+}
+
+impl<T: ?Sized> SequencedMutex<T> {
+    /// Acquires the mutex for the sequence number.
+    ///
+    /// Waits until all previous sequence numbers are unlocked or skipped.
+    /// Returns an error if the sequence number was already passed, for example when it was skipped by the gap timeout.
+    /// Returns a guard that releases the mutex and passes it to the next sequence number when dropped.
+    /// Dropping the future doesn't skip the sequence number, so it may be locked again later.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_sequenced::SequencedMutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = SequencedMutex::new(Vec::new());
+    ///     let second = mutex.lock_at(1);
+    ///     let first = mutex.lock_at(0);
+    ///
+    ///     first.await.unwrap().push(0);
+    ///     second.await.unwrap().push(1);
+    ///     assert_eq!(*mutex.lock_at(2).await.unwrap(), vec![0, 1]);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock_at(&self, seq: usize) -> SequencedMutexGuardFuture<'_, T> {
+        SequencedMutexGuardFuture {
+            mutex: self,
+            seq,
+            key: self.next_key(),
+            sleep: None,
+        }
+    }
+
+    /// Acquires the mutex for the sequence number.
+    ///
+    /// Waits until all previous sequence numbers are unlocked or skipped.
+    /// Returns an error if the sequence number was already passed, for example when it was skipped by the gap timeout.
+    /// Returns a guard that releases the mutex and passes it to the next sequence number when dropped.
+    /// `SequencedMutexOwnedGuard` have a `'static` lifetime, but requires the `Arc<SequencedMutex<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_sequenced::SequencedMutex;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(SequencedMutex::with_start(10, 5));
+    ///     let guard = mutex.lock_at_owned(5).await.unwrap();
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock_at_owned(self: &Arc<Self>, seq: usize) -> SequencedMutexOwnedGuardFuture<T> {
+        SequencedMutexOwnedGuardFuture {
+            mutex: self.clone(),
+            seq,
+            key: self.next_key(),
+            sleep: None,
+        }
+    }
+
+    /// Marks the sequence number as missing, so the next sequence numbers don't wait for it.
+    /// The sequence numbers, which were already passed, are ignored.
+    /// The pending locks of the skipped sequence number return an error, when it's passed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::mutex_sequenced::SequencedMutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = SequencedMutex::new(0);
+    ///     mutex.skip(0);
+    ///     mutex.skip(1);
+    ///     assert_eq!(mutex.current(), 2);
+    ///     assert!(mutex.lock_at(0).await.is_err());
+    /// }
+    /// ```
+    #[inline]
+    pub fn skip(&self, seq: usize) {
+        self.inner.skip(seq);
+        // The gap may move, so the lowest waiting sequence number checks its turn and restarts its stall.
+        self.inner.wake_closest();
+    }
+
+    /// Returns the next expected sequence number.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.inner.current.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    fn next_key(&self) -> usize {
+        self.next_key.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn try_acquire(&self, seq: usize) -> Poll<Result<(), SkippedError>> {
+        let current = self.inner.current.load(Ordering::Acquire);
+        if is_ahead(current, seq) {
+            return Poll::Ready(Err(SkippedError { seq }));
+        }
+
+        if seq == current
+            && self
+                .is_acquired
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            // The current sequence number could be skipped before the flag was taken.
+            if self.inner.current.load(Ordering::Acquire) == seq {
+                return Poll::Ready(Ok(()));
+            }
+            self.is_acquired.store(false, Ordering::Release);
+        }
+
+        Poll::Pending
+    }
+
+    fn poll_acquire(
+        &self,
+        seq: usize,
+        key: usize,
+        cx: &mut Context<'_>,
+        sleep: &mut Option<GapSleep>,
+    ) -> Poll<Result<(), SkippedError>> {
+        loop {
+            if let Poll::Ready(result) = self.try_acquire(seq) {
+                return Poll::Ready(result);
+            }
+
+            self.inner.store_waker(seq, key, cx.waker());
+            // Only the lowest waiting sequence number sleeps until the end of the gap timeout,
+            // the later ones are woken by the unlocks, when the gap moves.
+            match (self.wait_for_gap(seq, key), &self.gap_timeout) {
+                (Some(deadline), Some(gap_timeout)) => {
+                    let armed = match sleep {
+                        Some(armed) if armed.deadline == deadline => armed,
+                        _ => sleep.insert(GapSleep {
+                            deadline,
+                            sleep: (gap_timeout.sleep)(
+                                deadline.saturating_duration_since(Instant::now()),
+                            ),
+                        }),
+                    };
+                    if armed.sleep.as_mut().poll(cx).is_ready() {
+                        // The gap is checked again, so it's skipped, if the deadline has passed.
+                        *sleep = None;
+                        continue;
+                    }
+                }
+                _ => *sleep = None,
+            }
+            return self.try_acquire(seq);
+        }
+    }
+
+    /// Registers the waiting sequence number and skips the gap before the lowest one if it lasts longer than the timeout.
+    /// Returns the end of the timeout, if the sequence number is the lowest waiting one and the gap is not skipped yet.
+    /// The replaced lowest sequence number is woken, so it disarms its sleep, and only one sleep is armed.
+    fn wait_for_gap(&self, seq: usize, key: usize) -> Option<Instant> {
+        let timeout = self.gap_timeout.as_ref()?.timeout;
+
+        let (stall, replaced) = self.stall.spin_lock(|stall| {
+            let current = self.inner.current.load(Ordering::SeqCst);
+            // The held sequence number is not a gap, so the stall is restarted after the release.
+            if !is_ahead(seq, current) || self.is_acquired.load(Ordering::SeqCst) {
+                *stall = None;
+                return (None, None);
+            }
+
+            let mut replaced = None;
+            match stall {
+                Some(stall) if stall.current == current => {
+                    if is_ahead(stall.seq, seq) {
+                        replaced = Some(stall.seq);
+                        stall.seq = seq;
+                        stall.key = key;
+                    }
+                }
+                _ => {
+                    *stall = Some(Stall {
+                        current,
+                        seq,
+                        key,
+                        since: Instant::now(),
+                    })
+                }
+            }
+            (*stall, replaced)
+        });
+        if let Some(replaced) = replaced {
+            self.inner.wake(replaced);
+        }
+
+        let stall = stall?;
+        let deadline = stall.since + timeout;
+        if Instant::now() >= deadline {
+            self.inner.skip_to(stall.seq);
+            None
+        } else if stall.seq == seq && stall.key == key {
+            Some(deadline)
+        } else {
+            None
+        }
+    }
+
+    /// Forgets the waker of the dropped future, and passes the stall to the next waiting sequence number,
+    /// when the lowest one stops waiting.
+    fn forget_waiter(&self, seq: usize, key: usize) {
+        self.inner.remove_waker(seq, key);
+        if self.gap_timeout.is_none() {
+            return;
+        }
+
+        let is_stalled = self.stall.spin_lock(|stall| match stall {
+            Some(stalled) if stalled.seq == seq && stalled.key == key => {
+                *stall = None;
+                true
+            }
+            _ => false,
+        });
+        if is_stalled {
+            self.inner.wake_closest();
+        }
+    }
+
+    #[inline]
+    fn unlock(&self, seq: usize) {
+        let is_woken = self.inner.unlock_from(seq);
+        self.is_acquired.store(false, Ordering::SeqCst);
+        // The duplicates of the released sequence number are passed, so they return the error.
+        self.inner.wake(seq);
+        // The current sequence number could be woken before the flag was released.
+        let is_woken = self.inner.wake_current() || is_woken;
+        if !is_woken && self.gap_timeout.is_some() {
+            // Nobody waits for the current sequence number, so the lowest waiting one starts the gap timeout.
+            self.inner.wake_closest();
+        }
+    }
+}
+
+/// The Simple SequencedMutex Guard
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the SequencedMutex, so the mutex will not be dropped while a guard exists.
+/// The lock is automatically released and passed to the next sequence number whenever the guard is dropped.
+#[derive(Debug)]
+pub struct SequencedMutexGuard<'a, T: ?Sized> {
+    mutex: &'a SequencedMutex<T>,
+    seq: usize,
+}
+
+#[derive(Debug)]
+pub struct SequencedMutexGuardFuture<'a, T: ?Sized> {
+    mutex: &'a SequencedMutex<T>,
+    seq: usize,
+    key: usize,
+    sleep: Option<GapSleep>,
+}
+
+/// An owned handle to a held SequencedMutex.
+/// This guard is only available from a SequencedMutex that is wrapped in an `Arc`. It is identical to `SequencedMutexGuard`, except that rather than borrowing the `SequencedMutex`, it clones the `Arc`, incrementing the reference count. This means that unlike `SequencedMutexGuard`, it will have the `'static` lifetime.
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `SequencedMutex`, so even if the lock goes away, the guard remains valid.
+/// The lock is automatically released and passed to the next sequence number whenever the guard is dropped.
+#[derive(Debug)]
+pub struct SequencedMutexOwnedGuard<T: ?Sized> {
+    mutex: Arc<SequencedMutex<T>>,
+    seq: usize,
+}
+
+#[derive(Debug)]
+pub struct SequencedMutexOwnedGuardFuture<T: ?Sized> {
+    mutex: Arc<SequencedMutex<T>>,
+    seq: usize,
+    key: usize,
+    sleep: Option<GapSleep>,
+}
+
+impl<T: ?Sized> SequencedMutexGuard<'_, T> {
+    /// Returns the sequence number, which holds the mutex.
+    #[inline]
+    pub fn seq(&self) -> usize {
+        self.seq
+    }
+}
+
+impl<T: ?Sized> SequencedMutexOwnedGuard<T> {
+    /// Returns the sequence number, which holds the mutex.
+    #[inline]
+    pub fn seq(&self) -> usize {
+        self.seq
+    }
+}
+
+impl<'a, T: ?Sized> Future for SequencedMutexGuardFuture<'a, T> {
+    type Output = Result<SequencedMutexGuard<'a, T>, SkippedError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this
+            .mutex
+            .poll_acquire(this.seq, this.key, cx, &mut this.sleep)
+        {
+            Poll::Ready(result) => Poll::Ready(result.map(|_| SequencedMutexGuard {
+                mutex: this.mutex,
+                seq: this.seq,
+            })),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: ?Sized> Future for SequencedMutexOwnedGuardFuture<T> {
+    type Output = Result<SequencedMutexOwnedGuard<T>, SkippedError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this
+            .mutex
+            .poll_acquire(this.seq, this.key, cx, &mut this.sleep)
+        {
+            Poll::Ready(result) => Poll::Ready(result.map(|_| SequencedMutexOwnedGuard {
+                mutex: this.mutex.clone(),
+                seq: this.seq,
+            })),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for SequencedMutexGuardFuture<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.forget_waiter(self.seq, self.key)
+    }
+}
+
+impl<T: ?Sized> Drop for SequencedMutexOwnedGuardFuture<T> {
+    fn drop(&mut self) {
+        self.mutex.forget_waiter(self.seq, self.key)
+    }
+}
+
+crate::impl_send_sync_mutex!(
+    SequencedMutex,
+    SequencedMutexGuard,
+    SequencedMutexOwnedGuard
+);
+
+crate::impl_deref_mut!(SequencedMutexGuard, 'a);
+crate::impl_deref_mut!(SequencedMutexOwnedGuard);
+
+impl<T: ?Sized> Drop for SequencedMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock(self.seq)
+    }
+}
+
+impl<T: ?Sized> Drop for SequencedMutexOwnedGuard<T> {
+    fn drop(&mut self) {
+        self.mutex.unlock(self.seq)
+    }
+}
+
+/// Error returned by `SequencedMutex::lock_at` when the sequence number was already passed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SkippedError {
+    seq: usize,
+}
+
+impl SkippedError {
+    /// Returns the sequence number, which was passed.
+    #[inline]
+    pub fn seq(&self) -> usize {
+        self.seq
+    }
+}
+
+impl Display for SkippedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sequence number {} was already passed", self.seq)
+    }
+}
+
+impl Error for SkippedError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::mutex_sequenced::{SequencedMutex, SequencedMutexOwnedGuard};
+    use futures::StreamExt;
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_sequence_order() {
+        let c = SequencedMutex::new(Vec::new());
+
+        futures::stream::iter((0..1000).rev())
+            .for_each_concurrent(None, |seq| {
+                let c = &c;
+                async move {
+                    let mut co = c.lock_at(seq).await.unwrap();
+                    co.push(seq);
+                }
+            })
+            .await;
+
+        let co = c.lock_at(1000).await.unwrap();
+        assert_eq!(*co, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_owned_sequence_order() {
+        let c = Arc::new(SequencedMutex::with_start(Vec::new(), usize::MAX - 500));
+
+        let handles: Vec<_> = (0..1000)
+            .rev()
+            .map(|i| {
+                let c = c.clone();
+                let seq = (usize::MAX - 500).wrapping_add(i);
+                tokio::spawn(async move {
+                    let mut co: SequencedMutexOwnedGuard<Vec<usize>> =
+                        c.lock_at_owned(seq).await.unwrap();
+                    co.push(i);
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(c.current(), 499);
+        let co = c.lock_at(499).await.unwrap();
+        assert_eq!(*co, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_skip() {
+        let c = SequencedMutex::new(Vec::new());
+
+        let third = tokio::time::timeout(Duration::from_millis(10), c.lock_at(2));
+        assert!(third.await.is_err());
+
+        c.skip(1);
+        assert_eq!(c.current(), 0);
+        c.skip(0);
+        assert_eq!(c.current(), 2);
+
+        c.lock_at(2).await.unwrap().push(2);
+        assert_eq!(c.lock_at(1).await.unwrap_err().seq(), 1);
+
+        // Skipping of passed sequence numbers is ignored
+        c.skip(2);
+        c.skip(4);
+        c.lock_at(3).await.unwrap().push(3);
+        assert_eq!(*c.lock_at(5).await.unwrap(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_skip_held() {
+        let c = SequencedMutex::new(0);
+
+        let co = c.lock_at(0).await.unwrap();
+        c.skip(0);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), c.lock_at(1))
+                .await
+                .is_err()
+        );
+        drop(co);
+
+        drop(c.lock_at(1).await.unwrap());
+        assert_eq!(c.current(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_skip_waiting() {
+        let c = Arc::new(SequencedMutex::new(0));
+        let co = c.lock_at(0).await.unwrap();
+
+        let waiting = {
+            let c = c.clone();
+            tokio::spawn(async move { c.lock_at_owned(1).await.map(|guard| guard.seq()) })
+        };
+        sleep(Duration::from_millis(10)).await;
+        c.skip(1);
+        drop(co);
+
+        let skipped = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(skipped.unwrap_err().seq(), 1);
+        assert_eq!(c.current(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_duplicates() {
+        let c = Arc::new(SequencedMutex::new(Vec::new()));
+        let mut co = c.lock_at(0).await.unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let c = c.clone();
+                tokio::spawn(async move {
+                    let mut co = c.lock_at_owned(1).await?;
+                    co.push(1);
+                    Ok::<_, crate::mutex_sequenced::SkippedError>(())
+                })
+            })
+            .collect();
+        sleep(Duration::from_millis(10)).await;
+        co.push(0);
+        drop(co);
+
+        let mut acquired = 0;
+        for handle in handles {
+            let result = tokio::time::timeout(Duration::from_secs(1), handle)
+                .await
+                .unwrap()
+                .unwrap();
+            match result {
+                Ok(()) => acquired += 1,
+                Err(err) => assert_eq!(err.seq(), 1),
+            }
+        }
+        assert_eq!(acquired, 1);
+        assert_eq!(*c.lock_at(2).await.unwrap(), vec![0, 1]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_gap_timeout() {
+        let c = Arc::new(
+            SequencedMutex::new(Vec::new()).with_gap_timeout(Duration::from_millis(500), sleep),
+        );
+        let mut co = c.lock_at(0).await.unwrap();
+
+        let handles: Vec<_> = vec![4, 2, 3, 6]
+            .into_iter()
+            .map(|seq| {
+                let c = c.clone();
+                tokio::spawn(async move {
+                    c.lock_at_owned(seq).await.unwrap().push(seq);
+                })
+            })
+            .collect();
+
+        sleep(Duration::from_millis(100)).await;
+        co.push(0);
+        drop(co);
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert!(c.lock_at(1).await.is_err());
+        assert_eq!(*c.lock_at(7).await.unwrap(), vec![0, 2, 3, 4, 6]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_gap_waiters_are_parked() {
+        let c =
+            Arc::new(SequencedMutex::new(0).with_gap_timeout(Duration::from_millis(200), sleep));
+        let polls = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = vec![3, 2]
+            .into_iter()
+            .map(|seq| {
+                let c = c.clone();
+                let polls = polls.clone();
+                tokio::spawn(async move {
+                    let mut guard = Box::pin(c.lock_at_owned(seq));
+                    futures::future::poll_fn(|cx| {
+                        polls.fetch_add(1, Ordering::SeqCst);
+                        guard.as_mut().poll(cx)
+                    })
+                    .await
+                    .unwrap()
+                    .seq()
+                })
+            })
+            .collect();
+
+        for (handle, seq) in handles.into_iter().zip(vec![3, 2]) {
+            assert_eq!(handle.await.unwrap(), seq);
+        }
+        assert_eq!(c.current(), 4);
+        assert!(polls.load(Ordering::SeqCst) <= 8);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_one_gap_sleep_armed() {
+        static ARMED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Armed;
+        impl Drop for Armed {
+            fn drop(&mut self) {
+                ARMED.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        let c = Arc::new(SequencedMutex::new(0).with_gap_timeout(
+            Duration::from_millis(200),
+            |duration| {
+                ARMED.fetch_add(1, Ordering::SeqCst);
+                let armed = Armed;
+                async move {
+                    sleep(duration).await;
+                    drop(armed);
+                }
+            },
+        ));
+
+        let mut handles = Vec::new();
+        for seq in [5, 4, 3, 2] {
+            let c = c.clone();
+            handles.push(tokio::spawn(async move {
+                c.lock_at_owned(seq).await.unwrap().seq()
+            }));
+            sleep(Duration::from_millis(10)).await;
+            assert_eq!(ARMED.load(Ordering::SeqCst), 1);
+        }
+
+        // The dropped lowest waiter disarms its sleep, and the next one arms its own.
+        handles.pop().unwrap().abort();
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(ARMED.load(Ordering::SeqCst), 1);
+
+        for (handle, seq) in handles.into_iter().zip(vec![5, 4, 3]) {
+            assert_eq!(handle.await.unwrap(), seq);
+        }
+        assert_eq!(ARMED.load(Ordering::SeqCst), 0);
+    }
+}
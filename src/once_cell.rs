@@ -0,0 +1,450 @@
+use crate::inner::WakerSet;
+use std::cell::UnsafeCell;
+use std::convert::Infallible;
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+const EMPTY: usize = 0;
+const RUNNING: usize = 1;
+const READY: usize = 2;
+
+/// The OnceCell is a cell which can be written only once, asynchronously.
+///
+/// Only one initializer future runs at the same time and others wait on it.
+/// If the running initializer is cancelled or fails, the next waiter takes over the initialization.
+pub struct OnceCell<T> {
+    state: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+    /// The wakers of the callers, which wait for the running initializer.
+    waiters: WakerSet,
+}
+
+impl<T> OnceCell<T> {
+    /// Create a new empty `OnceCell`
+    #[inline]
+    pub const fn new() -> OnceCell<T> {
+        OnceCell {
+            state: AtomicUsize::new(EMPTY),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            waiters: WakerSet::new(),
+        }
+    }
+
+    /// Returns a reference to the value, or `None` if the cell is not initialized yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::once_cell::OnceCell;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let cell = OnceCell::new();
+    ///     assert_eq!(cell.get(), None);
+    ///     cell.get_or_init(|| async { 10 }).await;
+    ///     assert_eq!(cell.get(), Some(&10));
+    /// }
+    /// ```
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) == READY {
+            Some(unsafe { self.get_unchecked() })
+        } else {
+            None
+        }
+    }
+
+    /// Sets the value of the cell.
+    ///
+    /// Returns the value back if the cell is already initialized or is being initialized right now.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::once_cell::OnceCell;
+    ///
+    /// let cell = OnceCell::new();
+    /// assert_eq!(cell.set(10), Ok(()));
+    /// assert_eq!(cell.set(20), Err(20));
+    /// assert_eq!(cell.get(), Some(&10));
+    /// ```
+    #[inline]
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.try_start() {
+            InitGuard { cell: self }.finish(value);
+            Ok(())
+        } else {
+            Err(value)
+        }
+    }
+
+    /// Returns a reference to the value, initializing it with the `init` future if the cell is empty.
+    ///
+    /// Only one `init` future runs at the same time, other callers wait for it.
+    /// If the running future is dropped before completion, one of the waiters runs its own `init`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::once_cell::OnceCell;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let cell = OnceCell::new();
+    ///     let value = cell.get_or_init(|| async { 10 }).await;
+    ///     assert_eq!(*value, 10);
+    ///     let value = cell.get_or_init(|| async { 20 }).await;
+    ///     assert_eq!(*value, 10);
+    /// }
+    /// ```
+    pub async fn get_or_init<F, Fut>(&self, init: F) -> &T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        match self
+            .get_or_try_init(|| async { Ok::<T, Infallible>(init().await) })
+            .await
+        {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Returns a reference to the value, initializing it with the fallible `init` future if the cell is empty.
+    ///
+    /// If `init` returns an error, the cell stays empty, the error is returned to the caller
+    /// and one of the waiters runs its own `init`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::once_cell::OnceCell;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let cell = OnceCell::new();
+    ///     let err = cell.get_or_try_init(|| async { Err::<i32, _>("fail") }).await;
+    ///     assert_eq!(err, Err("fail"));
+    ///     let value = cell.get_or_try_init(|| async { Ok::<_, ()>(10) }).await;
+    ///     assert_eq!(value, Ok(&10));
+    /// }
+    /// ```
+    pub async fn get_or_try_init<F, Fut, E>(&self, init: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+
+        match (InitFuture {
+            cell: self,
+            id: None,
+        })
+        .await
+        {
+            Some(guard) => Ok(guard.finish(init().await?)),
+            None => Ok(unsafe { self.get_unchecked() }),
+        }
+    }
+
+    /// Takes the value out of the cell, leaving it empty.
+    #[inline]
+    pub fn take(&mut self) -> Option<T> {
+        if *self.state.get_mut() == READY {
+            *self.state.get_mut() = EMPTY;
+            Some(unsafe { self.value.get_mut().as_ptr().read() })
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the cell, returning the wrapped value.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    #[inline]
+    fn try_start(&self) -> bool {
+        self.state
+            .compare_exchange(EMPTY, RUNNING, Ordering::Acquire, Ordering::Acquire)
+            .is_ok()
+    }
+
+    #[inline]
+    unsafe fn get_unchecked(&self) -> &T {
+        &*(*self.value.get()).as_ptr()
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        OnceCell::new()
+    }
+}
+
+impl<T: Debug> Debug for OnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceCell")
+            .field("value", &self.get())
+            .finish()
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+unsafe impl<T> Send for OnceCell<T> where T: Send {}
+unsafe impl<T> Sync for OnceCell<T> where T: Send + Sync {}
+
+/// Waits until the cell is initialized or until the caller becomes the initializer.
+struct InitFuture<'a, T> {
+    cell: &'a OnceCell<T>,
+    /// The id of the registered waker.
+    id: Option<usize>,
+}
+
+impl<'a, T> Future for InitFuture<'a, T> {
+    type Output = Option<InitGuard<'a, T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let cell = self.cell;
+        loop {
+            if cell.try_start() {
+                return Poll::Ready(Some(InitGuard { cell }));
+            }
+
+            // The state is checked again under the lock of the waiters, so the finished initializer doesn't miss the waker.
+            let mut state = RUNNING;
+            let is_registered = cell.waiters.register(&mut self.id, cx.waker(), || {
+                state = cell.state.load(Ordering::Acquire);
+                state == RUNNING
+            });
+
+            if is_registered {
+                return Poll::Pending;
+            } else if state == READY {
+                return Poll::Ready(None);
+            }
+            // The initializer was cancelled right now, so the future tries to take over.
+        }
+    }
+}
+
+impl<T> Drop for InitFuture<'_, T> {
+    fn drop(&mut self) {
+        self.cell.waiters.remove(self.id);
+    }
+}
+
+/// Returns the cell to the empty state if the initialization was not finished.
+struct InitGuard<'a, T> {
+    cell: &'a OnceCell<T>,
+}
+
+impl<'a, T> InitGuard<'a, T> {
+    fn finish(self, value: T) -> &'a T {
+        let cell = self.cell;
+        std::mem::forget(self);
+        unsafe { (*cell.value.get()).as_mut_ptr().write(value) };
+        cell.state.store(READY, Ordering::Release);
+        cell.waiters.wake_all();
+        unsafe { cell.get_unchecked() }
+    }
+}
+
+impl<T> Drop for InitGuard<'_, T> {
+    fn drop(&mut self) {
+        self.cell.state.store(EMPTY, Ordering::Release);
+        self.cell.waiters.wake_all();
+    }
+}
+
+/// The Lazy is a value which is initialized asynchronously on the first access.
+///
+/// `Lazy::new` is a `const fn`, so it may be used in `static`.
+/// The initializer may be called more than once if the initializing future is cancelled,
+/// that's why it should be a `Fn`.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::once_cell::Lazy;
+///
+/// static VALUE: Lazy<u32> = Lazy::new(|| Box::pin(async { 10 }));
+///
+/// #[tokio::main]
+/// async fn main() {
+///     assert_eq!(*VALUE.force().await, 10);
+///     assert_eq!(VALUE.get(), Some(&10));
+/// }
+/// ```
+pub struct Lazy<T, F = fn() -> Pin<Box<dyn Future<Output = T> + Send>>> {
+    cell: OnceCell<T>,
+    init: F,
+}
+
+impl<T, F> Lazy<T, F> {
+    /// Create a new `Lazy` with the given initializer
+    #[inline]
+    pub const fn new(init: F) -> Lazy<T, F> {
+        Lazy {
+            cell: OnceCell::new(),
+            init,
+        }
+    }
+
+    /// Returns a reference to the value, or `None` if it is not initialized yet.
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        self.cell.get()
+    }
+}
+
+impl<T, F, Fut> Lazy<T, F>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = T>,
+{
+    /// Returns a reference to the value, initializing it if needed.
+    pub async fn force(&self) -> &T {
+        self.cell.get_or_init(|| (self.init)()).await
+    }
+}
+
+impl<T: Debug, F> Debug for Lazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lazy").field("cell", &self.cell).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::once_cell::{Lazy, OnceCell};
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_init_once() {
+        let cell = OnceCell::new();
+        let calls = AtomicUsize::new(0);
+
+        futures::future::join_all((0..100).map(|_| {
+            cell.get_or_init(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_millis(10)).await;
+                10
+            })
+        }))
+        .await
+        .into_iter()
+        .for_each(|v| assert_eq!(*v, 10));
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_try_init_error() {
+        let cell = OnceCell::new();
+
+        let res = cell.get_or_try_init(|| async { Err::<i32, _>(()) }).await;
+        assert_eq!(res, Err(()));
+        assert_eq!(cell.get(), None);
+
+        let res = cell.get_or_try_init(|| async { Ok::<_, ()>(10) }).await;
+        assert_eq!(res, Ok(&10));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_cancelled_init() {
+        let cell = Arc::new(OnceCell::new());
+
+        let res = tokio::time::timeout(
+            Duration::from_millis(10),
+            cell.get_or_init(futures::future::pending::<i32>),
+        )
+        .await;
+        assert!(res.is_err());
+
+        let waiter = {
+            let cell = cell.clone();
+            tokio::spawn(async move { *cell.get_or_init(|| async { 20 }).await })
+        };
+
+        assert_eq!(waiter.await.unwrap(), 20);
+        assert_eq!(cell.get(), Some(&20));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_waiters_are_woken_once() {
+        let cell = Arc::new(OnceCell::new());
+        let polls = Arc::new(AtomicUsize::new(0));
+
+        let init = {
+            let cell = cell.clone();
+            tokio::spawn(async move {
+                *cell
+                    .get_or_init(|| async {
+                        sleep(Duration::from_millis(100)).await;
+                        10
+                    })
+                    .await
+            })
+        };
+        sleep(Duration::from_millis(10)).await;
+
+        let waiter = {
+            let cell = cell.clone();
+            let polls = polls.clone();
+            tokio::spawn(async move {
+                let mut value = Box::pin(cell.get_or_init(|| async { 20 }));
+                futures::future::poll_fn(|cx| {
+                    polls.fetch_add(1, Ordering::SeqCst);
+                    value.as_mut().poll(cx).map(|value| *value)
+                })
+                .await
+            })
+        };
+
+        assert_eq!(init.await.unwrap(), 10);
+        assert_eq!(waiter.await.unwrap(), 10);
+        assert!(polls.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_set() {
+        let cell = OnceCell::new();
+        assert_eq!(cell.set(String::from("lol")), Ok(()));
+        assert_eq!(cell.set(String::from("kek")), Err(String::from("kek")));
+
+        let value = cell.get_or_init(|| async { String::from("kek") }).await;
+        assert_eq!(value, "lol");
+        assert_eq!(cell.into_inner(), Some(String::from("lol")));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_lazy_static() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        static LAZY: Lazy<usize> =
+            Lazy::new(|| Box::pin(async { CALLS.fetch_add(1, Ordering::SeqCst) + 10 }));
+
+        futures::future::join_all((0..100).map(|_| LAZY.force()))
+            .await
+            .into_iter()
+            .for_each(|v| assert_eq!(*v, 10));
+
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+}
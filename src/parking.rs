@@ -0,0 +1,125 @@
+use crate::inner::{grow, Inner};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Waker;
+
+/// The number of buckets of the parking table, it's a power of two.
+const BUCKETS_BITS: u32 = 8;
+const BUCKETS: usize = 1 << BUCKETS_BITS;
+
+/// The waker of the locker, which is parked on the lock with the `key` address.
+#[derive(Debug)]
+struct Parked {
+    key: usize,
+    id: usize,
+    waker: Waker,
+}
+
+/// The bucket is aligned to the cache line, so the lockers of different buckets don't contend on it.
+/// The lockers are queued in the order of parking, so the oldest one is usually at the front.
+#[derive(Debug)]
+#[repr(align(64))]
+struct Bucket {
+    parked: Inner<VecDeque<Parked>>,
+}
+
+// The parked lockers are accessed only under the lock of the bucket.
+unsafe impl Sync for Bucket {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_BUCKET: Bucket = Bucket {
+    parked: Inner::new(VecDeque::new()),
+};
+
+/// The global table of the parked lockers, which is shared by all compact locks, so the locks don't keep waiters themselves.
+/// The lockers are hashed into the buckets by the address of the lock, so the lock must not move while lockers are parked on it.
+static TABLE: [Bucket; BUCKETS] = [EMPTY_BUCKET; BUCKETS];
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+#[inline]
+fn bucket(key: usize) -> &'static Bucket {
+    // The Fibonacci hashing spreads the aligned addresses over the buckets.
+    let hash = (key as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - BUCKETS_BITS);
+    &TABLE[hash as usize]
+}
+
+/// Returns the id of the new parked locker. The id is never `0`.
+#[inline]
+pub(crate) fn next_id() -> usize {
+    loop {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// Parks the locker on the `key`, if `validate` returns `true` under the lock of the bucket.
+/// The locker, which is parked already, keeps its place and only updates the waker.
+///
+/// The unpark of the same `key` takes the lock of the bucket too, so the validated state isn't changed without waking the locker.
+/// The waker is cloned and the full bucket is grown outside of the lock, and the unused clone is dropped outside of it too.
+pub(crate) fn park(key: usize, id: usize, waker: &Waker, validate: impl FnOnce() -> bool) -> bool {
+    let bucket = bucket(key);
+    let mut validate = Some(validate);
+    let mut waker = Some(waker.clone());
+    loop {
+        let is_parked = bucket.parked.spin_lock(|parked| {
+            let index = parked.iter().position(|parked| parked.id == id);
+            if index.is_none() && parked.len() == parked.capacity() {
+                return None;
+            }
+            if !validate.take().expect("the locker is validated once")() {
+                return Some(false);
+            }
+
+            match index {
+                // The old waker is swapped out, so it's dropped outside of the lock.
+                Some(index) => {
+                    let stored = &mut parked[index].waker;
+                    if let Some(waker) = waker.as_mut().filter(|waker| !stored.will_wake(waker)) {
+                        std::mem::swap(stored, waker);
+                    }
+                }
+                None => parked.extend(waker.take().map(|waker| Parked { key, id, waker })),
+            }
+            Some(true)
+        });
+        match is_parked {
+            Some(is_parked) => return is_parked,
+            None => grow(&bucket.parked, 4),
+        }
+    }
+}
+
+/// Unparks the oldest locker of the `key`.
+/// The `callback` is called under the lock of the bucket with `true` if other lockers are still parked on the `key`.
+pub(crate) fn unpark_one(key: usize, callback: impl FnOnce(bool)) {
+    let waker = bucket(key).parked.spin_lock(|parked| {
+        let waker = parked
+            .iter()
+            .position(|parked| parked.key == key)
+            .and_then(|i| parked.remove(i))
+            .map(|parked| parked.waker);
+        callback(parked.iter().any(|parked| parked.key == key));
+        waker
+    });
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Removes the parked locker of the `key`.
+/// Returns `false` if it was unparked already.
+pub(crate) fn remove(key: usize, id: usize) -> bool {
+    let removed = bucket(key).parked.spin_lock(|parked| {
+        parked
+            .iter()
+            .position(|parked| parked.key == key && parked.id == id)
+            .and_then(|i| parked.remove(i))
+    });
+    // The waker is dropped outside of the lock.
+    removed.is_some()
+}
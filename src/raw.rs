@@ -0,0 +1,291 @@
+use crate::inner::{Acquisition, HotInner, Inner, WaitQueue, Waiting};
+use crate::readers::Readers;
+use crate::wait_strategy::{WaitStrategy, WakeImmediately};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
+
+/// The algorithm of the asynchronous mutex, which is plugged into the `raw_mutex::Mutex<R, T>`,
+/// so the guards, the owned guards, the mapped guards and the lock futures are provided by the crate.
+///
+/// # Safety
+///
+/// The implementation must guarantee, that the mutex is held by at most one locker at the same time,
+/// and that the acquiring of the mutex synchronizes with its previous release.
+/// The guards are held across `.await`, so the mutex must allow to be released by another thread, than it was acquired by.
+pub unsafe trait RawAsyncMutex {
+    /// The released mutex, so the mutex may be created in the const context.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self;
+
+    /// The state of the locker, which lives in its pinned lock future, like the node of the intrusive queue of waiters.
+    type Waiter: Default + Debug;
+
+    /// Tries to acquire the mutex without waiting.
+    fn try_lock(&self) -> bool;
+
+    /// Tries to acquire the mutex, or makes sure, that the task is woken, when it should try again.
+    /// The `waiter` is the same on every poll of the locker.
+    fn poll_lock(&self, waiter: Pin<&mut Self::Waiter>, cx: &mut Context<'_>) -> Poll<()>;
+
+    /// Gives up the waiting of the locker, whose future is dropped before it acquired the mutex.
+    #[inline]
+    fn cancel(&self, waiter: Pin<&mut Self::Waiter>) {
+        let _ = waiter;
+    }
+
+    /// Releases the mutex.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be held by the caller.
+    unsafe fn unlock(&self);
+}
+
+/// The algorithm of the asynchronous RW Lock, which is plugged into the `raw_rwlock::RwLock<R, T>`,
+/// so the guards, the owned guards, the mapped guards and the lock futures are provided by the crate.
+///
+/// # Safety
+///
+/// The implementation must guarantee, that the exclusive lock is never held together with any other lock,
+/// and that the acquiring of the lock synchronizes with the previous release of the exclusive lock.
+/// The guards are held across `.await`, so the lock must allow to be released by another thread, than it was acquired by.
+pub unsafe trait RawAsyncRwLock {
+    /// The released lock, so the lock may be created in the const context.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self;
+
+    /// The state of the locker, which lives in its pinned lock future, like the node of the intrusive queue of waiters.
+    type Waiter: Default + Debug;
+
+    /// Tries to acquire the shared lock without waiting.
+    fn try_lock_shared(&self) -> bool;
+
+    /// Tries to acquire the shared lock, or makes sure, that the task is woken, when it should try again.
+    /// The `waiter` is the same on every poll of the locker.
+    fn poll_lock_shared(&self, waiter: Pin<&mut Self::Waiter>, cx: &mut Context<'_>) -> Poll<()>;
+
+    /// Releases the shared lock.
+    ///
+    /// # Safety
+    ///
+    /// The shared lock must be held by the caller.
+    unsafe fn unlock_shared(&self);
+
+    /// Tries to acquire the exclusive lock without waiting.
+    fn try_lock_exclusive(&self) -> bool;
+
+    /// Tries to acquire the exclusive lock, or makes sure, that the task is woken, when it should try again.
+    /// The `waiter` is the same on every poll of the locker.
+    fn poll_lock_exclusive(&self, waiter: Pin<&mut Self::Waiter>, cx: &mut Context<'_>)
+        -> Poll<()>;
+
+    /// Releases the exclusive lock.
+    ///
+    /// # Safety
+    ///
+    /// The exclusive lock must be held by the caller.
+    unsafe fn unlock_exclusive(&self);
+
+    /// Gives up the waiting of the locker, whose future is dropped before it acquired the lock.
+    #[inline]
+    fn cancel(&self, waiter: Pin<&mut Self::Waiter>) {
+        let _ = waiter;
+    }
+}
+
+/// The waiter of the `RawMutex` and the `RawRwLock`, which is linked into the queue of the lock.
+#[derive(Debug)]
+pub struct RawWaiter {
+    waiting: Waiting,
+}
+
+impl Default for RawWaiter {
+    #[inline]
+    fn default() -> Self {
+        RawWaiter {
+            waiting: Waiting::new(),
+        }
+    }
+}
+
+impl RawWaiter {
+    /// The waiting is shared with the queue of the lock, so the waiter is never borrowed mutably.
+    #[inline]
+    fn waiting(self: Pin<&mut Self>) -> &Waiting {
+        &self.into_ref().get_ref().waiting
+    }
+}
+
+/// The algorithm of the `Mutex`: the eventually fair mutex, whose contended lockers wait by the `WaitStrategy`.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::raw::RawMutex;
+/// use fast_async_mutex::raw_mutex::Mutex;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mutex: Mutex<RawMutex, _> = Mutex::new(10);
+///     let guard = mutex.lock().await;
+///     assert_eq!(*guard, 10);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RawMutex<W = WakeImmediately> {
+    queue: WaitQueue,
+    strategy: PhantomData<fn() -> W>,
+    inner: HotInner<()>,
+}
+
+// The state is accessed only by the atomics and under the spin lock of the queue.
+unsafe impl<W> Send for RawMutex<W> {}
+unsafe impl<W> Sync for RawMutex<W> {}
+
+unsafe impl<W: WaitStrategy> RawAsyncMutex for RawMutex<W> {
+    const INIT: Self = RawMutex {
+        queue: WaitQueue::new(),
+        strategy: PhantomData,
+        inner: Inner::new(()),
+    };
+
+    type Waiter = RawWaiter;
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        self.queue.try_acquire::<W, _>(&self.inner)
+    }
+
+    #[inline]
+    fn poll_lock(&self, waiter: Pin<&mut Self::Waiter>, cx: &mut Context<'_>) -> Poll<()> {
+        match self
+            .queue
+            .poll_acquire::<W>(waiter.waiting(), cx.waker(), || self.try_lock())
+        {
+            Acquisition::Pending => Poll::Pending,
+            _ => Poll::Ready(()),
+        }
+    }
+
+    #[inline]
+    fn cancel(&self, waiter: Pin<&mut Self::Waiter>) {
+        self.queue.cancel(&self.inner, waiter.waiting())
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        self.queue.unlock(&self.inner)
+    }
+}
+
+/// The algorithm of the `RwLock`: the readers join the held read lock, and the waiting writer is handed the lock eventually.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::raw::RawRwLock;
+/// use fast_async_mutex::raw_rwlock::RwLock;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let lock: RwLock<RawRwLock, _> = RwLock::new(10);
+///     *lock.write().await += 1;
+///     assert_eq!(*lock.read().await, 11);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RawRwLock<W = WakeImmediately> {
+    pub(crate) readers: Readers,
+    pub(crate) queue: WaitQueue,
+    strategy: PhantomData<fn() -> W>,
+    inner: HotInner<()>,
+    /// Only one holder of the read lock may upgrade it to the write lock, so the upgradable lockers queue on it.
+    #[cfg(feature = "lock_api")]
+    pub(crate) upgradable: RawMutex<W>,
+}
+
+/// The bit of the readers counter, which is set by the upgrading reader, so the new readers don't join the read lock.
+pub(crate) const UPGRADING: usize = !(usize::MAX >> 1);
+
+// The state is accessed only by the atomics and under the spin lock of the queue.
+unsafe impl<W> Send for RawRwLock<W> {}
+unsafe impl<W> Sync for RawRwLock<W> {}
+
+unsafe impl<W: WaitStrategy> RawAsyncRwLock for RawRwLock<W> {
+    const INIT: Self = RawRwLock {
+        readers: Readers::new(),
+        queue: WaitQueue::new(),
+        strategy: PhantomData,
+        inner: Inner::new(()),
+        #[cfg(feature = "lock_api")]
+        upgradable: RawMutex::INIT,
+    };
+
+    type Waiter = RawWaiter;
+
+    /// The reader, which doesn't wait, arrives in the current read phase.
+    #[inline]
+    fn try_lock_shared(&self) -> bool {
+        let arrival = self.readers.phase.load(Ordering::SeqCst);
+        self.readers
+            .try_acquire::<W, _>(&self.queue, &self.inner, arrival, false, UPGRADING)
+    }
+
+    /// The upgrading reader stops the new readers from joining the read lock.
+    #[inline]
+    fn poll_lock_shared(&self, waiter: Pin<&mut Self::Waiter>, cx: &mut Context<'_>) -> Poll<()> {
+        let is_acquired = self.readers.poll_acquire::<W, _>(
+            &self.queue,
+            &self.inner,
+            waiter.waiting(),
+            cx.waker(),
+            false,
+            UPGRADING,
+        );
+        if is_acquired {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    #[inline]
+    unsafe fn unlock_shared(&self) {
+        self.readers.release(&self.queue, &self.inner)
+    }
+
+    #[inline]
+    fn try_lock_exclusive(&self) -> bool {
+        self.queue.try_acquire::<W, _>(&self.inner)
+    }
+
+    #[inline]
+    fn poll_lock_exclusive(
+        &self,
+        waiter: Pin<&mut Self::Waiter>,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        let waiting = waiter.waiting();
+        waiting.mark_exclusive();
+        match self
+            .queue
+            .poll_acquire::<W>(waiting, cx.waker(), || self.try_lock_exclusive())
+        {
+            Acquisition::Pending => Poll::Pending,
+            _ => Poll::Ready(()),
+        }
+    }
+
+    #[inline]
+    unsafe fn unlock_exclusive(&self) {
+        self.queue.unlock(&self.inner)
+    }
+
+    #[inline]
+    fn cancel(&self, waiter: Pin<&mut Self::Waiter>) {
+        self.queue.cancel(&self.inner, waiter.waiting())
+    }
+}
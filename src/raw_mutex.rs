@@ -0,0 +1,494 @@
+use crate::raw::RawAsyncMutex;
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The Mutex over the `RawAsyncMutex` algorithm, like the `lock_api::Mutex` for the synchronous locks.
+/// The algorithm provides only the locking, and the mutex provides the data, the guards, the owned and the mapped guards.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::raw::RawMutex;
+/// use fast_async_mutex::raw_mutex::{Mutex, MutexGuard};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mutex: Mutex<RawMutex, _> = Mutex::new((1, 2));
+///     let mut guard = MutexGuard::map(mutex.lock().await, |data| &mut data.1);
+///     *guard += 1;
+///     drop(guard);
+///     assert_eq!(*mutex.lock().await, (1, 3));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Mutex<R, T: ?Sized> {
+    raw: R,
+    data: UnsafeCell<T>,
+}
+
+impl<R: RawAsyncMutex, T> Mutex<R, T> {
+    /// Create a new `Mutex`
+    #[inline]
+    pub const fn new(data: T) -> Mutex<R, T> {
+        Mutex::from_raw(R::INIT, data)
+    }
+}
+
+impl<R, T> Mutex<R, T> {
+    /// Create a new `Mutex` over the given algorithm
+    #[inline]
+    pub const fn from_raw(raw: R, data: T) -> Mutex<R, T> {
+        Mutex {
+            raw,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Returns the data of the mutex.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Mutex<R, T> {
+    /// Acquires the mutex.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::raw::RawMutex;
+    /// use fast_async_mutex::raw_mutex::Mutex;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex: Mutex<RawMutex, _> = Mutex::new(10);
+    ///     let guard = mutex.lock().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock(&self) -> MutexGuardFuture<'_, R, T> {
+        MutexGuardFuture {
+            mutex: self,
+            waiter: R::Waiter::default(),
+            is_acquired: false,
+        }
+    }
+
+    /// Acquires the mutex.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when dropped.
+    /// `MutexOwnedGuard` have a `'static` lifetime, but requires the `Arc<Mutex<R, T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::raw::RawMutex;
+    /// use fast_async_mutex::raw_mutex::Mutex;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex: Arc<Mutex<RawMutex, _>> = Arc::new(Mutex::new(10));
+    ///     let guard = mutex.lock_owned().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn lock_owned(self: &Arc<Self>) -> MutexOwnedGuardFuture<R, T> {
+        MutexOwnedGuardFuture {
+            mutex: self.clone(),
+            waiter: R::Waiter::default(),
+            is_acquired: false,
+        }
+    }
+
+    /// Acquires the mutex, if it's released, without waiting.
+    #[inline]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, R, T>> {
+        if self.raw.try_lock() {
+            Some(MutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    /// Acquires the mutex, if it's released, without waiting.
+    #[inline]
+    pub fn try_lock_owned(self: &Arc<Self>) -> Option<MutexOwnedGuard<R, T>> {
+        if self.raw.try_lock() {
+            Some(MutexOwnedGuard {
+                mutex: self.clone(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<R, T: ?Sized> Mutex<R, T> {
+    /// Returns the mutable reference to the data, the mutex isn't acquired, because it's borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Returns the algorithm of the mutex, so the same mutex may be acquired by other means, like the blocking `lock_api` traits.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must not be released, while it's held by a guard.
+    #[inline]
+    pub unsafe fn raw(&self) -> &R {
+        &self.raw
+    }
+
+    /// Returns the raw pointer to the data, which may be accessed only while the mutex is held.
+    #[inline]
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+}
+
+/// The Mutex Guard of the `Mutex<R, T>`
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the Mutex, so the mutex will not be dropped while a guard exists.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct MutexGuard<'a, R: RawAsyncMutex, T: ?Sized> {
+    mutex: &'a Mutex<R, T>,
+}
+
+/// An owned handle to a held `Mutex<R, T>`.
+/// This guard is only available from a Mutex that is wrapped in an `Arc`. It is identical to `MutexGuard`, except that rather than borrowing the `Mutex`, it clones the `Arc`, incrementing the reference count. This means that unlike `MutexGuard`, it will have the `'static` lifetime.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct MutexOwnedGuard<R: RawAsyncMutex, T: ?Sized> {
+    mutex: Arc<Mutex<R, T>>,
+}
+
+/// The guard of the part of the data, which is returned by `MutexGuard::map`.
+/// The lock is automatically released whenever the guard is dropped.
+#[derive(Debug)]
+pub struct MappedMutexGuard<'a, R: RawAsyncMutex, T: ?Sized> {
+    raw: &'a R,
+    data: *mut T,
+    marker: PhantomData<&'a mut T>,
+}
+
+#[derive(Debug)]
+pub struct MutexGuardFuture<'a, R: RawAsyncMutex, T: ?Sized> {
+    mutex: &'a Mutex<R, T>,
+    waiter: R::Waiter,
+    is_acquired: bool,
+}
+
+#[derive(Debug)]
+pub struct MutexOwnedGuardFuture<R: RawAsyncMutex, T: ?Sized> {
+    mutex: Arc<Mutex<R, T>>,
+    waiter: R::Waiter,
+    is_acquired: bool,
+}
+
+impl<'a, R: RawAsyncMutex, T: ?Sized> MutexGuard<'a, R, T> {
+    /// Makes the guard of the part of the data, like the field of the struct.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        guard: MutexGuard<'a, R, T>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedMutexGuard<'a, R, U> {
+        let mutex = guard.mutex;
+        // The mapped guard releases the mutex instead of the guard.
+        std::mem::forget(guard);
+        MappedMutexGuard {
+            raw: &mutex.raw,
+            data: f(unsafe { &mut *mutex.data.get() }),
+            marker: PhantomData,
+        }
+    }
+
+    /// Makes the guard of the part of the data, or returns the guard back, if the part doesn't exist.
+    #[inline]
+    pub fn try_map<U: ?Sized>(
+        guard: MutexGuard<'a, R, T>,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<MappedMutexGuard<'a, R, U>, MutexGuard<'a, R, T>> {
+        let mutex = guard.mutex;
+        match f(unsafe { &mut *mutex.data.get() }) {
+            Some(data) => {
+                std::mem::forget(guard);
+                Ok(MappedMutexGuard {
+                    raw: &mutex.raw,
+                    data,
+                    marker: PhantomData,
+                })
+            }
+            None => Err(guard),
+        }
+    }
+}
+
+impl<'a, R: RawAsyncMutex, T: ?Sized> MappedMutexGuard<'a, R, T> {
+    /// Makes the guard of the part of the mapped data.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        guard: MappedMutexGuard<'a, R, T>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedMutexGuard<'a, R, U> {
+        let raw = guard.raw;
+        let data = f(unsafe { &mut *guard.data });
+        std::mem::forget(guard);
+        MappedMutexGuard {
+            raw,
+            data,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, R: RawAsyncMutex, T: ?Sized> Future for MutexGuardFuture<'a, R, T> {
+    type Output = MutexGuard<'a, R, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The waiter is pinned with the future, and other fields aren't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let waiter = unsafe { Pin::new_unchecked(&mut this.waiter) };
+        match this.mutex.raw.poll_lock(waiter, cx) {
+            Poll::Ready(()) => {
+                this.is_acquired = true;
+                Poll::Ready(MutexGuard { mutex: this.mutex })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Future for MutexOwnedGuardFuture<R, T> {
+    type Output = MutexOwnedGuard<R, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The waiter is pinned with the future, and other fields aren't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let waiter = unsafe { Pin::new_unchecked(&mut this.waiter) };
+        match this.mutex.raw.poll_lock(waiter, cx) {
+            Poll::Ready(()) => {
+                this.is_acquired = true;
+                Poll::Ready(MutexOwnedGuard {
+                    mutex: this.mutex.clone(),
+                })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+unsafe impl<R: Send, T: ?Sized + Send> Send for Mutex<R, T> {}
+unsafe impl<R: Sync, T: ?Sized + Send> Sync for Mutex<R, T> {}
+
+unsafe impl<R: RawAsyncMutex + Sync, T: ?Sized + Send> Send for MutexGuard<'_, R, T> {}
+unsafe impl<R: RawAsyncMutex + Sync, T: ?Sized + Send + Sync> Sync for MutexGuard<'_, R, T> {}
+
+unsafe impl<R: RawAsyncMutex + Send + Sync, T: ?Sized + Send> Send for MutexOwnedGuard<R, T> {}
+unsafe impl<R: RawAsyncMutex + Send + Sync, T: ?Sized + Send + Sync> Sync
+    for MutexOwnedGuard<R, T>
+{
+}
+
+unsafe impl<R: RawAsyncMutex + Sync, T: ?Sized + Send> Send for MappedMutexGuard<'_, R, T> {}
+unsafe impl<R: RawAsyncMutex + Sync, T: ?Sized + Send + Sync> Sync for MappedMutexGuard<'_, R, T> {}
+
+impl<R: RawAsyncMutex, T: ?Sized> Deref for MutexGuard<'_, R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> DerefMut for MutexGuard<'_, R, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Deref for MutexOwnedGuard<R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> DerefMut for MutexOwnedGuard<R, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Deref for MappedMutexGuard<'_, R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> DerefMut for MappedMutexGuard<'_, R, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Drop for MutexGuard<'_, R, T> {
+    fn drop(&mut self) {
+        unsafe { self.mutex.raw.unlock() }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Drop for MutexOwnedGuard<R, T> {
+    fn drop(&mut self) {
+        unsafe { self.mutex.raw.unlock() }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Drop for MappedMutexGuard<'_, R, T> {
+    fn drop(&mut self) {
+        unsafe { self.raw.unlock() }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Drop for MutexGuardFuture<'_, R, T> {
+    fn drop(&mut self) {
+        if !self.is_acquired {
+            // The future is pinned, so it's dropped in place.
+            let waiter = unsafe { Pin::new_unchecked(&mut self.waiter) };
+            self.mutex.raw.cancel(waiter)
+        }
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> Drop for MutexOwnedGuardFuture<R, T> {
+    fn drop(&mut self) {
+        if !self.is_acquired {
+            // The future is pinned, so it's dropped in place.
+            let waiter = unsafe { Pin::new_unchecked(&mut self.waiter) };
+            self.mutex.raw.cancel(waiter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{RawAsyncMutex, RawMutex};
+    use crate::raw_mutex::{MappedMutexGuard, Mutex, MutexGuard, MutexOwnedGuard};
+    use crate::traits::suite::check_mutex;
+    use crate::wait_strategy::{ImmediatePark, SpinThenPark};
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use tokio::time::{timeout, Duration};
+
+    /// The simplest algorithm, which is plugged by the user: the flag, whose lockers wake themselves.
+    #[derive(Debug)]
+    struct SpinMutex(AtomicBool);
+
+    unsafe impl RawAsyncMutex for SpinMutex {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = SpinMutex(AtomicBool::new(false));
+
+        type Waiter = ();
+
+        fn try_lock(&self) -> bool {
+            self.0
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        }
+
+        fn poll_lock(&self, _waiter: Pin<&mut ()>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.try_lock() {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        unsafe fn unlock(&self) {
+            self.0.store(false, Ordering::Release)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_mutex() {
+        let c: Mutex<RawMutex, _> = Mutex::new(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: MutexGuard<_, i32> = c.lock().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_owned_mutex() {
+        let c: Arc<Mutex<RawMutex<ImmediatePark>, _>> = Arc::new(Mutex::new(0));
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: MutexOwnedGuard<_, i32> = c.lock_owned().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.lock_owned().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[test]
+    fn test_suite() {
+        check_mutex::<Mutex<RawMutex, usize>>();
+        check_mutex::<Mutex<RawMutex<SpinThenPark>, usize>>();
+        check_mutex::<Mutex<SpinMutex, usize>>();
+    }
+
+    #[tokio::test]
+    async fn test_try_lock() {
+        let c: Mutex<SpinMutex, _> = Mutex::new(0);
+
+        let co = c.try_lock().unwrap();
+        assert!(c.try_lock().is_none());
+        assert!(timeout(Duration::from_millis(1), c.lock()).await.is_err());
+        drop(co);
+
+        *c.try_lock().unwrap() += 1;
+        assert_eq!(c.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_mapped_guard() {
+        let c: Mutex<RawMutex, _> = Mutex::new((0, vec![1, 2]));
+
+        let co = MutexGuard::map(block_on(c.lock()), |data| &mut data.1);
+        let mut co = MappedMutexGuard::map(co, |data| &mut data[1]);
+        *co += 1;
+        assert!(c.try_lock().is_none());
+        drop(co);
+
+        let co = MutexGuard::try_map(block_on(c.lock()), |data| data.1.get_mut(2));
+        let co = co.expect_err("the part doesn't exist");
+        assert_eq!(*co, (0, vec![1, 3]));
+    }
+}
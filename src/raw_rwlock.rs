@@ -0,0 +1,770 @@
+use crate::raw::RawAsyncRwLock;
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The RW Lock over the `RawAsyncRwLock` algorithm, like the `lock_api::RwLock` for the synchronous locks.
+/// The algorithm provides only the locking, and the lock provides the data, the guards, the owned and the mapped guards.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::raw::RawRwLock;
+/// use fast_async_mutex::raw_rwlock::{RwLock, RwLockReadGuard};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let lock: RwLock<RawRwLock, _> = RwLock::new((1, 2));
+///     *lock.write().await = (1, 3);
+///     let guard = RwLockReadGuard::map(lock.read().await, |data| &data.1);
+///     assert_eq!(*guard, 3);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RwLock<R, T: ?Sized> {
+    raw: R,
+    data: UnsafeCell<T>,
+}
+
+impl<R: RawAsyncRwLock, T> RwLock<R, T> {
+    /// Create a new `RwLock`
+    #[inline]
+    pub const fn new(data: T) -> RwLock<R, T> {
+        RwLock::from_raw(R::INIT, data)
+    }
+}
+
+impl<R, T> RwLock<R, T> {
+    /// Create a new `RwLock` over the given algorithm
+    #[inline]
+    pub const fn from_raw(raw: R, data: T) -> RwLock<R, T> {
+        RwLock {
+            raw,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Returns the data of the lock.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> RwLock<R, T> {
+    /// Acquires the lock for are write.
+    ///
+    /// Returns a guard that releases the lock and wake the next locker when dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::raw::RawRwLock;
+    /// use fast_async_mutex::raw_rwlock::RwLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let lock: RwLock<RawRwLock, _> = RwLock::new(5);
+    ///     let mut guard = lock.write().await;
+    ///     *guard += 1;
+    ///     assert_eq!(*guard, 6);
+    /// }
+    /// ```
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuardFuture<'_, R, T> {
+        RwLockWriteGuardFuture {
+            lock: self,
+            waiter: R::Waiter::default(),
+            is_acquired: false,
+        }
+    }
+
+    /// Acquires the lock for are write.
+    ///
+    /// Returns a guard that releases the lock and wake the next locker when dropped.
+    /// `RwLockWriteOwnedGuard` have a `'static` lifetime, but requires the `Arc<RwLock<R, T>>` type
+    #[inline]
+    pub fn write_owned(self: &Arc<Self>) -> RwLockWriteOwnedGuardFuture<R, T> {
+        RwLockWriteOwnedGuardFuture {
+            lock: self.clone(),
+            waiter: R::Waiter::default(),
+            is_acquired: false,
+        }
+    }
+
+    /// Acquires the lock for are read.
+    ///
+    /// Returns a guard that releases the lock and wake the next locker when dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::raw::RawRwLock;
+    /// use fast_async_mutex::raw_rwlock::RwLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let lock: RwLock<RawRwLock, _> = RwLock::new(5);
+    ///     let (guard1, guard2) = tokio::join!(lock.read(), lock.read());
+    ///     assert_eq!(*guard1 + *guard2, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuardFuture<'_, R, T> {
+        RwLockReadGuardFuture {
+            lock: self,
+            waiter: R::Waiter::default(),
+            is_acquired: false,
+        }
+    }
+
+    /// Acquires the lock for are read.
+    ///
+    /// Returns a guard that releases the lock and wake the next locker when dropped.
+    /// `RwLockReadOwnedGuard` have a `'static` lifetime, but requires the `Arc<RwLock<R, T>>` type
+    #[inline]
+    pub fn read_owned(self: &Arc<Self>) -> RwLockReadOwnedGuardFuture<R, T> {
+        RwLockReadOwnedGuardFuture {
+            lock: self.clone(),
+            waiter: R::Waiter::default(),
+            is_acquired: false,
+        }
+    }
+
+    /// Acquires the lock for are write, if it's released, without waiting.
+    #[inline]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, R, T>> {
+        if self.raw.try_lock_exclusive() {
+            Some(RwLockWriteGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Acquires the lock for are read, if it isn't held by the writer, without waiting.
+    #[inline]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, R, T>> {
+        if self.raw.try_lock_shared() {
+            Some(RwLockReadGuard { lock: self })
+        } else {
+            None
+        }
+    }
+}
+
+impl<R, T: ?Sized> RwLock<R, T> {
+    /// Returns the mutable reference to the data, the lock isn't acquired, because it's borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Returns the algorithm of the lock, so the same lock may be acquired by other means, like the blocking `lock_api` traits.
+    ///
+    /// # Safety
+    ///
+    /// The lock must not be released, while it's held by a guard.
+    #[inline]
+    pub unsafe fn raw(&self) -> &R {
+        &self.raw
+    }
+
+    /// Returns the raw pointer to the data, which may be accessed only while the lock is held.
+    #[inline]
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+}
+
+/// The Write Guard of the `RwLock<R, T>`, which gives the exclusive access to the data.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped.
+#[derive(Debug)]
+pub struct RwLockWriteGuard<'a, R: RawAsyncRwLock, T: ?Sized> {
+    lock: &'a RwLock<R, T>,
+}
+
+/// The Write Guard of the `Arc<RwLock<R, T>>` with the `'static` lifetime.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped.
+#[derive(Debug)]
+pub struct RwLockWriteOwnedGuard<R: RawAsyncRwLock, T: ?Sized> {
+    lock: Arc<RwLock<R, T>>,
+}
+
+/// The Read Guard of the `RwLock<R, T>`, which gives the shared access to the data.
+/// The lock is automatically released by the last reader, whose guard is dropped.
+#[derive(Debug)]
+pub struct RwLockReadGuard<'a, R: RawAsyncRwLock, T: ?Sized> {
+    lock: &'a RwLock<R, T>,
+}
+
+/// The Read Guard of the `Arc<RwLock<R, T>>` with the `'static` lifetime.
+/// The lock is automatically released by the last reader, whose guard is dropped.
+#[derive(Debug)]
+pub struct RwLockReadOwnedGuard<R: RawAsyncRwLock, T: ?Sized> {
+    lock: Arc<RwLock<R, T>>,
+}
+
+/// The guard of the part of the data, which is returned by `RwLockWriteGuard::map`.
+/// The lock is automatically released whenever the guard is dropped.
+#[derive(Debug)]
+pub struct MappedRwLockWriteGuard<'a, R: RawAsyncRwLock, T: ?Sized> {
+    raw: &'a R,
+    data: *mut T,
+    marker: PhantomData<&'a mut T>,
+}
+
+/// The guard of the part of the data, which is returned by `RwLockReadGuard::map`.
+/// The lock is automatically released by the last reader, whose guard is dropped.
+#[derive(Debug)]
+pub struct MappedRwLockReadGuard<'a, R: RawAsyncRwLock, T: ?Sized> {
+    raw: &'a R,
+    data: *const T,
+    marker: PhantomData<&'a T>,
+}
+
+#[derive(Debug)]
+pub struct RwLockWriteGuardFuture<'a, R: RawAsyncRwLock, T: ?Sized> {
+    lock: &'a RwLock<R, T>,
+    waiter: R::Waiter,
+    is_acquired: bool,
+}
+
+#[derive(Debug)]
+pub struct RwLockWriteOwnedGuardFuture<R: RawAsyncRwLock, T: ?Sized> {
+    lock: Arc<RwLock<R, T>>,
+    waiter: R::Waiter,
+    is_acquired: bool,
+}
+
+#[derive(Debug)]
+pub struct RwLockReadGuardFuture<'a, R: RawAsyncRwLock, T: ?Sized> {
+    lock: &'a RwLock<R, T>,
+    waiter: R::Waiter,
+    is_acquired: bool,
+}
+
+#[derive(Debug)]
+pub struct RwLockReadOwnedGuardFuture<R: RawAsyncRwLock, T: ?Sized> {
+    lock: Arc<RwLock<R, T>>,
+    waiter: R::Waiter,
+    is_acquired: bool,
+}
+
+impl<'a, R: RawAsyncRwLock, T: ?Sized> RwLockWriteGuard<'a, R, T> {
+    /// Makes the guard of the part of the data, like the field of the struct.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        guard: RwLockWriteGuard<'a, R, T>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRwLockWriteGuard<'a, R, U> {
+        let lock = guard.lock;
+        // The mapped guard releases the lock instead of the guard.
+        std::mem::forget(guard);
+        MappedRwLockWriteGuard {
+            raw: &lock.raw,
+            data: f(unsafe { &mut *lock.data.get() }),
+            marker: PhantomData,
+        }
+    }
+
+    /// Makes the guard of the part of the data, or returns the guard back, if the part doesn't exist.
+    #[inline]
+    pub fn try_map<U: ?Sized>(
+        guard: RwLockWriteGuard<'a, R, T>,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<MappedRwLockWriteGuard<'a, R, U>, RwLockWriteGuard<'a, R, T>> {
+        let lock = guard.lock;
+        match f(unsafe { &mut *lock.data.get() }) {
+            Some(data) => {
+                std::mem::forget(guard);
+                Ok(MappedRwLockWriteGuard {
+                    raw: &lock.raw,
+                    data,
+                    marker: PhantomData,
+                })
+            }
+            None => Err(guard),
+        }
+    }
+}
+
+impl<'a, R: RawAsyncRwLock, T: ?Sized> RwLockReadGuard<'a, R, T> {
+    /// Makes the guard of the part of the data, like the field of the struct.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        guard: RwLockReadGuard<'a, R, T>,
+        f: impl FnOnce(&T) -> &U,
+    ) -> MappedRwLockReadGuard<'a, R, U> {
+        let lock = guard.lock;
+        // The mapped guard releases the lock instead of the guard.
+        std::mem::forget(guard);
+        MappedRwLockReadGuard {
+            raw: &lock.raw,
+            data: f(unsafe { &*lock.data.get() }),
+            marker: PhantomData,
+        }
+    }
+
+    /// Makes the guard of the part of the data, or returns the guard back, if the part doesn't exist.
+    #[inline]
+    pub fn try_map<U: ?Sized>(
+        guard: RwLockReadGuard<'a, R, T>,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Result<MappedRwLockReadGuard<'a, R, U>, RwLockReadGuard<'a, R, T>> {
+        let lock = guard.lock;
+        match f(unsafe { &*lock.data.get() }) {
+            Some(data) => {
+                std::mem::forget(guard);
+                Ok(MappedRwLockReadGuard {
+                    raw: &lock.raw,
+                    data,
+                    marker: PhantomData,
+                })
+            }
+            None => Err(guard),
+        }
+    }
+}
+
+impl<'a, R: RawAsyncRwLock, T: ?Sized> MappedRwLockWriteGuard<'a, R, T> {
+    /// Makes the guard of the part of the mapped data.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        guard: MappedRwLockWriteGuard<'a, R, T>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRwLockWriteGuard<'a, R, U> {
+        let raw = guard.raw;
+        let data = f(unsafe { &mut *guard.data });
+        std::mem::forget(guard);
+        MappedRwLockWriteGuard {
+            raw,
+            data,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, R: RawAsyncRwLock, T: ?Sized> MappedRwLockReadGuard<'a, R, T> {
+    /// Makes the guard of the part of the mapped data.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        guard: MappedRwLockReadGuard<'a, R, T>,
+        f: impl FnOnce(&T) -> &U,
+    ) -> MappedRwLockReadGuard<'a, R, U> {
+        let raw = guard.raw;
+        let data = f(unsafe { &*guard.data });
+        std::mem::forget(guard);
+        MappedRwLockReadGuard {
+            raw,
+            data,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, R: RawAsyncRwLock, T: ?Sized> Future for RwLockWriteGuardFuture<'a, R, T> {
+    type Output = RwLockWriteGuard<'a, R, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The waiter is pinned with the future, and other fields aren't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let waiter = unsafe { Pin::new_unchecked(&mut this.waiter) };
+        match this.lock.raw.poll_lock_exclusive(waiter, cx) {
+            Poll::Ready(()) => {
+                this.is_acquired = true;
+                Poll::Ready(RwLockWriteGuard { lock: this.lock })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Future for RwLockWriteOwnedGuardFuture<R, T> {
+    type Output = RwLockWriteOwnedGuard<R, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The waiter is pinned with the future, and other fields aren't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let waiter = unsafe { Pin::new_unchecked(&mut this.waiter) };
+        match this.lock.raw.poll_lock_exclusive(waiter, cx) {
+            Poll::Ready(()) => {
+                this.is_acquired = true;
+                Poll::Ready(RwLockWriteOwnedGuard {
+                    lock: this.lock.clone(),
+                })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, R: RawAsyncRwLock, T: ?Sized> Future for RwLockReadGuardFuture<'a, R, T> {
+    type Output = RwLockReadGuard<'a, R, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The waiter is pinned with the future, and other fields aren't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let waiter = unsafe { Pin::new_unchecked(&mut this.waiter) };
+        match this.lock.raw.poll_lock_shared(waiter, cx) {
+            Poll::Ready(()) => {
+                this.is_acquired = true;
+                Poll::Ready(RwLockReadGuard { lock: this.lock })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Future for RwLockReadOwnedGuardFuture<R, T> {
+    type Output = RwLockReadOwnedGuard<R, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The waiter is pinned with the future, and other fields aren't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let waiter = unsafe { Pin::new_unchecked(&mut this.waiter) };
+        match this.lock.raw.poll_lock_shared(waiter, cx) {
+            Poll::Ready(()) => {
+                this.is_acquired = true;
+                Poll::Ready(RwLockReadOwnedGuard {
+                    lock: this.lock.clone(),
+                })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+unsafe impl<R: Send, T: ?Sized + Send> Send for RwLock<R, T> {}
+unsafe impl<R: Sync, T: ?Sized + Send + Sync> Sync for RwLock<R, T> {}
+
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Send + Sync> Send for RwLockWriteGuard<'_, R, T> {}
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Send + Sync> Sync for RwLockWriteGuard<'_, R, T> {}
+
+unsafe impl<R: RawAsyncRwLock + Send + Sync, T: ?Sized + Send + Sync> Send
+    for RwLockWriteOwnedGuard<R, T>
+{
+}
+unsafe impl<R: RawAsyncRwLock + Send + Sync, T: ?Sized + Send + Sync> Sync
+    for RwLockWriteOwnedGuard<R, T>
+{
+}
+
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Send + Sync> Send for RwLockReadGuard<'_, R, T> {}
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Send + Sync> Sync for RwLockReadGuard<'_, R, T> {}
+
+unsafe impl<R: RawAsyncRwLock + Send + Sync, T: ?Sized + Send + Sync> Send
+    for RwLockReadOwnedGuard<R, T>
+{
+}
+unsafe impl<R: RawAsyncRwLock + Send + Sync, T: ?Sized + Send + Sync> Sync
+    for RwLockReadOwnedGuard<R, T>
+{
+}
+
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Send + Sync> Send
+    for MappedRwLockWriteGuard<'_, R, T>
+{
+}
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Send + Sync> Sync
+    for MappedRwLockWriteGuard<'_, R, T>
+{
+}
+
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Sync> Send for MappedRwLockReadGuard<'_, R, T> {}
+unsafe impl<R: RawAsyncRwLock + Sync, T: ?Sized + Sync> Sync for MappedRwLockReadGuard<'_, R, T> {}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Deref for RwLockWriteGuard<'_, R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> DerefMut for RwLockWriteGuard<'_, R, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Deref for RwLockWriteOwnedGuard<R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> DerefMut for RwLockWriteOwnedGuard<R, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Deref for RwLockReadGuard<'_, R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Deref for RwLockReadOwnedGuard<R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Deref for MappedRwLockWriteGuard<'_, R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> DerefMut for MappedRwLockWriteGuard<'_, R, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Deref for MappedRwLockReadGuard<'_, R, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockWriteGuard<'_, R, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock_exclusive() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockWriteOwnedGuard<R, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock_exclusive() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockReadGuard<'_, R, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock_shared() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockReadOwnedGuard<R, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock_shared() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for MappedRwLockWriteGuard<'_, R, T> {
+    fn drop(&mut self) {
+        unsafe { self.raw.unlock_exclusive() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for MappedRwLockReadGuard<'_, R, T> {
+    fn drop(&mut self) {
+        unsafe { self.raw.unlock_shared() }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockWriteGuardFuture<'_, R, T> {
+    fn drop(&mut self) {
+        if !self.is_acquired {
+            // The future is pinned, so it's dropped in place.
+            let waiter = unsafe { Pin::new_unchecked(&mut self.waiter) };
+            self.lock.raw.cancel(waiter)
+        }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockWriteOwnedGuardFuture<R, T> {
+    fn drop(&mut self) {
+        if !self.is_acquired {
+            // The future is pinned, so it's dropped in place.
+            let waiter = unsafe { Pin::new_unchecked(&mut self.waiter) };
+            self.lock.raw.cancel(waiter)
+        }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockReadGuardFuture<'_, R, T> {
+    fn drop(&mut self) {
+        if !self.is_acquired {
+            // The future is pinned, so it's dropped in place.
+            let waiter = unsafe { Pin::new_unchecked(&mut self.waiter) };
+            self.lock.raw.cancel(waiter)
+        }
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> Drop for RwLockReadOwnedGuardFuture<R, T> {
+    fn drop(&mut self) {
+        if !self.is_acquired {
+            // The future is pinned, so it's dropped in place.
+            let waiter = unsafe { Pin::new_unchecked(&mut self.waiter) };
+            self.lock.raw.cancel(waiter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{RawAsyncRwLock, RawRwLock};
+    use crate::raw_rwlock::{
+        MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, RwLockWriteOwnedGuard,
+    };
+    use crate::traits::suite::check_rwlock;
+    use crate::wait_strategy::ImmediatePark;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    /// The simplest algorithm, which is plugged by the user: the counter of the readers,
+    /// where the writer is `usize::MAX`, and the lockers wake themselves.
+    #[derive(Debug)]
+    struct SpinRwLock(AtomicUsize);
+
+    unsafe impl RawAsyncRwLock for SpinRwLock {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = SpinRwLock(AtomicUsize::new(0));
+
+        type Waiter = ();
+
+        fn try_lock_shared(&self) -> bool {
+            self.0
+                .fetch_update(Ordering::Acquire, Ordering::Relaxed, |readers| {
+                    if readers == usize::MAX {
+                        None
+                    } else {
+                        Some(readers + 1)
+                    }
+                })
+                .is_ok()
+        }
+
+        fn poll_lock_shared(&self, _waiter: Pin<&mut ()>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.try_lock_shared() {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        unsafe fn unlock_shared(&self) {
+            self.0.fetch_sub(1, Ordering::Release);
+        }
+
+        fn try_lock_exclusive(&self) -> bool {
+            self.0
+                .compare_exchange(0, usize::MAX, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        }
+
+        fn poll_lock_exclusive(&self, _waiter: Pin<&mut ()>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.try_lock_exclusive() {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        unsafe fn unlock_exclusive(&self) {
+            self.0.store(0, Ordering::Release)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_rwlock() {
+        let c: RwLock<RawRwLock, _> = RwLock::new(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: RwLockWriteGuard<_, i32> = c.write().await;
+                *co += 1;
+                drop(co);
+                let co: RwLockReadGuard<_, i32> = c.read().await;
+                assert!(*co > 0);
+            })
+            .await;
+
+        let co = c.read().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_owned_rwlock() {
+        let c: Arc<RwLock<RawRwLock<ImmediatePark>, _>> = Arc::new(RwLock::new(0));
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: RwLockWriteOwnedGuard<_, i32> = c.write_owned().await;
+                *co += 1;
+                drop(co);
+                let co = c.read_owned().await;
+                assert!(*co > 0);
+            })
+            .await;
+
+        let co = c.read_owned().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[test]
+    fn test_suite() {
+        check_rwlock::<RwLock<RawRwLock, usize>>();
+        check_rwlock::<RwLock<RawRwLock<ImmediatePark>, usize>>();
+        check_rwlock::<RwLock<SpinRwLock, usize>>();
+    }
+
+    #[test]
+    fn test_try_lock() {
+        let c: RwLock<SpinRwLock, _> = RwLock::new(0);
+
+        let r1 = c.try_read().unwrap();
+        let r2 = c.try_read().unwrap();
+        assert!(c.try_write().is_none());
+        drop((r1, r2));
+
+        let mut w = c.try_write().unwrap();
+        assert!(c.try_read().is_none());
+        *w += 1;
+        drop(w);
+        assert_eq!(c.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_mapped_guards() {
+        let c: RwLock<RawRwLock, _> = RwLock::new((0, vec![1, 2]));
+
+        let co = RwLockWriteGuard::map(block_on(c.write()), |data| &mut data.1);
+        let mut co = MappedRwLockWriteGuard::map(co, |data| &mut data[1]);
+        *co += 1;
+        assert!(c.try_read().is_none());
+        drop(co);
+
+        let r1 = RwLockReadGuard::map(block_on(c.read()), |data| &data.1[1]);
+        let r2 = RwLockReadGuard::try_map(block_on(c.read()), |data| data.1.get(2));
+        let r2 = r2.expect_err("the part doesn't exist");
+        assert_eq!(*r1, 3);
+        assert!(c.try_write().is_none());
+        drop((r1, r2));
+        assert!(c.try_write().is_some());
+    }
+}
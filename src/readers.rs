@@ -0,0 +1,108 @@
+use crate::cache_padded::{hot, Hot};
+use crate::inner::{Acquisition, HotInner, WaitQueue, Waiting};
+use crate::wait_strategy::WaitStrategy;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Waker;
+
+/// The admission of the readers of the RW locks, which is shared by the `RwLock` and the `RawRwLock`.
+///
+/// The first reader acquires the lock from the `WaitQueue` and starts the read phase, the next readers join it without acquiring.
+/// The readers, which arrived before the read phase, join it freely, but every reader, which arrived in the read phase,
+/// is counted as a bypass, while an exclusive locker waits. So they stop joining, when the oldest waiter was bypassed too many times.
+#[derive(Debug)]
+pub(crate) struct Readers {
+    /// The number of the readers, which hold the lock, with the flag bits of the lock.
+    pub(crate) count: Hot<AtomicUsize>,
+    /// The read phase, which is started by every acquisition of the lock by a reader.
+    pub(crate) phase: AtomicUsize,
+}
+
+impl Readers {
+    pub(crate) const fn new() -> Readers {
+        Readers {
+            count: hot(AtomicUsize::new(0)),
+            phase: AtomicUsize::new(1),
+        }
+    }
+
+    /// Joins the held read lock, or acquires the lock by the first reader.
+    /// The reader joins only while other readers hold the lock and none of the `blocked` bits is set.
+    /// The recursive reader joins even if the oldest waiter was bypassed too many times, and it isn't counted.
+    #[inline]
+    pub(crate) fn try_acquire<W: WaitStrategy, T: ?Sized>(
+        &self,
+        queue: &WaitQueue,
+        inner: &HotInner<T>,
+        arrival: usize,
+        is_recursive: bool,
+        blocked: usize,
+    ) -> bool {
+        let is_late = !is_recursive && arrival == self.phase.load(Ordering::SeqCst);
+        let is_joined = self
+            .count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |readers| {
+                let is_exhausted = is_late && queue.is_join_exhausted();
+                if readers > 0 && readers & blocked == 0 && !is_exhausted {
+                    Some(readers + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok();
+        if is_joined {
+            if is_late {
+                queue.count_join();
+            }
+            return true;
+        }
+
+        if queue.try_acquire::<W, _>(inner) {
+            self.start_phase();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Polls the waiting reader, which arrived in the phase remembered by its waiting.
+    /// The parked readers after the admitted one are woken one by one past the waiting writers, so they join it.
+    #[inline]
+    pub(crate) fn poll_acquire<W: WaitStrategy, T: ?Sized>(
+        &self,
+        queue: &WaitQueue,
+        inner: &HotInner<T>,
+        waiting: &Waiting,
+        waker: &Waker,
+        is_recursive: bool,
+        blocked: usize,
+    ) -> bool {
+        let arrival = waiting.arrival(&self.phase);
+        match queue.poll_acquire::<W>(waiting, waker, || {
+            self.try_acquire::<W, T>(queue, inner, arrival, is_recursive, blocked)
+        }) {
+            Acquisition::Pending => return false,
+            Acquisition::HandedOff => self.start_phase(),
+            Acquisition::Acquired => {}
+        }
+
+        if W::IS_PARKING {
+            queue.wake_oldest_shared();
+        }
+        true
+    }
+
+    /// Counts the first reader of the acquired lock, which starts the read phase.
+    #[inline]
+    pub(crate) fn start_phase(&self) {
+        self.count.fetch_add(1, Ordering::AcqRel);
+        self.phase.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Releases the reader, and the lock by the last of them.
+    #[inline]
+    pub(crate) fn release<T: ?Sized>(&self, queue: &WaitQueue, inner: &HotInner<T>) {
+        if self.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            queue.unlock(inner)
+        }
+    }
+}
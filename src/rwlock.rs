@@ -1,31 +1,74 @@
-use crate::inner::Inner;
+use crate::delegation::{Delegation, Delegations, Progress};
+use crate::inner::{Acquisition, HotInner, Inner, WaitQueue, Waiting};
+use crate::readers::Readers;
+use crate::wait_strategy::{WaitStrategy, WakeImmediately};
 use std::fmt::Debug;
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::task::{Context, Poll, Waker};
 
 /// The RW Lock mechanism accepts you get concurrent shared access to your data without waiting.
 /// And get unique access with locks like a Mutex.
+///
+/// The waiting writer is handed the lock, when it was bypassed too many times or waits too long.
+/// Readers join the held read lock without acquiring it again. The readers, which arrived before the read phase, join it freely,
+/// but every reader, which arrived in the read phase, is counted as a bypass, while a writer waits.
+/// They don't join, when the oldest waiter was bypassed too many times, so the task must not read again, while it holds the read guard.
+/// Such read waits for the writer, which waits for the held guard. `read_recursive` joins the held read lock anyway.
+///
+/// The contended lockers wait for the lock by the `WaitStrategy`, they wake themselves immediately by default.
+/// The waiting lockers are queued by the nodes, which are owned by the queue and reused by the next lockers,
+/// so the lock futures aren't pinned, and neither leaving the queue nor dropping the future waits for other threads.
+///
+/// The small writes may be queued by `update`, so the writer, which holds the lock, applies them in one write section.
 #[derive(Debug)]
-pub struct RwLock<T: ?Sized> {
-    readers: AtomicUsize,
-    inner: Inner<T>,
+pub struct RwLock<T: ?Sized, W = WakeImmediately> {
+    readers: Readers,
+    queue: WaitQueue,
+    strategy: PhantomData<fn() -> W>,
+    updates: Delegations<T>,
+    inner: HotInner<T>,
 }
 
 impl<T> RwLock<T> {
     /// Create a new `RWLock`
     #[inline]
     pub const fn new(data: T) -> RwLock<T> {
+        RwLock::with_strategy(data)
+    }
+}
+
+impl<T, W> RwLock<T, W> {
+    /// Create a new `RWLock`, whose contended lockers wait by the given `WaitStrategy`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock::RwLock;
+    /// use fast_async_mutex::wait_strategy::ImmediatePark;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex: RwLock<_, ImmediatePark> = RwLock::with_strategy(10);
+    ///     let guard = mutex.read().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub const fn with_strategy(data: T) -> RwLock<T, W> {
         RwLock {
-            readers: AtomicUsize::new(0),
+            readers: Readers::new(),
+            queue: WaitQueue::new(),
+            strategy: PhantomData,
+            updates: Delegations::new(),
             inner: Inner::new(data),
         }
     }
 }
 
-impl<T: ?Sized> RwLock<T> {
+impl<T: ?Sized, W: WaitStrategy> RwLock<T, W> {
     /// Acquires the mutex for are write.
     ///
     /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
@@ -44,10 +87,10 @@ impl<T: ?Sized> RwLock<T> {
     /// }
     /// ```
     #[inline]
-    pub fn write(&self) -> RwLockWriteGuardFuture<T> {
+    pub fn write(&self) -> RwLockWriteGuardFuture<'_, T, W> {
         RwLockWriteGuardFuture {
-            mutex: &self,
-            is_realized: false,
+            mutex: self,
+            waiting: Waiting::new(),
         }
     }
 
@@ -70,10 +113,10 @@ impl<T: ?Sized> RwLock<T> {
     /// }
     /// ```
     #[inline]
-    pub fn write_owned(self: &Arc<Self>) -> RwLockWriteOwnedGuardFuture<T> {
+    pub fn write_owned(self: &Arc<Self>) -> RwLockWriteOwnedGuardFuture<T, W> {
         RwLockWriteOwnedGuardFuture {
             mutex: self.clone(),
-            is_realized: false,
+            waiting: Waiting::new(),
         }
     }
 
@@ -89,16 +132,43 @@ impl<T: ?Sized> RwLock<T> {
     /// #[tokio::main]
     /// async fn main() {
     ///     let mutex = RwLock::new(10);
+    ///     let (guard, guard2) = tokio::join!(mutex.read(), mutex.read());
+    ///     assert_eq!(*guard, *guard2);
+    /// }
+    /// ```
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuardFuture<'_, T, W> {
+        RwLockReadGuardFuture {
+            mutex: self,
+            waiting: Waiting::new(),
+            is_recursive: false,
+        }
+    }
+
+    /// Acquires the mutex for are read, and joins the held read lock even if a writer waits for it.
+    ///
+    /// It's used to read again by the task, which holds the read guard, because the plain `read` may wait for the writer,
+    /// which waits for the held guard. The recursive readers aren't counted as bypasses, so they may delay the waiting writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock::RwLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = RwLock::new(10);
     ///     let guard = mutex.read().await;
-    ///     let guard2 = mutex.read().await;
+    ///     let guard2 = mutex.read_recursive().await;
     ///     assert_eq!(*guard, *guard2);
     /// }
     /// ```
     #[inline]
-    pub fn read(&self) -> RwLockReadGuardFuture<T> {
+    pub fn read_recursive(&self) -> RwLockReadGuardFuture<'_, T, W> {
         RwLockReadGuardFuture {
-            mutex: &self,
-            is_realized: false,
+            mutex: self,
+            waiting: Waiting::new(),
+            is_recursive: true,
         }
     }
 
@@ -115,34 +185,121 @@ impl<T: ?Sized> RwLock<T> {
     /// #[tokio::main]
     /// async fn main() {
     ///     let mutex = Arc::new(RwLock::new(10));
-    ///     let guard = mutex.read().await;
-    ///     let guard2 = mutex.read().await;
+    ///     let guard = mutex.read_owned().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn read_owned(self: &Arc<Self>) -> RwLockReadOwnedGuardFuture<T, W> {
+        RwLockReadOwnedGuardFuture {
+            mutex: self.clone(),
+            waiting: Waiting::new(),
+            is_recursive: false,
+        }
+    }
+
+    /// Acquires the mutex for are read like `read_owned`, and joins the held read lock even if a writer waits for it like `read_recursive`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock::RwLock;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(RwLock::new(10));
+    ///     let guard = mutex.read_owned().await;
+    ///     let guard2 = mutex.read_recursive_owned().await;
     ///     assert_eq!(*guard, *guard2);
     /// }
     /// ```
     #[inline]
-    pub fn read_owned(self: &Arc<Self>) -> RwLockReadOwnedGuardFuture<T> {
+    pub fn read_recursive_owned(self: &Arc<Self>) -> RwLockReadOwnedGuardFuture<T, W> {
         RwLockReadOwnedGuardFuture {
             mutex: self.clone(),
-            is_realized: false,
+            waiting: Waiting::new(),
+            is_recursive: true,
         }
     }
 
+    /// Queues the update of the data, and returns the future of its completion, which resolves into the result of the closure.
+    ///
+    /// The writer, which holds the lock, applies all queued updates in the order of their submission,
+    /// before the lock is released, so many small writes are coalesced into one write section and the readers wait for them once.
+    /// If no writer holds the lock, the updater acquires it and applies the queued updates itself.
+    ///
+    /// The panic of the update, which was applied by another writer, is caught and resumed by its updater, so it doesn't unwind the writer.
+    /// The write guard, which is dropped while panicking, leaves the queued updates to their updaters.
+    ///
+    /// Dropping the future, while the writer applies the update, doesn't wait for it.
+    /// The writer finishes the abandoned update and drops its result, so the update and the result must be `'static`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock::RwLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = RwLock::new(10);
+    ///     mutex.update(|data| *data += 1).await;
+    ///     assert_eq!(*mutex.read().await, 11);
+    /// }
+    /// ```
     #[inline]
-    fn unlock_reader(&self) {
-        if self.readers.fetch_sub(1, Ordering::Release) == 1 {
-            self.inner.unlock()
+    pub fn update<F, R>(&self, f: F) -> RwLockUpdateFuture<'_, T, F, R, W>
+    where
+        F: FnOnce(&mut T) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        RwLockUpdateFuture {
+            mutex: self,
+            waiting: Waiting::new(),
+            update: Delegation::new(f),
         }
     }
 
     #[inline]
-    fn add_reader(&self) {
-        self.readers.fetch_add(1, Ordering::Release);
+    fn poll_write(&self, waiting: &Waiting, waker: &Waker) -> bool {
+        waiting.mark_exclusive();
+        self.queue.poll_acquire::<W>(waiting, waker, || {
+            self.queue.try_acquire::<W, _>(&self.inner)
+        }) != Acquisition::Pending
     }
 
     #[inline]
-    fn try_acquire_reader(&self) -> bool {
-        self.readers.load(Ordering::Acquire) > 0 || self.inner.try_acquire()
+    fn poll_read(&self, waiting: &Waiting, waker: &Waker, is_recursive: bool) -> bool {
+        self.readers
+            .poll_acquire::<W, T>(&self.queue, &self.inner, waiting, waker, is_recursive, 0)
+    }
+}
+
+impl<T: ?Sized, W> RwLock<T, W> {
+    #[inline]
+    fn unlock_reader(&self) {
+        self.readers.release(&self.queue, &self.inner)
+    }
+
+    /// Applies the queued updates before the lock is released, so the readers don't see the data between them.
+    /// The lock is released on every path, even if applying the updates unwinds.
+    #[inline]
+    fn unlock_writer(&self) {
+        let _unlock = WriterUnlock { mutex: self };
+        if self.updates.is_queued() && !std::thread::panicking() {
+            self.updates
+                .run_pending(unsafe { &mut *self.inner.data.get() });
+        }
+    }
+}
+
+/// Releases the write lock, when it's dropped.
+struct WriterUnlock<'a, T: ?Sized, W> {
+    mutex: &'a RwLock<T, W>,
+}
+
+impl<T: ?Sized, W> Drop for WriterUnlock<'_, T, W> {
+    fn drop(&mut self) {
+        self.mutex.queue.unlock(&self.mutex.inner)
     }
 }
 
@@ -150,14 +307,14 @@ impl<T: ?Sized> RwLock<T> {
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the RWLock, so the mutex will not be dropped while a guard exists.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct RwLockWriteGuard<'a, T: ?Sized> {
-    mutex: &'a RwLock<T>,
+pub struct RwLockWriteGuard<'a, T: ?Sized, W = WakeImmediately> {
+    mutex: &'a RwLock<T, W>,
 }
 
 #[derive(Debug)]
-pub struct RwLockWriteGuardFuture<'a, T: ?Sized> {
-    mutex: &'a RwLock<T>,
-    is_realized: bool,
+pub struct RwLockWriteGuardFuture<'a, T: ?Sized, W = WakeImmediately> {
+    mutex: &'a RwLock<T, W>,
+    waiting: Waiting,
 }
 
 /// An owned handle to a held RWLock.
@@ -165,28 +322,39 @@ pub struct RwLockWriteGuardFuture<'a, T: ?Sized> {
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `RWLock`, so even if the lock goes away, the guard remains valid.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct RwLockWriteOwnedGuard<T: ?Sized> {
-    mutex: Arc<RwLock<T>>,
+pub struct RwLockWriteOwnedGuard<T: ?Sized, W = WakeImmediately> {
+    mutex: Arc<RwLock<T, W>>,
+}
+
+#[derive(Debug)]
+pub struct RwLockWriteOwnedGuardFuture<T: ?Sized, W = WakeImmediately> {
+    mutex: Arc<RwLock<T, W>>,
+    waiting: Waiting,
 }
 
+/// The future of the queued update, which resolves into the result of the update, when it's applied.
+/// The update is queued in the pinned future, so the queued update doesn't allocate.
+/// The drop of the future abandons the running update to the writer, which drops its result.
 #[derive(Debug)]
-pub struct RwLockWriteOwnedGuardFuture<T: ?Sized> {
-    mutex: Arc<RwLock<T>>,
-    is_realized: bool,
+pub struct RwLockUpdateFuture<'a, T: ?Sized, F, R, W = WakeImmediately> {
+    mutex: &'a RwLock<T, W>,
+    waiting: Waiting,
+    update: Delegation<T, F, R>,
 }
 
 /// The Simple Write Lock Guard
 /// As long as you have this guard, you have shared access to the underlying `T`. The guard internally borrows the `RWLock`, so the mutex will not be dropped while a guard exists.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct RwLockReadGuard<'a, T: ?Sized> {
-    mutex: &'a RwLock<T>,
+pub struct RwLockReadGuard<'a, T: ?Sized, W = WakeImmediately> {
+    mutex: &'a RwLock<T, W>,
 }
 
 #[derive(Debug)]
-pub struct RwLockReadGuardFuture<'a, T: ?Sized> {
-    mutex: &'a RwLock<T>,
-    is_realized: bool,
+pub struct RwLockReadGuardFuture<'a, T: ?Sized, W = WakeImmediately> {
+    mutex: &'a RwLock<T, W>,
+    waiting: Waiting,
+    is_recursive: bool,
 }
 
 /// An owned handle to a held RWLock.
@@ -194,108 +362,186 @@ pub struct RwLockReadGuardFuture<'a, T: ?Sized> {
 /// As long as you have this guard, you have shared access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `RWLock`, so even if the lock goes away, the guard remains valid.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct RwLockReadOwnedGuard<T: ?Sized> {
-    mutex: Arc<RwLock<T>>,
+pub struct RwLockReadOwnedGuard<T: ?Sized, W = WakeImmediately> {
+    mutex: Arc<RwLock<T, W>>,
 }
 
 #[derive(Debug)]
-pub struct RwLockReadOwnedGuardFuture<T: ?Sized> {
-    mutex: Arc<RwLock<T>>,
-    is_realized: bool,
+pub struct RwLockReadOwnedGuardFuture<T: ?Sized, W = WakeImmediately> {
+    mutex: Arc<RwLock<T, W>>,
+    waiting: Waiting,
+    is_recursive: bool,
 }
 
-impl<'a, T: ?Sized> Future for RwLockWriteGuardFuture<'a, T> {
-    type Output = RwLockWriteGuard<'a, T>;
+impl<'a, T: ?Sized, W: WaitStrategy> Future for RwLockWriteGuardFuture<'a, T, W> {
+    type Output = RwLockWriteGuard<'a, T, W>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire() {
-            self.is_realized = true;
-            Poll::Ready(RwLockWriteGuard { mutex: self.mutex })
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &*self;
+        if this.mutex.poll_write(&this.waiting, cx.waker()) {
+            Poll::Ready(RwLockWriteGuard { mutex: this.mutex })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<T: ?Sized> Future for RwLockWriteOwnedGuardFuture<T> {
-    type Output = RwLockWriteOwnedGuard<T>;
+impl<T: ?Sized, W: WaitStrategy> Future for RwLockWriteOwnedGuardFuture<T, W> {
+    type Output = RwLockWriteOwnedGuard<T, W>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire() {
-            self.is_realized = true;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &*self;
+        if this.mutex.poll_write(&this.waiting, cx.waker()) {
             Poll::Ready(RwLockWriteOwnedGuard {
-                mutex: self.mutex.clone(),
+                mutex: this.mutex.clone(),
             })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<'a, T: ?Sized> Future for RwLockReadGuardFuture<'a, T> {
-    type Output = RwLockReadGuard<'a, T>;
+impl<'a, T: ?Sized, W: WaitStrategy> Future for RwLockReadGuardFuture<'a, T, W> {
+    type Output = RwLockReadGuard<'a, T, W>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.try_acquire_reader() {
-            self.is_realized = true;
-            self.mutex.add_reader();
-            Poll::Ready(RwLockReadGuard { mutex: self.mutex })
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &*self;
+        if this
+            .mutex
+            .poll_read(&this.waiting, cx.waker(), this.is_recursive)
+        {
+            Poll::Ready(RwLockReadGuard { mutex: this.mutex })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<T: ?Sized> Future for RwLockReadOwnedGuardFuture<T> {
-    type Output = RwLockReadOwnedGuard<T>;
+impl<T: ?Sized, W: WaitStrategy> Future for RwLockReadOwnedGuardFuture<T, W> {
+    type Output = RwLockReadOwnedGuard<T, W>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.try_acquire_reader() {
-            self.is_realized = true;
-            self.mutex.add_reader();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &*self;
+        if this
+            .mutex
+            .poll_read(&this.waiting, cx.waker(), this.is_recursive)
+        {
             Poll::Ready(RwLockReadOwnedGuard {
-                mutex: self.mutex.clone(),
+                mutex: this.mutex.clone(),
             })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
+impl<T: ?Sized, F, R, W> Future for RwLockUpdateFuture<'_, T, F, R, W>
+where
+    F: FnOnce(&mut T) -> R + 'static,
+    R: 'static,
+    W: WaitStrategy,
+{
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The update is linked into the queue of the lock, so the pinned future is never moved or borrowed mutably.
+        let this = self.into_ref().get_ref();
+        let mutex = this.mutex;
+        if this.update.is_submitted() {
+            match this.update.poll(&mutex.updates, cx.waker()) {
+                Progress::Done(result) => {
+                    mutex.queue.cancel(&mutex.inner, &this.waiting);
+                    return Poll::Ready(result);
+                }
+                Progress::Running => return Poll::Pending,
+                Progress::Queued => {}
+            }
+        }
+
+        if !mutex.poll_write(&this.waiting, cx.waker()) {
+            if !this.update.is_submitted() {
+                this.update.submit(&mutex.updates, cx.waker());
+            }
+            return Poll::Pending;
+        }
+
+        // The updates, which were queued before, are applied first, so the updates are applied in the order of their submission.
+        let mut guard = RwLockWriteGuard { mutex };
+        mutex.updates.run_pending(&mut guard);
+        let result = if this.update.is_submitted() {
+            match this.update.poll(&mutex.updates, cx.waker()) {
+                Progress::Done(result) => result,
+                _ => unreachable!("the queued update isn't applied by the writer"),
+            }
+        } else {
+            this.update.run_by_holder(&mut guard)
+        };
+        Poll::Ready(result)
+    }
+}
+
 crate::impl_send_sync_rwlock!(
-    RwLock,
+    RwLock<W>,
     RwLockReadGuard,
     RwLockReadOwnedGuard,
     RwLockWriteGuard,
     RwLockWriteOwnedGuard
 );
 
-crate::impl_deref_mut!(RwLockWriteGuard, 'a);
-crate::impl_deref_mut!(RwLockWriteOwnedGuard);
-crate::impl_deref!(RwLockReadGuard, 'a);
-crate::impl_deref!(RwLockReadOwnedGuard);
+crate::impl_deref_mut!(RwLockWriteGuard<W>, 'a);
+crate::impl_deref_mut!(RwLockWriteOwnedGuard<W>);
+crate::impl_deref!(RwLockReadGuard<W>, 'a);
+crate::impl_deref!(RwLockReadOwnedGuard<W>);
 
-crate::impl_drop_guard!(RwLockWriteGuard, 'a, unlock);
-crate::impl_drop_guard!(RwLockWriteOwnedGuard, unlock);
-crate::impl_drop_guard_self!(RwLockReadGuard, 'a, unlock_reader);
-crate::impl_drop_guard_self!(RwLockReadOwnedGuard, unlock_reader);
+crate::impl_drop_guard_self!(RwLockWriteGuard<W>, 'a, unlock_writer);
+crate::impl_drop_guard_self!(RwLockWriteOwnedGuard<W>, unlock_writer);
+crate::impl_drop_guard_self!(RwLockReadGuard<W>, 'a, unlock_reader);
+crate::impl_drop_guard_self!(RwLockReadOwnedGuard<W>, unlock_reader);
 
-crate::impl_drop_guard_future!(RwLockWriteGuardFuture, 'a, unlock);
-crate::impl_drop_guard_future!(RwLockWriteOwnedGuardFuture, unlock);
-crate::impl_drop_guard_future!(RwLockReadGuardFuture, 'a, unlock);
-crate::impl_drop_guard_future!(RwLockReadOwnedGuardFuture, unlock);
+impl<T: ?Sized, W> Drop for RwLockWriteGuardFuture<'_, T, W> {
+    fn drop(&mut self) {
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
+
+impl<T: ?Sized, W> Drop for RwLockWriteOwnedGuardFuture<T, W> {
+    fn drop(&mut self) {
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
+
+impl<T: ?Sized, F, R, W> Drop for RwLockUpdateFuture<'_, T, F, R, W> {
+    fn drop(&mut self) {
+        self.update.cancel(&self.mutex.updates);
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
+
+impl<T: ?Sized, W> Drop for RwLockReadGuardFuture<'_, T, W> {
+    fn drop(&mut self) {
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
+
+impl<T: ?Sized, W> Drop for RwLockReadOwnedGuardFuture<T, W> {
+    fn drop(&mut self) {
+        self.mutex.queue.cancel(&self.mutex.inner, &self.waiting)
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use crate::inner::MAX_BYPASS;
     use crate::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard, RwLockWriteOwnedGuard};
+    use crate::wait_strategy::{Adaptive, ImmediatePark, SpinThenPark, WaitStrategy, YieldBackoff};
     use futures::executor::block_on;
     use futures::{FutureExt, StreamExt, TryStreamExt};
+    use std::future::Future;
     use std::ops::AddAssign;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
+    use std::task::{Context, Poll};
     use tokio::time::{sleep, Duration};
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
@@ -313,6 +559,55 @@ mod tests {
         assert_eq!(*co, 10000)
     }
 
+    async fn check_strategy<W: WaitStrategy>() {
+        let c: RwLock<_, W> = RwLock::with_strategy(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |i| {
+                let c = &c;
+                async move {
+                    if i % 4 == 0 {
+                        *c.write().await += 1;
+                    } else {
+                        let co = c.read().await;
+                        sleep(Duration::from_micros(10)).await;
+                        assert!(*co <= 2500);
+                    }
+                }
+            })
+            .await;
+
+        let co = c.read().await;
+        assert_eq!(*co, 2500)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_wait_strategies() {
+        check_strategy::<ImmediatePark>().await;
+        check_strategy::<SpinThenPark>().await;
+        check_strategy::<YieldBackoff>().await;
+        check_strategy::<Adaptive>().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_parked_readers() {
+        let c: Arc<RwLock<_, ImmediatePark>> = Arc::new(RwLock::with_strategy(0));
+        let co = c.write().await;
+
+        let readers: Vec<_> = (0..10)
+            .map(|_| {
+                let c = c.clone();
+                tokio::spawn(async move { *c.read_owned().await })
+            })
+            .collect();
+        sleep(Duration::from_millis(10)).await;
+        drop(co);
+
+        for reader in readers {
+            assert_eq!(reader.await.unwrap(), 0);
+        }
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
     async fn test_mutex_delay() {
         let expected_result = 100;
@@ -387,10 +682,9 @@ mod tests {
             .for_each_concurrent(None, |_c| futures::future::ready(()))
             .await;
 
-        assert!(matches!(
-            tokio::time::timeout(Duration::from_millis(1), c.write()).await,
-            Err(_)
-        ));
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.write())
+            .await
+            .is_err());
 
         let co2: RwLockReadGuard<String> = c.read().await;
         assert_eq!(*co, *co2);
@@ -409,10 +703,9 @@ mod tests {
 
         let mut co: RwLockWriteGuard<String> = c.write().await;
 
-        assert!(matches!(
-            tokio::time::timeout(Duration::from_millis(1), c.read()).await,
-            Err(_)
-        ));
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.read())
+            .await
+            .is_err());
 
         *co += "lol";
 
@@ -424,6 +717,239 @@ mod tests {
         assert_eq!(*co, *co2);
     }
 
+    #[tokio::test]
+    async fn test_cancelled_reading() {
+        let c = RwLock::new(0);
+
+        let co: RwLockWriteGuard<i32> = c.write().await;
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.read())
+            .await
+            .is_err());
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.write())
+            .await
+            .is_err());
+
+        drop(co);
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.read())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bounded_bypass() {
+        let tasks = 4;
+        let c = Arc::new(RwLock::new(()));
+        let acquisitions = Arc::new(AtomicUsize::new(0));
+        let max_bypass = Arc::new(AtomicUsize::new(0));
+
+        // Every task locks again right after the release, so it would bypass waiting writers without the handoff.
+        // Readers don't hold the lock across polls, so every read acquires the lock again and is counted as a bypass.
+        let handles: Vec<_> = (0..tasks * 2)
+            .map(|i| {
+                let c = c.clone();
+                let acquisitions = acquisitions.clone();
+                let max_bypass = max_bypass.clone();
+                tokio::spawn(async move {
+                    for _ in 0..200 {
+                        if i % 2 == 0 {
+                            let before = acquisitions.load(Ordering::SeqCst);
+                            let co = c.write().await;
+                            let bypass = acquisitions.fetch_add(1, Ordering::SeqCst) - before;
+                            max_bypass.fetch_max(bypass, Ordering::SeqCst);
+                            let _ = tokio::task::yield_now().await;
+                            drop(co);
+                        } else {
+                            drop(c.read().await);
+                            acquisitions.fetch_add(1, Ordering::SeqCst);
+                            let _ = tokio::task::yield_now().await;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // The waiting writer has at most `tasks - 1` writers before it.
+        let max_bypass = max_bypass.load(Ordering::SeqCst);
+        assert!(max_bypass < tasks * (MAX_BYPASS + 1), "{}", max_bypass);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_writer_not_starved_by_overlapping_readers() {
+        let c = Arc::new(RwLock::new(0));
+        let is_written = Arc::new(AtomicUsize::new(0));
+
+        // The readers hold the lock in turns, so there is always a reader, which holds the lock.
+        let readers: Vec<_> = (0..2)
+            .map(|i| {
+                let c = c.clone();
+                let is_written = is_written.clone();
+                tokio::spawn(async move {
+                    sleep(Duration::from_millis(i)).await;
+                    while is_written.load(Ordering::SeqCst) == 0 {
+                        let co = c.read().await;
+                        sleep(Duration::from_millis(2)).await;
+                        drop(co);
+                    }
+                })
+            })
+            .collect();
+
+        sleep(Duration::from_millis(10)).await;
+        let co = tokio::time::timeout(Duration::from_millis(500), c.write())
+            .await
+            .expect("the writer is starved by the readers");
+        is_written.store(1, Ordering::SeqCst);
+        drop(co);
+
+        for reader in readers {
+            reader.await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_read_recursive() {
+        let c = Arc::new(RwLock::new(0));
+
+        let co = c.read().await;
+        let waiting = c.clone();
+        let write = tokio::spawn(async move {
+            *waiting.write().await += 1;
+        });
+
+        // The writer was bypassed by too many readers, so only the recursive reader joins.
+        sleep(Duration::from_millis(10)).await;
+        for _ in 0..MAX_BYPASS {
+            drop(c.read().await);
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(10), c.read())
+            .await
+            .is_err());
+        let co2 = tokio::time::timeout(Duration::from_millis(100), c.read_recursive())
+            .await
+            .expect("the recursive reader must join the held lock");
+        assert_eq!(*co, *co2);
+        let co3 = tokio::time::timeout(Duration::from_millis(100), c.read_recursive_owned())
+            .await
+            .expect("the recursive reader must join the held lock");
+        assert_eq!(*co, *co3);
+        drop(co);
+        drop(co2);
+        drop(co3);
+
+        write.await.unwrap();
+        assert_eq!(*c.read().await, 1);
+    }
+
+    #[test]
+    fn test_update_panic() {
+        let c = RwLock::new(0);
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // The update, which is applied by its own updater.
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            block_on(c.update(|_| panic!("the update panics")))
+        }));
+        assert!(panic.is_err());
+        assert_eq!(*block_on(c.read()), 0);
+
+        // The update, which is applied by the writer on the release.
+        let co = block_on(c.write());
+        let mut panicked = Box::pin(c.update(|_| panic!("the update panics")));
+        let mut other = Box::pin(c.update(|co| *co += 1));
+        assert!(panicked.as_mut().poll(&mut cx).is_pending());
+        assert!(other.as_mut().poll(&mut cx).is_pending());
+        drop(co);
+
+        assert_eq!(other.as_mut().poll(&mut cx), Poll::Ready(()));
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| panicked.as_mut().poll(&mut cx)));
+        assert!(panic.is_err());
+        drop(panicked);
+        *block_on(c.write()) += 1;
+        assert_eq!(*block_on(c.read()), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_update() {
+        let c = RwLock::new(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |i| {
+                let c = &c;
+                async move {
+                    if i % 4 == 0 {
+                        let co = c.read().await;
+                        assert!(*co <= 10000);
+                    } else {
+                        c.update(|co| *co += 1).await;
+                    }
+                }
+            })
+            .await;
+
+        let co = c.read().await;
+        assert_eq!(*co, 7500)
+    }
+
+    #[test]
+    fn test_coalesced_updates() {
+        let c = RwLock::new(0);
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let update = |i| Box::pin(c.update(move |co| std::mem::replace(co, i)));
+
+        // The writer applies the updates, which were queued while it held the lock, before the release.
+        let co = block_on(c.write());
+        let mut updates: Vec<_> = (1..=5).map(update).collect();
+        for update in updates.iter_mut() {
+            assert!(update.as_mut().poll(&mut cx).is_pending());
+        }
+        drop(co);
+        assert_eq!(*block_on(c.read()), 5);
+
+        // The first updater, which acquires the lock, applies the updates of others in the order of submission.
+        let co = block_on(c.read());
+        updates.extend((6..=10).map(update));
+        for update in updates.iter_mut().skip(5) {
+            assert!(update.as_mut().poll(&mut cx).is_pending());
+        }
+        drop(co);
+
+        let results = updates
+            .iter_mut()
+            .rev()
+            .map(|update| match update.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => panic!("the queued update isn't applied"),
+            });
+        assert!(results.eq([9, 8, 7, 6, 5, 4, 3, 2, 1, 0]));
+        assert_eq!(*block_on(c.read()), 10);
+    }
+
+    #[test]
+    fn test_dropped_pending_lock() {
+        let c = RwLock::new(0);
+
+        // The dropped futures, which never acquired the lock, leave it held.
+        let co = block_on(c.write());
+        assert!(c.read().now_or_never().is_none());
+        assert!(c.write().now_or_never().is_none());
+        assert!(c.read().now_or_never().is_none());
+        drop(co);
+
+        let co = block_on(c.read());
+        assert!(c.write().now_or_never().is_none());
+        assert!(c.write().now_or_never().is_none());
+        drop(co);
+        assert!(c.write().now_or_never().is_some());
+    }
+
     #[test]
     fn multithreading_test() {
         let num = 100;
@@ -458,4 +984,17 @@ mod tests {
             assert_eq!(num / 2, *lock)
         })
     }
+
+    #[test]
+    fn test_unpin_futures() {
+        fn assert_unpin<T: Unpin>(_: &T) {}
+
+        // The waiting node is owned by the queue, so the lock futures can be polled without pinning.
+        let lock = Arc::new(RwLock::<_, ImmediatePark>::with_strategy(0));
+        assert_unpin(&lock.write());
+        assert_unpin(&lock.write_owned());
+        assert_unpin(&lock.read());
+        assert_unpin(&lock.read_owned());
+        assert_unpin(&lock.read_recursive());
+    }
 }
@@ -1,34 +1,212 @@
-use crate::inner::OrderedInner;
+use crate::inner::{cmp_ids, grow_field, is_ahead, Inner, WakerRing};
+use crate::wait_strategy::{ImmediatePark, WaitStrategy};
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::Range;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::task::{Context, Poll, Waker};
 
 /// The Ordered RW Lock will be locking all reads, which starting after write and unlocking them only when write will realize.
 /// It may be slow down the reads speed, but decrease time to write on systems, where it is critical.
 ///
-/// **BUT RW Lock has some limitations. You should avoid acquiring the second reading before realizing first inside the one future.
-/// Because it can happen that between your readings a write from another thread will acquire the mutex, and you will get a deadlock.**
+/// Every lock takes a ticket, which remembers how many writers and readers took tickets before it.
+/// The state of the lock is the number of released writers and readers, so it doesn't depend on the order, in which readers are released:
+///
+/// * A reader is admitted when all writers before it are released. So consecutive readers are admitted together as a batch.
+/// * A writer is admitted when all writers and readers before it are released.
+///   Readers after the writer can't be admitted before it, so the writer waits only for the preceding batch.
+/// * A dropped writer ticket is released as soon as the writers before it are released, so it doesn't delay the next batch.
+///   A dropped reader ticket is released when its batch is admitted.
+///
+/// You should avoid acquiring the second reading before realizing first inside the one future,
+/// because a write from another thread can take a ticket between your readings, and you will get a deadlock.
+/// Clone the read guard instead, the clone joins the batch of the original guard.
+/// In debug builds, the lock panics, when a task waits for a ticket, which is queued after a guard held by the same task.
+/// Such wait isn't always a deadlock, so the report may be replaced by the hook of `set_self_deadlock_hook`.
+/// The held guard is owned by the task, which acquired it, and the task is recognized by its waker,
+/// so the futures with their own wakers, like the futures of `FuturesUnordered`, are owned by themselves.
+///
+/// The waiting lockers are parked until their turn by default, the other `WaitStrategy` may be chosen by `with_strategy`.
+#[derive(Debug)]
+pub struct OrderedRwLock<T: ?Sized, W = ImmediatePark> {
+    tickets: Inner<Tickets>,
+    writers_done: AtomicUsize,
+    readers_done: AtomicUsize,
+    /// The ticket after the last released writer, so all tickets before it are released.
+    first_waiting: AtomicUsize,
+    cancelled: Inner<Cancelled>,
+    cancelled_len: AtomicUsize,
+    wakers: WakerRing,
+    /// The held guards with the wakers of their owners, which acquired them, to detect the self-deadlock.
+    #[cfg(debug_assertions)]
+    held: Inner<[Option<(Ticket, Waker)>; TRACKED_GUARDS]>,
+    /// The id of the last reported ticket plus one, so every waiting locker is reported once.
+    #[cfg(debug_assertions)]
+    reported: AtomicUsize,
+    strategy: PhantomData<fn() -> W>,
+    inner: Inner<T>,
+}
+
+/// The place in the queue of the lock with the numbers of writers and readers before it.
+/// The `id` is the number of all tickets before it, so consecutive tickets have consecutive ids.
+#[derive(Debug, Clone, Copy)]
+struct Ticket {
+    id: usize,
+    writers: usize,
+    readers: usize,
+    /// It's used only to detect the self-deadlock in debug builds.
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    is_writer: bool,
+}
+
+/// The address of the hook of the self-deadlock, or `0` if the self-deadlock panics.
+static SELF_DEADLOCK_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the hook, which is called in debug builds instead of the panic, when a task waits for the `OrderedRwLock` ticket,
+/// which is queued after a guard held by the same task. The hook is called once per waiting locker, and it isn't called in release builds.
+///
+/// Such wait isn't always a deadlock: the guard may be released by another future of the task, or the wait may be bounded by a timeout.
+/// So the tasks, which wait so on purpose, may replace the panic by the hook, which, for example, writes it into the log.
+/// Returns the previous hook, which may be restored by `set_self_deadlock_hook`, or `None` if the self-deadlock panicked.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::rwlock_ordered::{reset_self_deadlock_hook, set_self_deadlock_hook};
+///
+/// let previous = set_self_deadlock_hook(|| eprintln!("the task waits for the OrderedRwLock, which it holds"));
+/// assert!(previous.is_none());
+/// reset_self_deadlock_hook();
+/// ```
+pub fn set_self_deadlock_hook(hook: fn()) -> Option<fn()> {
+    as_hook(SELF_DEADLOCK_HOOK.swap(hook as usize, Ordering::SeqCst))
+}
+
+/// Removes the hook of `set_self_deadlock_hook`, so the self-deadlock panics again.
+/// Returns the removed hook.
+pub fn reset_self_deadlock_hook() -> Option<fn()> {
+    as_hook(SELF_DEADLOCK_HOOK.swap(0, Ordering::SeqCst))
+}
+
+/// The hook is stored only by `set_self_deadlock_hook`, so the non-zero address is always the address of the `fn()`.
+fn as_hook(address: usize) -> Option<fn()> {
+    match address {
+        0 => None,
+        address => Some(unsafe { std::mem::transmute::<usize, fn()>(address) }),
+    }
+}
+
+/// Calls the hook of `set_self_deadlock_hook`, or panics if it isn't set.
+#[cfg(debug_assertions)]
+fn report_self_deadlock() {
+    match as_hook(SELF_DEADLOCK_HOOK.load(Ordering::SeqCst)) {
+        Some(hook) => hook(),
+        None => report_default(),
+    }
+}
+
+#[cfg(debug_assertions)]
+fn report_default() {
+    panic!("the task waits for the OrderedRwLock ticket, which is queued after a guard held by the same task");
+}
+
+/// The number of held guards, which are checked for the self-deadlock in debug builds.
+/// The guards above it aren't tracked, so the bookkeeping doesn't grow with the number of readers.
+#[cfg(debug_assertions)]
+const TRACKED_GUARDS: usize = 8;
+
+#[cfg(debug_assertions)]
+#[allow(clippy::declare_interior_mutable_const)]
+const UNTRACKED_GUARD: Option<(Ticket, Waker)> = None;
+
+/// The numbers of issued writer and reader tickets.
+#[derive(Debug)]
+struct Tickets {
+    writers: usize,
+    readers: usize,
+}
+
+/// The dropped tickets, which wait for their turn to be released.
+/// The queues are grown outside of the spin lock, so the cancel doesn't allocate under it.
 #[derive(Debug)]
-pub struct OrderedRwLock<T: ?Sized> {
-    readers: AtomicUsize,
-    inner: OrderedInner<T>,
+struct Cancelled {
+    /// The number of writers before the reader and its id, sorted by the number of writers.
+    readers: VecDeque<(usize, usize)>,
+    /// The number of writers before the writer and its id, sorted by the number of writers.
+    writers: VecDeque<(usize, usize)>,
+}
+
+/// The capacity of the queue of the dropped tickets, which is allocated by the first cancel.
+const MIN_CANCELLED: usize = 4;
+
+impl Cancelled {
+    /// The positions of the dropped readers of the batch.
+    fn batch(&self, batch: usize) -> Range<usize> {
+        let start = self
+            .readers
+            .partition_point(|&(writers, _)| cmp_ids(writers, batch).is_lt());
+        let end = self
+            .readers
+            .partition_point(|&(writers, _)| cmp_ids(writers, batch).is_le());
+        start..end
+    }
 }
 
 impl<T> OrderedRwLock<T> {
     /// Create a new `OrderedRWLock`
     #[inline]
     pub const fn new(data: T) -> OrderedRwLock<T> {
+        OrderedRwLock::with_strategy(data)
+    }
+}
+
+impl<T, W> OrderedRwLock<T, W> {
+    /// Create a new `OrderedRWLock`, whose lockers wait for their turn by the given `WaitStrategy`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock_ordered::OrderedRwLock;
+    /// use fast_async_mutex::wait_strategy::YieldBackoff;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex: OrderedRwLock<_, YieldBackoff> = OrderedRwLock::with_strategy(10);
+    ///     let guard = mutex.read().await;
+    ///     assert_eq!(*guard, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub const fn with_strategy(data: T) -> OrderedRwLock<T, W> {
         OrderedRwLock {
-            readers: AtomicUsize::new(0),
-            inner: OrderedInner::new(data),
+            tickets: Inner::new(Tickets {
+                writers: 0,
+                readers: 0,
+            }),
+            writers_done: AtomicUsize::new(0),
+            readers_done: AtomicUsize::new(0),
+            first_waiting: AtomicUsize::new(0),
+            cancelled: Inner::new(Cancelled {
+                readers: VecDeque::new(),
+                writers: VecDeque::new(),
+            }),
+            cancelled_len: AtomicUsize::new(0),
+            wakers: WakerRing::new(),
+            #[cfg(debug_assertions)]
+            held: Inner::new([UNTRACKED_GUARD; TRACKED_GUARDS]),
+            #[cfg(debug_assertions)]
+            reported: AtomicUsize::new(0),
+            strategy: PhantomData,
+            inner: Inner::new(data),
         }
     }
 }
 
-impl<T: ?Sized> OrderedRwLock<T> {
+impl<T: ?Sized, W> OrderedRwLock<T, W> {
     /// Acquires the mutex for are write.
     ///
     /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
@@ -47,10 +225,11 @@ impl<T: ?Sized> OrderedRwLock<T> {
     /// }
     /// ```
     #[inline]
-    pub fn write(&self) -> OrderedRwLockWriteGuardFuture<T> {
+    pub fn write(&self) -> OrderedRwLockWriteGuardFuture<'_, T, W> {
         OrderedRwLockWriteGuardFuture {
-            mutex: &self,
-            id: self.inner.generate_id(),
+            mutex: self,
+            ticket: self.write_ticket(),
+            attempt: 0,
             is_realized: false,
         }
     }
@@ -74,10 +253,11 @@ impl<T: ?Sized> OrderedRwLock<T> {
     /// }
     /// ```
     #[inline]
-    pub fn write_owned(self: &Arc<Self>) -> OrderedRwLockWriteOwnedGuardFuture<T> {
+    pub fn write_owned(self: &Arc<Self>) -> OrderedRwLockWriteOwnedGuardFuture<T, W> {
         OrderedRwLockWriteOwnedGuardFuture {
             mutex: self.clone(),
-            id: self.inner.generate_id(),
+            ticket: self.write_ticket(),
+            attempt: 0,
             is_realized: false,
         }
     }
@@ -100,10 +280,11 @@ impl<T: ?Sized> OrderedRwLock<T> {
     /// }
     /// ```
     #[inline]
-    pub fn read(&self) -> OrderedRwLockReadGuardFuture<T> {
+    pub fn read(&self) -> OrderedRwLockReadGuardFuture<'_, T, W> {
         OrderedRwLockReadGuardFuture {
-            mutex: &self,
-            id: self.inner.generate_id(),
+            mutex: self,
+            ticket: self.read_ticket(),
+            attempt: 0,
             is_realized: false,
         }
     }
@@ -127,28 +308,393 @@ impl<T: ?Sized> OrderedRwLock<T> {
     /// }
     /// ```
     #[inline]
-    pub fn read_owned(self: &Arc<Self>) -> OrderedRwLockReadOwnedGuardFuture<T> {
+    pub fn read_owned(self: &Arc<Self>) -> OrderedRwLockReadOwnedGuardFuture<T, W> {
         OrderedRwLockReadOwnedGuardFuture {
             mutex: self.clone(),
-            id: self.inner.generate_id(),
+            ticket: self.read_ticket(),
+            attempt: 0,
             is_realized: false,
         }
     }
 
+    /// Acquires the mutex for are upgradable read.
+    ///
+    /// The upgradable read takes a writer ticket, but it's admitted together with the readers of the batch before it.
+    /// So the upgrade waits only for these readers and becomes the writer before any writer ticketed after it.
+    /// Readers ticketed after the upgradable read wait for its release, like after a writer.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock_ordered::OrderedRwLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = OrderedRwLock::new(10);
+    ///     let reader = mutex.read().await;
+    ///     let guard = mutex.upgradable_read().await;
+    ///     assert_eq!(*guard, *reader);
+    ///
+    ///     drop(reader);
+    ///     let mut guard = guard.upgrade().await;
+    ///     *guard += 1;
+    ///     assert_eq!(*guard, 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn upgradable_read(&self) -> OrderedRwLockUpgradableReadGuardFuture<'_, T, W> {
+        OrderedRwLockUpgradableReadGuardFuture {
+            mutex: self,
+            ticket: self.write_ticket(),
+            attempt: 0,
+            is_realized: false,
+        }
+    }
+
+    /// Acquires the mutex for are upgradable read.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    /// `OrderedRwLockUpgradableReadOwnedGuard` have a `'static` lifetime, but requires the `Arc<OrderedRwLock<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock_ordered::OrderedRwLock;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(OrderedRwLock::new(10));
+    ///     let guard = mutex.upgradable_read_owned().await;
+    ///     let mut guard = guard.upgrade().await;
+    ///     *guard += 1;
+    ///     assert_eq!(*guard, 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn upgradable_read_owned(
+        self: &Arc<Self>,
+    ) -> OrderedRwLockUpgradableReadOwnedGuardFuture<T, W> {
+        OrderedRwLockUpgradableReadOwnedGuardFuture {
+            mutex: self.clone(),
+            ticket: self.write_ticket(),
+            attempt: 0,
+            is_realized: false,
+        }
+    }
+
+    #[inline]
+    fn write_ticket(&self) -> Ticket {
+        self.tickets.spin_lock(|tickets| {
+            let ticket = Ticket {
+                id: tickets.writers.wrapping_add(tickets.readers),
+                writers: tickets.writers,
+                readers: tickets.readers,
+                is_writer: true,
+            };
+            tickets.writers = tickets.writers.wrapping_add(1);
+            ticket
+        })
+    }
+
+    #[inline]
+    fn read_ticket(&self) -> Ticket {
+        self.tickets.spin_lock(|tickets| {
+            let ticket = Ticket {
+                id: tickets.writers.wrapping_add(tickets.readers),
+                writers: tickets.writers,
+                readers: tickets.readers,
+                is_writer: false,
+            };
+            tickets.readers = tickets.readers.wrapping_add(1);
+            ticket
+        })
+    }
+
+    #[inline]
+    fn try_acquire_writer(&self, ticket: Ticket) -> bool {
+        self.writers_done.load(Ordering::SeqCst) == ticket.writers
+            && self.readers_done.load(Ordering::SeqCst) == ticket.readers
+    }
+
+    /// The writers after the batch could be dropped and released already.
+    #[inline]
+    fn try_acquire_reader(&self, ticket: Ticket) -> bool {
+        !is_ahead(ticket.writers, self.writers_done.load(Ordering::SeqCst))
+    }
+
+    /// The readers of the batch before the upgradable read may be not released yet.
+    #[inline]
+    fn try_acquire_upgradable(&self, ticket: Ticket) -> bool {
+        self.writers_done.load(Ordering::SeqCst) == ticket.writers
+    }
+
+    /// Tries to acquire the ticket, and stores the waker of the parked ticket if it's not its turn yet.
+    /// The ticket is checked again after the waker is stored, so the wake between them isn't lost.
+    #[inline]
+    fn poll_ticket(
+        &self,
+        ticket: Ticket,
+        waker: &Waker,
+        attempt: &mut usize,
+        try_acquire: fn(&Self, Ticket) -> bool,
+    ) -> bool
+    where
+        W: WaitStrategy,
+    {
+        let is_acquired = try_acquire(self, ticket)
+            || W::spin(*attempt, || try_acquire(self, ticket))
+            || W::IS_PARKING && {
+                self.wakers
+                    .store(ticket.id, self.first_waiting.load(Ordering::SeqCst), waker);
+                try_acquire(self, ticket)
+            };
+
+        if !is_acquired {
+            // The task may acquire a guard or be polled by another waker after the first poll, so every poll is checked.
+            #[cfg(debug_assertions)]
+            self.check_self_deadlock(ticket, waker);
+            W::wait(*attempt, waker);
+            *attempt += 1;
+            return false;
+        }
+
+        #[cfg(debug_assertions)]
+        self.track_held(ticket, waker.clone());
+        true
+    }
+
+    /// Reports the self-deadlock, if the owner of the ticket waits for it, while it owns the guard, which is admitted before the ticket.
+    /// The locker is reported once, not on every wakeup.
+    #[cfg(debug_assertions)]
+    fn check_self_deadlock(&self, ticket: Ticket, owner: &Waker) {
+        let is_deadlocked = self.held.spin_lock(|held| {
+            held.iter().flatten().any(|(guard, guard_owner)| {
+                // The reader of the same batch is admitted together with the guard.
+                let is_same_batch =
+                    !guard.is_writer && !ticket.is_writer && guard.writers == ticket.writers;
+                is_ahead(ticket.id, guard.id) && !is_same_batch && guard_owner.will_wake(owner)
+            })
+        });
+
+        let reported = ticket.id.wrapping_add(1);
+        if is_deadlocked && self.reported.swap(reported, Ordering::SeqCst) != reported {
+            report_self_deadlock();
+        }
+    }
+
+    /// Remembers the held guard in the free slot, the guard isn't tracked if all slots are taken.
+    #[cfg(debug_assertions)]
+    fn track_held(&self, ticket: Ticket, waker: Waker) {
+        self.held.spin_lock(|held| {
+            if let Some(slot) = held.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some((ticket, waker));
+            }
+        })
+    }
+
+    #[cfg(debug_assertions)]
+    fn release_held(&self, ticket: Ticket) {
+        self.held.spin_lock(|held| {
+            let slot = held
+                .iter_mut()
+                .find(|slot| matches!(slot, Some((guard, _)) if guard.id == ticket.id));
+            if let Some(slot) = slot {
+                *slot = None;
+            }
+        })
+    }
+
+    /// Adds the reader to the batch of the held reader.
+    /// The reader is accounted as released in advance, so releasing both of them counts as one reader.
     #[inline]
-    fn unlock_reader(&self) {
-        self.readers.fetch_sub(1, Ordering::Release);
-        self.inner.unlock()
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn clone_reader(&self, ticket: Ticket) {
+        self.readers_done.fetch_sub(1, Ordering::SeqCst);
+
+        #[cfg(debug_assertions)]
+        {
+            let waker = self.held.spin_lock(|held| {
+                held.iter()
+                    .flatten()
+                    .find(|(guard, _)| guard.id == ticket.id)
+                    .map(|(_, waker)| waker.clone())
+            });
+            if let Some(waker) = waker {
+                self.track_held(ticket, waker);
+            }
+        }
     }
 
+    /// Wakes the next ticket, which is admitted in the same batch if it's a reader.
     #[inline]
-    fn add_reader(&self) {
-        self.readers.fetch_add(1, Ordering::Release);
+    fn add_reader(&self, ticket: Ticket) {
+        self.wakers.wake(
+            ticket.id.wrapping_add(1),
+            self.first_waiting.load(Ordering::SeqCst),
+        );
+    }
+
+    #[inline]
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn unlock_reader(&self, ticket: Ticket) {
+        #[cfg(debug_assertions)]
+        self.release_held(ticket);
+
+        let readers_done = self
+            .readers_done
+            .fetch_add(1, Ordering::SeqCst)
+            .wrapping_add(1);
+
+        // The writer after the batch has this id, if all readers before it are released.
+        let writer = self
+            .writers_done
+            .load(Ordering::SeqCst)
+            .wrapping_add(readers_done);
+        self.wakers
+            .wake(writer, self.first_waiting.load(Ordering::SeqCst));
+
+        if self.cancelled_len.load(Ordering::SeqCst) != 0 {
+            self.release_cancelled();
+        }
+    }
+
+    #[inline]
+    fn unlock_writer(&self, ticket: Ticket) {
+        #[cfg(debug_assertions)]
+        self.release_held(ticket);
+
+        let next = ticket.id.wrapping_add(1);
+        self.first_waiting.store(next, Ordering::SeqCst);
+        self.writers_done.fetch_add(1, Ordering::SeqCst);
+        self.wakers.wake(next, next);
+
+        if self.cancelled_len.load(Ordering::SeqCst) != 0 {
+            self.release_cancelled();
+        }
     }
 
+    /// Releases the upgradable read without the upgrade.
+    /// The readers of its batch may be still held, so the tickets before it aren't marked as released.
     #[inline]
-    pub fn try_acquire_reader(&self, id: usize) -> bool {
-        id == self.inner.current.load(Ordering::Acquire) + self.readers.load(Ordering::Acquire)
+    fn unlock_upgradable(&self, ticket: Ticket) {
+        #[cfg(debug_assertions)]
+        self.release_held(ticket);
+
+        self.wakers.remove(ticket.id);
+        self.writers_done.fetch_add(1, Ordering::SeqCst);
+        self.wakers.wake(
+            ticket.id.wrapping_add(1),
+            self.first_waiting.load(Ordering::SeqCst),
+        );
+
+        if self.cancelled_len.load(Ordering::SeqCst) != 0 {
+            self.release_cancelled();
+        }
+    }
+
+    fn cancel_reader(&self, ticket: Ticket) {
+        self.wakers.remove(ticket.id);
+        while !self.cancelled.spin_lock(|cancelled| {
+            if cancelled.readers.len() == cancelled.readers.capacity() {
+                return false;
+            }
+            let index = cancelled.batch(ticket.writers).end;
+            cancelled.readers.insert(index, (ticket.writers, ticket.id));
+            self.cancelled_len.fetch_add(1, Ordering::SeqCst);
+
+            // The batch could be admitted and passed by the dropped writers already.
+            let writers_done = self.writers_done.load(Ordering::SeqCst);
+            if is_ahead(writers_done, ticket.writers) {
+                self.release_readers(cancelled, ticket.writers, writers_done);
+            }
+            self.release_cancelled_locked(cancelled);
+            true
+        }) {
+            grow_field(
+                &self.cancelled,
+                |cancelled| &mut cancelled.readers,
+                MIN_CANCELLED,
+            );
+        }
+    }
+
+    fn cancel_writer(&self, ticket: Ticket) {
+        self.wakers.remove(ticket.id);
+        while !self.cancelled.spin_lock(|cancelled| {
+            if cancelled.writers.len() == cancelled.writers.capacity() {
+                return false;
+            }
+            let index = cancelled
+                .writers
+                .partition_point(|&(writers, _)| cmp_ids(writers, ticket.writers).is_lt());
+            cancelled.writers.insert(index, (ticket.writers, ticket.id));
+            self.cancelled_len.fetch_add(1, Ordering::SeqCst);
+            self.release_cancelled_locked(cancelled);
+            true
+        }) {
+            grow_field(
+                &self.cancelled,
+                |cancelled| &mut cancelled.writers,
+                MIN_CANCELLED,
+            );
+        }
+    }
+
+    /// Releases the dropped tickets, whose turn has come.
+    fn release_cancelled(&self) {
+        self.cancelled
+            .spin_lock(|cancelled| self.release_cancelled_locked(cancelled))
+    }
+
+    /// The tickets are removed under the lock, so only one thread releases them.
+    fn release_cancelled_locked(&self, cancelled: &mut Cancelled) {
+        loop {
+            let writers_done = self.writers_done.load(Ordering::SeqCst);
+
+            if !cancelled.batch(writers_done).is_empty() {
+                self.release_readers(cancelled, writers_done, writers_done);
+            } else if let Ok(index) = cancelled
+                .writers
+                .binary_search_by(|&(writers, _)| cmp_ids(writers, writers_done))
+            {
+                let (_, id) = cancelled.writers.remove(index).unwrap();
+                // Readers after the writer may be admitted together with the current batch,
+                // because the next writer waits for both batches anyway.
+                self.cancelled_len.fetch_sub(1, Ordering::SeqCst);
+                self.writers_done.fetch_add(1, Ordering::SeqCst);
+
+                let readers_done = self.readers_done.load(Ordering::SeqCst);
+                let first_waiting = self.first_waiting.load(Ordering::SeqCst);
+                self.wakers.wake(
+                    writers_done.wrapping_add(1).wrapping_add(readers_done),
+                    first_waiting,
+                );
+                self.wakers.wake(id.wrapping_add(1), first_waiting);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Releases the dropped readers of the admitted batch at once.
+    fn release_readers(&self, cancelled: &mut Cancelled, batch: usize, writers_done: usize) {
+        let readers = cancelled.batch(batch);
+        let len = readers.len();
+        self.cancelled_len.fetch_sub(len, Ordering::SeqCst);
+        let readers_done = self
+            .readers_done
+            .fetch_add(len, Ordering::SeqCst)
+            .wrapping_add(len);
+
+        // Continue waking the batch after the dropped readers, and wake the writer after the batch.
+        let first_waiting = self.first_waiting.load(Ordering::SeqCst);
+        for (_, id) in cancelled.readers.drain(readers) {
+            self.wakers.wake(id.wrapping_add(1), first_waiting);
+        }
+        self.wakers
+            .wake(writers_done.wrapping_add(readers_done), first_waiting);
     }
 }
 
@@ -156,14 +702,16 @@ impl<T: ?Sized> OrderedRwLock<T> {
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the RWLock, so the mutex will not be dropped while a guard exists.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct OrderedRwLockWriteGuard<'a, T: ?Sized> {
-    mutex: &'a OrderedRwLock<T>,
+pub struct OrderedRwLockWriteGuard<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedRwLock<T, W>,
+    ticket: Ticket,
 }
 
 #[derive(Debug)]
-pub struct OrderedRwLockWriteGuardFuture<'a, T: ?Sized> {
-    mutex: &'a OrderedRwLock<T>,
-    id: usize,
+pub struct OrderedRwLockWriteGuardFuture<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedRwLock<T, W>,
+    ticket: Ticket,
+    attempt: usize,
     is_realized: bool,
 }
 
@@ -172,14 +720,16 @@ pub struct OrderedRwLockWriteGuardFuture<'a, T: ?Sized> {
 /// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `RWLock`, so even if the lock goes away, the guard remains valid.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct OrderedRwLockWriteOwnedGuard<T: ?Sized> {
-    mutex: Arc<OrderedRwLock<T>>,
+pub struct OrderedRwLockWriteOwnedGuard<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedRwLock<T, W>>,
+    ticket: Ticket,
 }
 
 #[derive(Debug)]
-pub struct OrderedRwLockWriteOwnedGuardFuture<T: ?Sized> {
-    mutex: Arc<OrderedRwLock<T>>,
-    id: usize,
+pub struct OrderedRwLockWriteOwnedGuardFuture<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedRwLock<T, W>>,
+    ticket: Ticket,
+    attempt: usize,
     is_realized: bool,
 }
 
@@ -187,14 +737,16 @@ pub struct OrderedRwLockWriteOwnedGuardFuture<T: ?Sized> {
 /// As long as you have this guard, you have shared access to the underlying `T`. The guard internally borrows the `RWLock`, so the mutex will not be dropped while a guard exists.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct OrderedRwLockReadGuard<'a, T: ?Sized> {
-    mutex: &'a OrderedRwLock<T>,
+pub struct OrderedRwLockReadGuard<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedRwLock<T, W>,
+    ticket: Ticket,
 }
 
 #[derive(Debug)]
-pub struct OrderedRwLockReadGuardFuture<'a, T: ?Sized> {
-    mutex: &'a OrderedRwLock<T>,
-    id: usize,
+pub struct OrderedRwLockReadGuardFuture<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedRwLock<T, W>,
+    ticket: Ticket,
+    attempt: usize,
     is_realized: bool,
 }
 
@@ -203,115 +755,494 @@ pub struct OrderedRwLockReadGuardFuture<'a, T: ?Sized> {
 /// As long as you have this guard, you have shared access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `RWLock`, so even if the lock goes away, the guard remains valid.
 /// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
 #[derive(Debug)]
-pub struct OrderedRwLockReadOwnedGuard<T: ?Sized> {
-    mutex: Arc<OrderedRwLock<T>>,
+pub struct OrderedRwLockReadOwnedGuard<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedRwLock<T, W>>,
+    ticket: Ticket,
 }
 
 #[derive(Debug)]
-pub struct OrderedRwLockReadOwnedGuardFuture<T: ?Sized> {
-    mutex: Arc<OrderedRwLock<T>>,
-    id: usize,
+pub struct OrderedRwLockReadOwnedGuardFuture<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedRwLock<T, W>>,
+    ticket: Ticket,
+    attempt: usize,
     is_realized: bool,
 }
 
-impl<'a, T: ?Sized> Future for OrderedRwLockWriteGuardFuture<'a, T> {
-    type Output = OrderedRwLockWriteGuard<'a, T>;
+/// The Upgradable Read Lock Guard
+/// As long as you have this guard, you have shared access to the underlying `T` and the place of the writer in the queue. The guard internally borrows the `RWLock`, so the mutex will not be dropped while a guard exists.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct OrderedRwLockUpgradableReadGuard<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedRwLock<T, W>,
+    ticket: Ticket,
+}
+
+#[derive(Debug)]
+pub struct OrderedRwLockUpgradableReadGuardFuture<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedRwLock<T, W>,
+    ticket: Ticket,
+    attempt: usize,
+    is_realized: bool,
+}
+
+/// An owned handle to a held RWLock.
+/// This guard is only available from a RWLock that is wrapped in an `Arc`. It is identical to `OrderedRwLockUpgradableReadGuard`, except that rather than borrowing the `RWLock`, it clones the `Arc`, incrementing the reference count. This means that unlike `OrderedRwLockUpgradableReadGuard`, it will have the `'static` lifetime.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct OrderedRwLockUpgradableReadOwnedGuard<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedRwLock<T, W>>,
+    ticket: Ticket,
+}
+
+#[derive(Debug)]
+pub struct OrderedRwLockUpgradableReadOwnedGuardFuture<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedRwLock<T, W>>,
+    ticket: Ticket,
+    attempt: usize,
+    is_realized: bool,
+}
+
+/// The future waits for the release of the readers before the upgradable read, which is released if the future is dropped.
+#[derive(Debug)]
+pub struct OrderedRwLockUpgradeFuture<'a, T: ?Sized, W = ImmediatePark> {
+    mutex: &'a OrderedRwLock<T, W>,
+    ticket: Ticket,
+    attempt: usize,
+    is_realized: bool,
+}
+
+/// The future waits for the release of the readers before the upgradable read, which is released if the future is dropped.
+#[derive(Debug)]
+pub struct OrderedRwLockUpgradeOwnedFuture<T: ?Sized, W = ImmediatePark> {
+    mutex: Arc<OrderedRwLock<T, W>>,
+    ticket: Ticket,
+    attempt: usize,
+    is_realized: bool,
+}
+
+impl<'a, T: ?Sized, W> OrderedRwLockUpgradableReadGuard<'a, T, W> {
+    /// Upgrades the read to the write, when the readers before it are released.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    #[inline]
+    pub fn upgrade(self) -> OrderedRwLockUpgradeFuture<'a, T, W> {
+        let future = OrderedRwLockUpgradeFuture {
+            mutex: self.mutex,
+            ticket: self.ticket,
+            attempt: 0,
+            is_realized: false,
+        };
+        std::mem::forget(self);
+        future
+    }
+}
+
+impl<T: ?Sized, W> OrderedRwLockUpgradableReadOwnedGuard<T, W> {
+    /// Upgrades the read to the write, when the readers before it are released.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    #[inline]
+    pub fn upgrade(self) -> OrderedRwLockUpgradeOwnedFuture<T, W> {
+        let guard = std::mem::ManuallyDrop::new(self);
+        OrderedRwLockUpgradeOwnedFuture {
+            // The guard isn't dropped, so the `Arc` is moved out of it.
+            mutex: unsafe { std::ptr::read(&guard.mutex) },
+            ticket: guard.ticket,
+            attempt: 0,
+            is_realized: false,
+        }
+    }
+}
+
+impl<'a, T: ?Sized, W: WaitStrategy> Future for OrderedRwLockWriteGuardFuture<'a, T, W> {
+    type Output = OrderedRwLockWriteGuard<'a, T, W>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire(self.id) {
-            self.is_realized = true;
-            Poll::Ready(OrderedRwLockWriteGuard { mutex: self.mutex })
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_writer,
+        ) {
+            this.is_realized = true;
+            Poll::Ready(OrderedRwLockWriteGuard {
+                mutex: this.mutex,
+                ticket: this.ticket,
+            })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<T: ?Sized> Future for OrderedRwLockWriteOwnedGuardFuture<T> {
-    type Output = OrderedRwLockWriteOwnedGuard<T>;
+impl<T: ?Sized, W: WaitStrategy> Future for OrderedRwLockWriteOwnedGuardFuture<T, W> {
+    type Output = OrderedRwLockWriteOwnedGuard<T, W>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.inner.try_acquire(self.id) {
-            self.is_realized = true;
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_writer,
+        ) {
+            this.is_realized = true;
             Poll::Ready(OrderedRwLockWriteOwnedGuard {
-                mutex: self.mutex.clone(),
+                mutex: this.mutex.clone(),
+                ticket: this.ticket,
             })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<'a, T: ?Sized> Future for OrderedRwLockReadGuardFuture<'a, T> {
-    type Output = OrderedRwLockReadGuard<'a, T>;
+impl<'a, T: ?Sized, W: WaitStrategy> Future for OrderedRwLockReadGuardFuture<'a, T, W> {
+    type Output = OrderedRwLockReadGuard<'a, T, W>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.try_acquire_reader(self.id) {
-            self.is_realized = true;
-            self.mutex.add_reader();
-            Poll::Ready(OrderedRwLockReadGuard { mutex: &self.mutex })
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_reader,
+        ) {
+            this.is_realized = true;
+            this.mutex.add_reader(this.ticket);
+            Poll::Ready(OrderedRwLockReadGuard {
+                mutex: this.mutex,
+                ticket: this.ticket,
+            })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
-impl<T: ?Sized> Future for OrderedRwLockReadOwnedGuardFuture<T> {
-    type Output = OrderedRwLockReadOwnedGuard<T>;
+impl<T: ?Sized, W: WaitStrategy> Future for OrderedRwLockReadOwnedGuardFuture<T, W> {
+    type Output = OrderedRwLockReadOwnedGuard<T, W>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.mutex.try_acquire_reader(self.id) {
-            self.is_realized = true;
-            self.mutex.add_reader();
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_reader,
+        ) {
+            this.is_realized = true;
+            this.mutex.add_reader(this.ticket);
             Poll::Ready(OrderedRwLockReadOwnedGuard {
-                mutex: self.mutex.clone(),
+                mutex: this.mutex.clone(),
+                ticket: this.ticket,
+            })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, T: ?Sized, W: WaitStrategy> Future for OrderedRwLockUpgradableReadGuardFuture<'a, T, W> {
+    type Output = OrderedRwLockUpgradableReadGuard<'a, T, W>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_upgradable,
+        ) {
+            this.is_realized = true;
+            Poll::Ready(OrderedRwLockUpgradableReadGuard {
+                mutex: this.mutex,
+                ticket: this.ticket,
+            })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized, W: WaitStrategy> Future for OrderedRwLockUpgradableReadOwnedGuardFuture<T, W> {
+    type Output = OrderedRwLockUpgradableReadOwnedGuard<T, W>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_upgradable,
+        ) {
+            this.is_realized = true;
+            Poll::Ready(OrderedRwLockUpgradableReadOwnedGuard {
+                mutex: this.mutex.clone(),
+                ticket: this.ticket,
+            })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, T: ?Sized, W: WaitStrategy> Future for OrderedRwLockUpgradeFuture<'a, T, W> {
+    type Output = OrderedRwLockWriteGuard<'a, T, W>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_writer,
+        ) {
+            this.is_realized = true;
+            // The ticket is held since the upgradable read already.
+            #[cfg(debug_assertions)]
+            this.mutex.release_held(this.ticket);
+            Poll::Ready(OrderedRwLockWriteGuard {
+                mutex: this.mutex,
+                ticket: this.ticket,
+            })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized, W: WaitStrategy> Future for OrderedRwLockUpgradeOwnedFuture<T, W> {
+    type Output = OrderedRwLockWriteOwnedGuard<T, W>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.mutex.poll_ticket(
+            this.ticket,
+            cx.waker(),
+            &mut this.attempt,
+            OrderedRwLock::try_acquire_writer,
+        ) {
+            this.is_realized = true;
+            // The ticket is held since the upgradable read already.
+            #[cfg(debug_assertions)]
+            this.mutex.release_held(this.ticket);
+            Poll::Ready(OrderedRwLockWriteOwnedGuard {
+                mutex: this.mutex.clone(),
+                ticket: this.ticket,
             })
         } else {
-            self.mutex.inner.store_waker(cx.waker());
             Poll::Pending
         }
     }
 }
 
 crate::impl_send_sync_rwlock!(
-    OrderedRwLock,
+    OrderedRwLock<W>,
     OrderedRwLockReadGuard,
     OrderedRwLockReadOwnedGuard,
     OrderedRwLockWriteGuard,
     OrderedRwLockWriteOwnedGuard
 );
 
-crate::impl_deref_mut!(OrderedRwLockWriteGuard, 'a);
-crate::impl_deref_mut!(OrderedRwLockWriteOwnedGuard);
-crate::impl_deref!(OrderedRwLockReadGuard, 'a);
-crate::impl_deref!(OrderedRwLockReadOwnedGuard);
+unsafe impl<T, W> Send for OrderedRwLockUpgradableReadGuard<'_, T, W> where T: ?Sized + Send {}
+unsafe impl<T, W> Sync for OrderedRwLockUpgradableReadGuard<'_, T, W> where T: Send + Sync + ?Sized {}
+
+unsafe impl<T, W> Send for OrderedRwLockUpgradableReadOwnedGuard<T, W> where T: ?Sized + Send {}
+unsafe impl<T, W> Sync for OrderedRwLockUpgradableReadOwnedGuard<T, W> where T: Send + Sync + ?Sized {}
+
+crate::impl_deref_mut!(OrderedRwLockWriteGuard<W>, 'a);
+crate::impl_deref_mut!(OrderedRwLockWriteOwnedGuard<W>);
+crate::impl_deref!(OrderedRwLockReadGuard<W>, 'a);
+crate::impl_deref!(OrderedRwLockReadOwnedGuard<W>);
+crate::impl_deref!(OrderedRwLockUpgradableReadGuard<W>, 'a);
+crate::impl_deref!(OrderedRwLockUpgradableReadOwnedGuard<W>);
+
+/// The clone of the read guard joins the batch of the original guard, so it doesn't wait for writers queued after it.
+/// It's the way to read the data again inside the one future without a deadlock.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::rwlock_ordered::OrderedRwLock;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mutex = OrderedRwLock::new(10);
+///     let guard = mutex.read().await;
+///     let write = mutex.write();
+///
+///     let guard2 = guard.clone();
+///     assert_eq!(*guard, *guard2);
+///
+///     drop(guard);
+///     drop(guard2);
+///     assert_eq!(*write.await, 10);
+/// }
+/// ```
+impl<T: ?Sized, W> Clone for OrderedRwLockReadGuard<'_, T, W> {
+    fn clone(&self) -> Self {
+        self.mutex.clone_reader(self.ticket);
+        OrderedRwLockReadGuard {
+            mutex: self.mutex,
+            ticket: self.ticket,
+        }
+    }
+}
+
+/// The clone of the read guard joins the batch of the original guard, so it doesn't wait for writers queued after it.
+impl<T: ?Sized, W> Clone for OrderedRwLockReadOwnedGuard<T, W> {
+    fn clone(&self) -> Self {
+        self.mutex.clone_reader(self.ticket);
+        OrderedRwLockReadOwnedGuard {
+            mutex: self.mutex.clone(),
+            ticket: self.ticket,
+        }
+    }
+}
 
-crate::impl_drop_guard!(OrderedRwLockWriteGuard, 'a, unlock);
-crate::impl_drop_guard!(OrderedRwLockWriteOwnedGuard, unlock);
-crate::impl_drop_guard_self!(OrderedRwLockReadGuard, 'a, unlock_reader);
-crate::impl_drop_guard_self!(OrderedRwLockReadOwnedGuard, unlock_reader);
+impl<T: ?Sized, W> Drop for OrderedRwLockReadGuard<'_, T, W> {
+    fn drop(&mut self) {
+        self.mutex.unlock_reader(self.ticket)
+    }
+}
 
-crate::impl_drop_guard_future!(OrderedRwLockWriteGuardFuture, 'a, unlock);
-crate::impl_drop_guard_future!(OrderedRwLockWriteOwnedGuardFuture, unlock);
-crate::impl_drop_guard_future!(OrderedRwLockReadGuardFuture, 'a, unlock);
-crate::impl_drop_guard_future!(OrderedRwLockReadOwnedGuardFuture, unlock);
+impl<T: ?Sized, W> Drop for OrderedRwLockReadOwnedGuard<T, W> {
+    fn drop(&mut self) {
+        self.mutex.unlock_reader(self.ticket)
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockWriteGuard<'_, T, W> {
+    fn drop(&mut self) {
+        self.mutex.unlock_writer(self.ticket)
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockWriteOwnedGuard<T, W> {
+    fn drop(&mut self) {
+        self.mutex.unlock_writer(self.ticket)
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockUpgradableReadGuard<'_, T, W> {
+    fn drop(&mut self) {
+        self.mutex.unlock_upgradable(self.ticket)
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockUpgradableReadOwnedGuard<T, W> {
+    fn drop(&mut self) {
+        self.mutex.unlock_upgradable(self.ticket)
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockWriteGuardFuture<'_, T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.cancel_writer(self.ticket)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockWriteOwnedGuardFuture<T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.cancel_writer(self.ticket)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockReadGuardFuture<'_, T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.cancel_reader(self.ticket)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockReadOwnedGuardFuture<T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.cancel_reader(self.ticket)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockUpgradableReadGuardFuture<'_, T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.cancel_writer(self.ticket)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockUpgradableReadOwnedGuardFuture<T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.cancel_writer(self.ticket)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockUpgradeFuture<'_, T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.unlock_upgradable(self.ticket)
+        }
+    }
+}
+
+impl<T: ?Sized, W> Drop for OrderedRwLockUpgradeOwnedFuture<T, W> {
+    fn drop(&mut self) {
+        if !self.is_realized {
+            self.mutex.unlock_upgradable(self.ticket)
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use crate::inner::Inner;
     use crate::rwlock_ordered::{
-        OrderedRwLock, OrderedRwLockReadGuard, OrderedRwLockWriteGuard,
-        OrderedRwLockWriteOwnedGuard,
+        OrderedRwLock, OrderedRwLockReadGuard, OrderedRwLockReadOwnedGuard,
+        OrderedRwLockWriteGuard, OrderedRwLockWriteOwnedGuard, Tickets,
+    };
+    use crate::wait_strategy::{
+        Adaptive, SpinThenPark, WaitStrategy, WakeImmediately, YieldBackoff,
     };
     use futures::executor::block_on;
     use futures::{FutureExt, StreamExt, TryStreamExt};
+    #[cfg(debug_assertions)]
+    use std::cell::Cell;
     use std::ops::AddAssign;
-    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::sync::Arc;
     use tokio::time::{sleep, Duration};
 
+    #[cfg(debug_assertions)]
+    thread_local! {
+        static REPORTS: Cell<usize> = const { Cell::new(0) };
+        static IS_PANICKING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// The hook of the tests, which counts the reports of the current thread, so the concurrent tests don't interfere.
+    /// The thread, which expects the default report, panics by it.
+    #[cfg(debug_assertions)]
+    fn test_hook() {
+        REPORTS.with(|reports| reports.set(reports.get() + 1));
+        if IS_PANICKING.with(Cell::get) {
+            crate::rwlock_ordered::report_default();
+        }
+    }
+
+    /// The tests, which wait for the lock, while the same task holds it, on purpose, count the reports instead of panicking.
+    fn count_self_deadlocks() {
+        #[cfg(debug_assertions)]
+        crate::rwlock_ordered::set_self_deadlock_hook(test_hook);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
     async fn test_mutex() {
         let c = OrderedRwLock::new(0);
@@ -327,8 +1258,38 @@ mod tests {
         assert_eq!(*co, 10000)
     }
 
+    async fn check_strategy<W: WaitStrategy>() {
+        let c: OrderedRwLock<_, W> = OrderedRwLock::with_strategy(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |i| {
+                let c = &c;
+                async move {
+                    if i % 4 == 0 {
+                        *c.write().await += 1;
+                    } else {
+                        let co = c.read().await;
+                        assert!(*co <= 2500);
+                    }
+                }
+            })
+            .await;
+
+        let co = c.read().await;
+        assert_eq!(*co, 2500)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_wait_strategies() {
+        check_strategy::<WakeImmediately>().await;
+        check_strategy::<SpinThenPark>().await;
+        check_strategy::<YieldBackoff>().await;
+        check_strategy::<Adaptive>().await;
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
     async fn test_mutex_delay() {
+        count_self_deadlocks();
         let expected_result = 100;
         let c = OrderedRwLock::new(0);
 
@@ -373,13 +1334,24 @@ mod tests {
     async fn test_overflow() {
         let mut c = OrderedRwLock::new(String::from("lol"));
 
-        c.inner.state = AtomicUsize::new(usize::max_value());
-        c.inner.current = AtomicUsize::new(usize::max_value());
+        c.tickets = Inner::new(Tickets {
+            writers: usize::MAX,
+            readers: usize::MAX,
+        });
+        c.writers_done = AtomicUsize::new(usize::MAX);
+        c.readers_done = AtomicUsize::new(usize::MAX);
+        c.first_waiting = AtomicUsize::new(usize::MAX.wrapping_add(usize::MAX));
+
+        let co = c.read().await;
+        let write = c.write();
+        let read = c.read();
+        drop(co);
 
-        let mut co: OrderedRwLockWriteGuard<String> = c.write().await;
+        let mut co: OrderedRwLockWriteGuard<String> = write.await;
         co.add_assign("lol");
+        drop(co);
 
-        assert_eq!(*co, "lollol");
+        assert_eq!(*read.await, "lollol");
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
@@ -404,6 +1376,7 @@ mod tests {
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
     async fn test_concurrent_reading() {
+        count_self_deadlocks();
         let c = OrderedRwLock::new(String::from("lol"));
 
         let co: OrderedRwLockReadGuard<String> = c.read().await;
@@ -414,10 +1387,9 @@ mod tests {
             .for_each_concurrent(None, |_c| futures::future::ready(()))
             .await;
 
-        assert!(matches!(
-            tokio::time::timeout(Duration::from_millis(1), c.write()).await,
-            Err(_)
-        ));
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.write())
+            .await
+            .is_err());
 
         let co2: OrderedRwLockReadGuard<String> = c.read().await;
         assert_eq!(*co, *co2);
@@ -425,6 +1397,7 @@ mod tests {
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
     async fn test_concurrent_reading_writing() {
+        count_self_deadlocks();
         let c = OrderedRwLock::new(String::from("lol"));
 
         let co: OrderedRwLockReadGuard<String> = c.read().await;
@@ -436,10 +1409,9 @@ mod tests {
 
         let mut co: OrderedRwLockWriteGuard<String> = c.write().await;
 
-        assert!(matches!(
-            tokio::time::timeout(Duration::from_millis(1), c.read()).await,
-            Err(_)
-        ));
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.read())
+            .await
+            .is_err());
 
         *co += "lol";
 
@@ -451,6 +1423,393 @@ mod tests {
         assert_eq!(*co, *co2);
     }
 
+    /// Returns a pseudo-random number, seeded by the current time.
+    fn random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    fn seed() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+            | 1
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_batch_admission() {
+        count_self_deadlocks();
+        let c = OrderedRwLock::new(0);
+
+        let mut co = c.write().await;
+        let batch: Vec<_> = (0..10).map(|_| c.read()).collect();
+        let mut write = c.write();
+        let late_batch: Vec<_> = (0..10).map(|_| c.read()).collect();
+
+        *co += 1;
+        drop(co);
+
+        // All readers of the batch are admitted together, while the next writer waits for them.
+        let batch = futures::future::join_all(batch).await;
+        assert!(batch.iter().all(|co| **co == 1));
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut write)
+            .await
+            .is_err());
+        drop(batch);
+
+        let mut co = write.await;
+        *co += 1;
+        drop(co);
+
+        let late_batch = futures::future::join_all(late_batch).await;
+        assert!(late_batch.iter().all(|co| **co == 2));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_random_release_order() {
+        count_self_deadlocks();
+        let mut state = seed();
+
+        for _ in 0..10 {
+            let c = OrderedRwLock::new(0);
+
+            let mut batch = futures::future::join_all((0..50).map(|_| c.read())).await;
+            let mut write = c.write();
+            let mut read = c.read();
+
+            while !batch.is_empty() {
+                assert!(tokio::time::timeout(Duration::from_micros(100), &mut write)
+                    .await
+                    .is_err());
+                assert!(tokio::time::timeout(Duration::from_micros(100), &mut read)
+                    .await
+                    .is_err());
+
+                let i = random(&mut state) as usize % batch.len();
+                drop(batch.swap_remove(i));
+            }
+
+            let mut co = write.await;
+            *co += 1;
+            assert!(tokio::time::timeout(Duration::from_micros(100), &mut read)
+                .await
+                .is_err());
+            drop(co);
+
+            assert_eq!(*read.await, 1);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_random_release_exclusion() {
+        let c = Arc::new(OrderedRwLock::new(()));
+        let readers = Arc::new(AtomicUsize::new(0));
+        let is_writing = Arc::new(AtomicBool::new(false));
+        let mut state = seed();
+
+        let handles: Vec<_> = (0..1000)
+            .map(|_| {
+                let is_write = random(&mut state) % 4 == 0;
+                let delay = Duration::from_micros(random(&mut state) % 100);
+                let is_cancelled = random(&mut state) % 8 == 0;
+                let c = c.clone();
+                let readers = readers.clone();
+                let is_writing = is_writing.clone();
+
+                if is_write {
+                    let write = c.write_owned();
+                    tokio::spawn(async move {
+                        if is_cancelled {
+                            sleep(delay).await;
+                            return drop(write);
+                        }
+                        let _co = write.await;
+                        assert!(!is_writing.swap(true, Ordering::SeqCst));
+                        assert_eq!(readers.load(Ordering::SeqCst), 0);
+                        sleep(delay).await;
+                        is_writing.store(false, Ordering::SeqCst);
+                    })
+                } else {
+                    let read = c.read_owned();
+                    tokio::spawn(async move {
+                        if is_cancelled {
+                            sleep(delay).await;
+                            return drop(read);
+                        }
+                        let _co = read.await;
+                        readers.fetch_add(1, Ordering::SeqCst);
+                        assert!(!is_writing.load(Ordering::SeqCst));
+                        sleep(delay).await;
+                        readers.fetch_sub(1, Ordering::SeqCst);
+                    })
+                }
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upgradable_read() {
+        let c = OrderedRwLock::new(0);
+
+        let co = c.read().await;
+        let upgradable = c.upgradable_read().await;
+        let mut write = c.write();
+        let mut read = c.read();
+        assert_eq!(*upgradable, *co);
+
+        // The readers after the upgradable read wait for it like after a writer.
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut read)
+            .await
+            .is_err());
+
+        let mut upgrade = upgradable.upgrade();
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut upgrade)
+            .await
+            .is_err());
+        drop(co);
+
+        let mut co = upgrade.await;
+        *co += 1;
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut write)
+            .await
+            .is_err());
+        drop(co);
+
+        let mut co = write.await;
+        *co += 1;
+        drop(co);
+        assert_eq!(*read.await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_upgrade() {
+        count_self_deadlocks();
+        let c = Arc::new(OrderedRwLock::new(0));
+
+        let co = c.read().await;
+        let upgradable = c.upgradable_read_owned().await;
+        let write = c.write();
+
+        let mut upgrade = upgradable.upgrade();
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut upgrade)
+            .await
+            .is_err());
+        drop(upgrade);
+        drop(co);
+
+        *write.await += 1;
+        let upgradable = c.upgradable_read_owned().await;
+        assert_eq!(*upgradable.upgrade().await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_random_upgradable_exclusion() {
+        let c = Arc::new(OrderedRwLock::new(0));
+        let readers = Arc::new(AtomicUsize::new(0));
+        let is_upgradable = Arc::new(AtomicBool::new(false));
+        let mut state = seed();
+
+        let handles: Vec<_> = (0..1000)
+            .map(|_| {
+                let kind = random(&mut state) % 3;
+                let is_upgrade = random(&mut state) % 2 == 0;
+                let delay = Duration::from_micros(random(&mut state) % 100);
+                let readers = readers.clone();
+                let is_upgradable = is_upgradable.clone();
+
+                match kind {
+                    0 => {
+                        let write = c.write_owned();
+                        tokio::spawn(async move {
+                            let mut co = write.await;
+                            assert_eq!(readers.load(Ordering::SeqCst), 0);
+                            *co += 1;
+                            sleep(delay).await;
+                        })
+                    }
+                    1 => {
+                        let read = c.read_owned();
+                        tokio::spawn(async move {
+                            let _co = read.await;
+                            readers.fetch_add(1, Ordering::SeqCst);
+                            sleep(delay).await;
+                            readers.fetch_sub(1, Ordering::SeqCst);
+                        })
+                    }
+                    _ => {
+                        let upgradable = c.upgradable_read_owned();
+                        tokio::spawn(async move {
+                            let co = upgradable.await;
+                            assert!(!is_upgradable.swap(true, Ordering::SeqCst));
+                            readers.fetch_add(1, Ordering::SeqCst);
+                            sleep(delay).await;
+                            readers.fetch_sub(1, Ordering::SeqCst);
+                            is_upgradable.store(false, Ordering::SeqCst);
+
+                            if is_upgrade {
+                                let mut co = co.upgrade().await;
+                                assert_eq!(readers.load(Ordering::SeqCst), 0);
+                                *co += 1;
+                            }
+                        })
+                    }
+                }
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_tickets() {
+        count_self_deadlocks();
+        let c = OrderedRwLock::new(0);
+
+        let co = c.read().await;
+        let write = c.write();
+        let read = c.read();
+        let mut write2 = c.write();
+        let read2 = c.read();
+        let read3 = c.read();
+
+        // The dropped writer doesn't delay the next batch.
+        drop(write);
+        let co2 = read.await;
+        drop(co);
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut write2)
+            .await
+            .is_err());
+        drop(co2);
+
+        let mut co = write2.await;
+        *co += 1;
+        drop(read2);
+        drop(co);
+
+        assert_eq!(*read3.await, 1);
+        assert_eq!(*c.write().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_many_cancelled_tickets() {
+        let c = OrderedRwLock::new(0);
+
+        // The queues of the dropped tickets are grown several times, while the lock is held.
+        let co = c.write().await;
+        let mut dropped = Vec::new();
+        for _ in 0..20 {
+            dropped.push(c.read());
+            dropped.push(c.read());
+            drop(c.write());
+        }
+        let read = c.read();
+        drop(dropped);
+        drop(co);
+
+        assert_eq!(*read.await, 0);
+        *c.write().await += 1;
+        assert_eq!(*c.read().await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_cloned_reading() {
+        count_self_deadlocks();
+        let c = Arc::new(OrderedRwLock::new(0));
+
+        let co: OrderedRwLockReadOwnedGuard<i32> = c.read_owned().await;
+        let mut write = c.write();
+        let co2 = co.clone();
+        let co3 = co2.clone();
+        assert_eq!(*co, *co3);
+
+        drop(co);
+        drop(co3);
+        assert!(tokio::time::timeout(Duration::from_millis(1), &mut write)
+            .await
+            .is_err());
+        drop(co2);
+
+        let mut co = write.await;
+        *co += 1;
+        drop(co);
+
+        let co = c.read().await;
+        let co2 = co.clone();
+        drop(co);
+        drop(co2);
+        assert_eq!(*c.write().await, 1);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_self_deadlock() {
+        count_self_deadlocks();
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let c = OrderedRwLock::new(0);
+
+                let co = c.read().await;
+                let _write = c.write();
+                let co2 = co.clone();
+                assert_eq!(*co, *co2);
+                let reports = REPORTS.with(Cell::get);
+
+                assert!(tokio::time::timeout(Duration::from_millis(1), c.read())
+                    .await
+                    .is_err());
+                assert_eq!(REPORTS.with(Cell::get), reports + 1);
+            });
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "the task waits for the OrderedRwLock ticket")]
+    fn test_self_deadlock_default_report() {
+        count_self_deadlocks();
+        IS_PANICKING.with(|is_panicking| is_panicking.set(true));
+
+        block_on(async {
+            let c = OrderedRwLock::new(0);
+
+            let _co = c.read().await;
+            let _write = c.write().await;
+        });
+    }
+
+    #[tokio::test]
+    async fn test_self_wait() {
+        count_self_deadlocks();
+        let c = OrderedRwLock::new(0);
+
+        // The read guard is released by the same task, so the writer, which waits for it, isn't deadlocked,
+        // but it's reported, so the task replaces the panic by the hook.
+        tokio::join!(
+            async {
+                let co = c.read().await;
+                sleep(Duration::from_millis(1)).await;
+                drop(co);
+            },
+            async {
+                sleep(Duration::from_micros(100)).await;
+                *c.write().await += 1;
+            }
+        );
+        assert_eq!(*c.read().await, 1);
+    }
+
     #[test]
     fn multithreading_test() {
         let num = 100;
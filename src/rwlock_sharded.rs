@@ -0,0 +1,546 @@
+use crate::inner::{HotInner, Inner};
+use crate::utils::thread_id;
+use crate::wait_strategy::{WaitStrategy, WakeImmediately};
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+const WRITER: usize = !(usize::MAX >> 1);
+
+/// The Sharded RW Lock keeps a readers counter per shard, and every thread reads through its own shard.
+/// So the concurrent reads from the different threads don't touch the same cache line.
+///
+/// The write acquires all shards, so it is slower than the write of the `RwLock`.
+/// It will work well for the read-mostly data, which is read from many threads at the same time.
+///
+/// **The waiting writer closes the shards for the new readers. So you should avoid acquiring the second reading before realizing first inside the one future,
+/// because a write from another thread can start between your readings, and you will get a deadlock.**
+#[derive(Debug)]
+pub struct ShardedRwLock<T: ?Sized> {
+    shards: Box<[Shard]>,
+    inner: HotInner<T>,
+}
+
+/// The readers counter of the shard, with the writer bit.
+/// It's aligned to be placed in its own cache line.
+#[derive(Debug)]
+#[repr(align(128))]
+struct Shard {
+    state: AtomicUsize,
+}
+
+impl<T> ShardedRwLock<T> {
+    /// Create a new `ShardedRwLock` with a shard per available CPU
+    #[inline]
+    pub fn new(data: T) -> ShardedRwLock<T> {
+        let shards = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        ShardedRwLock::with_shards(data, shards)
+    }
+
+    /// Create a new `ShardedRwLock` with the given number of shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    #[inline]
+    pub fn with_shards(data: T, shards: usize) -> ShardedRwLock<T> {
+        assert!(shards > 0, "ShardedRwLock requires at least one shard");
+        ShardedRwLock {
+            shards: (0..shards)
+                .map(|_| Shard {
+                    state: AtomicUsize::new(0),
+                })
+                .collect(),
+            inner: Inner::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> ShardedRwLock<T> {
+    /// Acquires the mutex for are write.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock_sharded::ShardedRwLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = ShardedRwLock::new(10);
+    ///     let mut guard = mutex.write().await;
+    ///     *guard += 1;
+    ///     assert_eq!(*guard, 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn write(&self) -> ShardedRwLockWriteGuardFuture<'_, T> {
+        ShardedRwLockWriteGuardFuture {
+            mutex: self,
+            is_locked: false,
+            is_realized: false,
+        }
+    }
+
+    /// Acquires the mutex for are write.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    /// `ShardedRwLockWriteOwnedGuard` have a `'static` lifetime, but requires the `Arc<ShardedRwLock<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock_sharded::ShardedRwLock;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(ShardedRwLock::new(10));
+    ///     let mut guard = mutex.write_owned().await;
+    ///     *guard += 1;
+    ///     assert_eq!(*guard, 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn write_owned(self: &Arc<Self>) -> ShardedRwLockWriteOwnedGuardFuture<T> {
+        ShardedRwLockWriteOwnedGuardFuture {
+            mutex: self.clone(),
+            is_locked: false,
+            is_realized: false,
+        }
+    }
+
+    /// Acquires the mutex for are read.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock_sharded::ShardedRwLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = ShardedRwLock::new(10);
+    ///     let guard = mutex.read().await;
+    ///     let guard2 = mutex.read().await;
+    ///     assert_eq!(*guard, *guard2);
+    /// }
+    /// ```
+    #[inline]
+    pub fn read(&self) -> ShardedRwLockReadGuardFuture<'_, T> {
+        ShardedRwLockReadGuardFuture { mutex: self }
+    }
+
+    /// Acquires the mutex for are read.
+    ///
+    /// Returns a guard that releases the mutex and wake the next locker when it will be dropped.
+    /// `ShardedRwLockReadOwnedGuard` have a `'static` lifetime, but requires the `Arc<ShardedRwLock<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::rwlock_sharded::ShardedRwLock;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mutex = Arc::new(ShardedRwLock::new(10));
+    ///     let guard = mutex.read_owned().await;
+    ///     let guard2 = mutex.read_owned().await;
+    ///     assert_eq!(*guard, *guard2);
+    /// }
+    /// ```
+    #[inline]
+    pub fn read_owned(self: &Arc<Self>) -> ShardedRwLockReadOwnedGuardFuture<T> {
+        ShardedRwLockReadOwnedGuardFuture {
+            mutex: self.clone(),
+        }
+    }
+
+    /// Returns the number of the readers shards.
+    #[inline]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    #[inline]
+    fn current_shard(&self) -> usize {
+        thread_id() % self.shards.len()
+    }
+
+    #[inline]
+    fn try_acquire_reader(&self, shard: usize) -> bool {
+        let state = &self.shards[shard].state;
+        if state.fetch_add(1, Ordering::Acquire) & WRITER == 0 {
+            true
+        } else {
+            state.fetch_sub(1, Ordering::Release);
+            false
+        }
+    }
+
+    #[inline]
+    fn unlock_reader(&self, shard: usize) {
+        self.shards[shard].state.fetch_sub(1, Ordering::Release);
+    }
+
+    /// Closes all shards for the new readers. The writer lock must be acquired.
+    #[inline]
+    fn lock_shards(&self) {
+        for shard in self.shards.iter() {
+            shard.state.fetch_or(WRITER, Ordering::AcqRel);
+        }
+    }
+
+    #[inline]
+    fn is_readers_released(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.state.load(Ordering::Acquire) == WRITER)
+    }
+
+    #[inline]
+    fn unlock_writer(&self) {
+        for shard in self.shards.iter() {
+            shard.state.fetch_and(!WRITER, Ordering::Release);
+        }
+        self.inner.unlock()
+    }
+
+    /// Acquires the writer lock and all shards step by step.
+    #[inline]
+    fn poll_writer(&self, is_locked: &mut bool, cx: &mut Context<'_>) -> bool {
+        if !*is_locked {
+            if !self.inner.try_acquire() {
+                WakeImmediately::wait(0, cx.waker());
+                return false;
+            }
+            self.lock_shards();
+            *is_locked = true;
+        }
+
+        if self.is_readers_released() {
+            true
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            false
+        }
+    }
+}
+
+/// The Simple Write Lock Guard
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the `ShardedRwLock`, so the mutex will not be dropped while a guard exists.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct ShardedRwLockWriteGuard<'a, T: ?Sized> {
+    mutex: &'a ShardedRwLock<T>,
+}
+
+#[derive(Debug)]
+pub struct ShardedRwLockWriteGuardFuture<'a, T: ?Sized> {
+    mutex: &'a ShardedRwLock<T>,
+    is_locked: bool,
+    is_realized: bool,
+}
+
+/// An owned handle to a held ShardedRwLock.
+/// This guard is only available from a ShardedRwLock that is wrapped in an `Arc`. It is identical to `ShardedRwLockWriteGuard`, except that rather than borrowing the `ShardedRwLock`, it clones the `Arc`, incrementing the reference count. This means that unlike `ShardedRwLockWriteGuard`, it will have the `'static` lifetime.
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `ShardedRwLock`, so even if the lock goes away, the guard remains valid.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct ShardedRwLockWriteOwnedGuard<T: ?Sized> {
+    mutex: Arc<ShardedRwLock<T>>,
+}
+
+#[derive(Debug)]
+pub struct ShardedRwLockWriteOwnedGuardFuture<T: ?Sized> {
+    mutex: Arc<ShardedRwLock<T>>,
+    is_locked: bool,
+    is_realized: bool,
+}
+
+/// The Simple Read Lock Guard
+/// As long as you have this guard, you have shared access to the underlying `T`. The guard internally borrows the `ShardedRwLock`, so the mutex will not be dropped while a guard exists.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct ShardedRwLockReadGuard<'a, T: ?Sized> {
+    mutex: &'a ShardedRwLock<T>,
+    shard: usize,
+}
+
+#[derive(Debug)]
+pub struct ShardedRwLockReadGuardFuture<'a, T: ?Sized> {
+    mutex: &'a ShardedRwLock<T>,
+}
+
+/// An owned handle to a held ShardedRwLock.
+/// This guard is only available from a ShardedRwLock that is wrapped in an `Arc`. It is identical to `ShardedRwLockReadGuard`, except that rather than borrowing the `ShardedRwLock`, it clones the `Arc`, incrementing the reference count. This means that unlike `ShardedRwLockReadGuard`, it will have the `'static` lifetime.
+/// As long as you have this guard, you have shared access to the underlying `T`. The guard internally keeps a reference-couned pointer to the original `ShardedRwLock`, so even if the lock goes away, the guard remains valid.
+/// The lock is automatically released and waked the next locker whenever the guard is dropped, at which point lock will succeed yet again.
+#[derive(Debug)]
+pub struct ShardedRwLockReadOwnedGuard<T: ?Sized> {
+    mutex: Arc<ShardedRwLock<T>>,
+    shard: usize,
+}
+
+#[derive(Debug)]
+pub struct ShardedRwLockReadOwnedGuardFuture<T: ?Sized> {
+    mutex: Arc<ShardedRwLock<T>>,
+}
+
+impl<'a, T: ?Sized> Future for ShardedRwLockWriteGuardFuture<'a, T> {
+    type Output = ShardedRwLockWriteGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mutex = self.mutex;
+        if mutex.poll_writer(&mut self.is_locked, cx) {
+            self.is_realized = true;
+            Poll::Ready(ShardedRwLockWriteGuard { mutex })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized> Future for ShardedRwLockWriteOwnedGuardFuture<T> {
+    type Output = ShardedRwLockWriteOwnedGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mutex = self.mutex.clone();
+        if mutex.poll_writer(&mut self.is_locked, cx) {
+            self.is_realized = true;
+            Poll::Ready(ShardedRwLockWriteOwnedGuard { mutex })
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Future for ShardedRwLockReadGuardFuture<'a, T> {
+    type Output = ShardedRwLockReadGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let shard = self.mutex.current_shard();
+        if self.mutex.try_acquire_reader(shard) {
+            Poll::Ready(ShardedRwLockReadGuard {
+                mutex: self.mutex,
+                shard,
+            })
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized> Future for ShardedRwLockReadOwnedGuardFuture<T> {
+    type Output = ShardedRwLockReadOwnedGuard<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let shard = self.mutex.current_shard();
+        if self.mutex.try_acquire_reader(shard) {
+            Poll::Ready(ShardedRwLockReadOwnedGuard {
+                mutex: self.mutex.clone(),
+                shard,
+            })
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for ShardedRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock_reader(self.shard)
+    }
+}
+
+impl<T: ?Sized> Drop for ShardedRwLockReadOwnedGuard<T> {
+    fn drop(&mut self) {
+        self.mutex.unlock_reader(self.shard)
+    }
+}
+
+impl<T: ?Sized> Drop for ShardedRwLockWriteGuardFuture<'_, T> {
+    fn drop(&mut self) {
+        if self.is_locked && !self.is_realized {
+            self.mutex.unlock_writer()
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for ShardedRwLockWriteOwnedGuardFuture<T> {
+    fn drop(&mut self) {
+        if self.is_locked && !self.is_realized {
+            self.mutex.unlock_writer()
+        }
+    }
+}
+
+crate::impl_send_sync_rwlock!(
+    ShardedRwLock,
+    ShardedRwLockReadGuard,
+    ShardedRwLockReadOwnedGuard,
+    ShardedRwLockWriteGuard,
+    ShardedRwLockWriteOwnedGuard
+);
+
+crate::impl_deref_mut!(ShardedRwLockWriteGuard, 'a);
+crate::impl_deref_mut!(ShardedRwLockWriteOwnedGuard);
+crate::impl_deref!(ShardedRwLockReadGuard, 'a);
+crate::impl_deref!(ShardedRwLockReadOwnedGuard);
+
+crate::impl_drop_guard_self!(ShardedRwLockWriteGuard, 'a, unlock_writer);
+crate::impl_drop_guard_self!(ShardedRwLockWriteOwnedGuard, unlock_writer);
+
+#[cfg(test)]
+mod tests {
+    use crate::rwlock_sharded::{
+        ShardedRwLock, ShardedRwLockReadGuard, ShardedRwLockWriteGuard,
+        ShardedRwLockWriteOwnedGuard,
+    };
+    use futures::executor::block_on;
+    use futures::{FutureExt, StreamExt, TryStreamExt};
+    use std::ops::AddAssign;
+    use std::sync::Arc;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_mutex() {
+        let c = ShardedRwLock::with_shards(0, 4);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: ShardedRwLockWriteGuard<i32> = c.write().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.write().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_mutex_delay() {
+        let expected_result = 100;
+        let c = ShardedRwLock::with_shards(0, 4);
+
+        futures::stream::iter(0..expected_result)
+            .then(|i| c.write().map(move |co| (i, co)))
+            .for_each_concurrent(None, |(i, mut co)| async move {
+                sleep(Duration::from_millis(expected_result - i)).await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.write().await;
+        assert_eq!(*co, expected_result)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_owned_mutex() {
+        let c = Arc::new(ShardedRwLock::new(0));
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: ShardedRwLockWriteOwnedGuard<i32> = c.write_owned().await;
+                *co += 1;
+            })
+            .await;
+
+        let co = c.write_owned().await;
+        assert_eq!(*co, 10000)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_timeout() {
+        let c = ShardedRwLock::new(String::from("lol"));
+
+        let co: ShardedRwLockWriteGuard<String> = c.write().await;
+
+        futures::stream::iter(0..10000i32)
+            .then(|_| tokio::time::timeout(Duration::from_nanos(1), c.write()))
+            .try_for_each_concurrent(None, |_c| futures::future::ok(()))
+            .await
+            .expect_err("timout must be");
+
+        drop(co);
+
+        let mut co: ShardedRwLockWriteGuard<String> = c.write().await;
+        co.add_assign("lol");
+
+        assert_eq!(*co, "lollol");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_concurrent_reading_writing() {
+        let c = ShardedRwLock::with_shards(String::from("lol"), 4);
+
+        let co: ShardedRwLockReadGuard<String> = c.read().await;
+        let co2: ShardedRwLockReadGuard<String> = c.read().await;
+        assert_eq!(*co, *co2);
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.write())
+            .await
+            .is_err());
+
+        drop(co);
+        drop(co2);
+
+        let mut co: ShardedRwLockWriteGuard<String> = c.write().await;
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.read())
+            .await
+            .is_err());
+
+        *co += "lol";
+
+        drop(co);
+
+        let co: ShardedRwLockReadGuard<String> = c.read().await;
+        let co2: ShardedRwLockReadGuard<String> = c.read().await;
+        assert_eq!(*co, "lollol");
+        assert_eq!(*co, *co2);
+    }
+
+    #[test]
+    fn multithreading_test() {
+        let num = 100;
+        let mutex = Arc::new(ShardedRwLock::with_shards(0, 8));
+        let ths: Vec<_> = (0..num)
+            .map(|i| {
+                let mutex = mutex.clone();
+                std::thread::spawn(move || {
+                    block_on(async {
+                        if i % 2 == 0 {
+                            let mut lock = mutex.write().await;
+                            *lock += 1;
+                            drop(lock)
+                        } else {
+                            let lock = mutex.read().await;
+                            assert!(*lock <= num / 2);
+                            drop(lock);
+                            let lock = mutex.read_owned().await;
+                            assert!(*lock <= num / 2);
+                        }
+                    })
+                })
+            })
+            .collect();
+
+        for thread in ths {
+            thread.join().unwrap();
+        }
+
+        block_on(async {
+            let lock = mutex.read().await;
+            assert_eq!(num / 2, *lock)
+        })
+    }
+}
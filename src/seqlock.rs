@@ -0,0 +1,364 @@
+use crate::inner::{HotInner, Inner};
+use crate::wait_strategy::{WaitStrategy, WakeImmediately};
+use std::fmt::Debug;
+use std::future::Future;
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The SeqLock provides optimistic reads of the small `Copy` data.
+///
+/// Readers take a snapshot of the data without any locking and retry if a write raced with them.
+/// So readers never block writers and never register wakers. The synchronous `read` waits for the write in progress by spinning,
+/// and the asynchronous `read_async` yields to the runtime and retries.
+/// Writers are serialized between each other like the `Mutex`.
+#[derive(Debug)]
+pub struct SeqLock<T: ?Sized> {
+    seq: AtomicUsize,
+    inner: HotInner<T>,
+}
+
+/// The number of spins of the synchronous reader, before it yields the thread to the writer.
+const READ_SPINS: usize = 64;
+
+impl<T> SeqLock<T> {
+    /// Create a new `SeqLock`
+    #[inline]
+    pub const fn new(data: T) -> SeqLock<T> {
+        SeqLock {
+            seq: AtomicUsize::new(0),
+            inner: Inner::new(data),
+        }
+    }
+}
+
+impl<T: Copy> SeqLock<T> {
+    /// Reads the consistent snapshot of the data, retrying while a write is in progress.
+    ///
+    /// The reader blocks the thread, until the writer releases the lock. The write guard may be held across `.await`,
+    /// so the reader must not be called on the thread, which has to run the writer, like the current-thread runtime.
+    /// The async code should use `read_async` or `try_read` instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::seqlock::SeqLock;
+    ///
+    /// let lock = SeqLock::new(10);
+    /// assert_eq!(lock.read(), 10);
+    /// ```
+    #[inline]
+    pub fn read(&self) -> T {
+        let mut spins = 0;
+        loop {
+            if let Some(data) = self.try_read() {
+                return data;
+            }
+
+            if spins < READ_SPINS {
+                spins += 1;
+                std::hint::spin_loop();
+            } else {
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    /// Reads the consistent snapshot of the data, waiting for the write in progress without blocking the thread.
+    ///
+    /// The reader doesn't register its waker, it wakes itself and retries on the next poll,
+    /// so the writer doesn't pay for the waiting readers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::seqlock::SeqLock;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     let lock = SeqLock::new(10);
+    ///     assert_eq!(lock.read_async().await, 10);
+    /// }
+    /// ```
+    #[inline]
+    pub fn read_async(&self) -> SeqLockReadFuture<'_, T> {
+        SeqLockReadFuture { lock: self }
+    }
+
+    /// Tries to read the snapshot of the data once.
+    ///
+    /// Returns `None` if a write is in progress or raced with the read.
+    #[inline]
+    pub fn try_read(&self) -> Option<T> {
+        let seq = self.seq.load(Ordering::Acquire);
+        if seq & 1 == 1 {
+            return None;
+        }
+
+        // The data can be changed by the writer at the same time, so the torn copy may be not a valid `T`.
+        // It's kept uninitialized and is assumed initialized only when the sequence wasn't changed.
+        let data = unsafe {
+            std::ptr::read_volatile(self.inner.data.get() as *const T as *const MaybeUninit<T>)
+        };
+        fence(Ordering::Acquire);
+
+        if self.seq.load(Ordering::Relaxed) == seq {
+            Some(unsafe { data.assume_init() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: ?Sized> SeqLock<T> {
+    /// Acquires the lock for are write.
+    ///
+    /// Returns a guard that finishes the write and releases the lock when dropped.
+    /// Readers retry their reads while the guard exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::seqlock::SeqLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let lock = SeqLock::new(10);
+    ///     let mut guard = lock.write().await;
+    ///     *guard += 1;
+    ///     drop(guard);
+    ///     assert_eq!(lock.read(), 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn write(&self) -> SeqLockWriteGuardFuture<'_, T> {
+        SeqLockWriteGuardFuture { mutex: self }
+    }
+
+    /// Acquires the lock for are write.
+    ///
+    /// Returns a guard that finishes the write and releases the lock when dropped.
+    /// `SeqLockWriteOwnedGuard` have a `'static` lifetime, but requires the `Arc<SeqLock<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::seqlock::SeqLock;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let lock = Arc::new(SeqLock::new(10));
+    ///     let mut guard = lock.write_owned().await;
+    ///     *guard += 1;
+    ///     drop(guard);
+    ///     assert_eq!(lock.read(), 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn write_owned(self: &Arc<Self>) -> SeqLockWriteOwnedGuardFuture<T> {
+        SeqLockWriteOwnedGuardFuture {
+            mutex: self.clone(),
+        }
+    }
+
+    /// Acquires the writer lock and makes the sequence odd, so readers will retry.
+    #[inline]
+    fn try_acquire_writer(&self) -> bool {
+        if self.inner.try_acquire() {
+            self.seq.fetch_add(1, Ordering::Relaxed);
+            fence(Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    fn unlock_writer(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.inner.unlock();
+    }
+}
+
+/// The SeqLock Write Guard
+/// As long as you have this guard, you have exclusive access to the underlying `T`. The guard internally borrows the `SeqLock`, so the lock will not be dropped while a guard exists.
+/// The write is finished and the lock is released whenever the guard is dropped.
+#[derive(Debug)]
+pub struct SeqLockWriteGuard<'a, T: ?Sized> {
+    mutex: &'a SeqLock<T>,
+}
+
+#[derive(Debug)]
+pub struct SeqLockWriteGuardFuture<'a, T: ?Sized> {
+    mutex: &'a SeqLock<T>,
+}
+
+/// An owned handle to a held SeqLock.
+/// This guard is only available from a SeqLock that is wrapped in an `Arc`. It is identical to `SeqLockWriteGuard`, except that rather than borrowing the `SeqLock`, it clones the `Arc`, incrementing the reference count. This means that unlike `SeqLockWriteGuard`, it will have the `'static` lifetime.
+/// The write is finished and the lock is released whenever the guard is dropped.
+#[derive(Debug)]
+pub struct SeqLockWriteOwnedGuard<T: ?Sized> {
+    mutex: Arc<SeqLock<T>>,
+}
+
+#[derive(Debug)]
+pub struct SeqLockWriteOwnedGuardFuture<T: ?Sized> {
+    mutex: Arc<SeqLock<T>>,
+}
+
+/// The future of the `read_async`, which yields to the runtime while the write is in progress.
+#[derive(Debug)]
+pub struct SeqLockReadFuture<'a, T: ?Sized> {
+    lock: &'a SeqLock<T>,
+}
+
+impl<T: Copy> Future for SeqLockReadFuture<'_, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.lock.try_read() {
+            Some(data) => Poll::Ready(data),
+            None => {
+                WakeImmediately::wait(0, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Future for SeqLockWriteGuardFuture<'a, T> {
+    type Output = SeqLockWriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.mutex.try_acquire_writer() {
+            Poll::Ready(SeqLockWriteGuard { mutex: self.mutex })
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: ?Sized> Future for SeqLockWriteOwnedGuardFuture<T> {
+    type Output = SeqLockWriteOwnedGuard<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.mutex.try_acquire_writer() {
+            Poll::Ready(SeqLockWriteOwnedGuard {
+                mutex: self.mutex.clone(),
+            })
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+crate::impl_send_sync_mutex!(SeqLock, SeqLockWriteGuard, SeqLockWriteOwnedGuard);
+
+crate::impl_deref_mut!(SeqLockWriteGuard, 'a);
+crate::impl_deref_mut!(SeqLockWriteOwnedGuard);
+
+crate::impl_drop_guard_self!(SeqLockWriteGuard, 'a, unlock_writer);
+crate::impl_drop_guard_self!(SeqLockWriteOwnedGuard, unlock_writer);
+
+#[cfg(test)]
+mod tests {
+    use crate::seqlock::{SeqLock, SeqLockWriteGuard};
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_writes() {
+        let c = SeqLock::new(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: SeqLockWriteGuard<i32> = c.write().await;
+                *co += 1;
+            })
+            .await;
+
+        assert_eq!(c.read(), 10000)
+    }
+
+    #[tokio::test]
+    async fn test_read_while_writing() {
+        let c = SeqLock::new(10);
+
+        let mut co = c.write().await;
+        *co += 1;
+        assert_eq!(c.try_read(), None);
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.write())
+            .await
+            .is_err());
+
+        drop(co);
+        assert_eq!(c.try_read(), Some(11));
+    }
+
+    #[tokio::test]
+    async fn test_read_async_while_writing() {
+        let c = Arc::new(SeqLock::new(10));
+
+        let mut co = c.write_owned().await;
+        let reader = {
+            let c = c.clone();
+            tokio::spawn(async move { c.read_async().await })
+        };
+        let _ = tokio::task::yield_now().await;
+        *co += 1;
+        drop(co);
+
+        assert_eq!(reader.await.unwrap(), 11);
+    }
+
+    #[test]
+    fn multithreading_test() {
+        let lock = Arc::new(SeqLock::new((0u64, 0u64)));
+        let is_done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                let is_done = is_done.clone();
+                std::thread::spawn(move || {
+                    while !is_done.load(Ordering::Relaxed) {
+                        let (a, b) = lock.read();
+                        assert_eq!(a * 2, b);
+                    }
+                })
+            })
+            .collect();
+
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    futures::executor::block_on(async {
+                        for _ in 0..1000 {
+                            let mut guard = lock.write().await;
+                            guard.0 += 1;
+                            guard.1 += 2;
+                        }
+                    })
+                })
+            })
+            .collect();
+
+        for thread in writers {
+            thread.join().unwrap();
+        }
+        is_done.store(true, Ordering::Relaxed);
+        for thread in readers {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(lock.read(), (4000, 8000));
+    }
+}
@@ -0,0 +1,508 @@
+use crate::cache_padded::CachePadded;
+use crate::inner::{HotInner, Inner};
+use crate::utils::thread_id;
+use crate::wait_strategy::{WaitStrategy, WakeImmediately};
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The Snapshot Lock keeps the data in the `Arc` and replaces it wholesale on every write.
+///
+/// Readers load the current snapshot without any locking and never wait on writers.
+/// Writers are serialized between each other like the `Mutex`, and commit the new version when the guard is dropped.
+/// The old snapshot is freed when the last reader drops it.
+///
+/// It will work well for the data, which is read on every request and replaced rarely, like routing tables.
+pub struct SnapshotLock<T> {
+    current: AtomicPtr<T>,
+    /// The counters of the readers, which are loading the pointer right now.
+    /// Every thread counts in its own stripe, and every stripe has a counter per phase.
+    readers: Box<[CachePadded<[AtomicUsize; 2]>]>,
+    /// The phase of the new readers, it's flipped by the writer.
+    phase: AtomicUsize,
+    inner: HotInner<()>,
+    _marker: PhantomData<Arc<T>>,
+}
+
+/// The number of the stripes of the readers counters, so the readers of different threads don't share the cache line.
+const READER_STRIPES: usize = 8;
+
+/// The number of spins of the writer, before it yields the thread to the loading readers.
+const DRAIN_SPINS: usize = 64;
+
+impl<T> SnapshotLock<T> {
+    /// Create a new `SnapshotLock`
+    #[inline]
+    pub fn new(data: T) -> SnapshotLock<T> {
+        SnapshotLock::from_arc(Arc::new(data))
+    }
+
+    /// Create a new `SnapshotLock` from the existing snapshot
+    #[inline]
+    pub fn from_arc(data: Arc<T>) -> SnapshotLock<T> {
+        SnapshotLock {
+            current: AtomicPtr::new(Arc::into_raw(data) as *mut T),
+            readers: (0..READER_STRIPES)
+                .map(|_| CachePadded::new([AtomicUsize::new(0), AtomicUsize::new(0)]))
+                .collect(),
+            phase: AtomicUsize::new(0),
+            inner: Inner::new(()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the current snapshot of the data.
+    ///
+    /// It never waits on writers, the snapshot stays the same even if a new version is committed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::snapshot_lock::SnapshotLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let lock = SnapshotLock::new(10);
+    ///     let snapshot = lock.load();
+    ///     lock.store(11).await;
+    ///     assert_eq!(*snapshot, 10);
+    ///     assert_eq!(*lock.load(), 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn load(&self) -> Arc<T> {
+        // The readers counter protects the pointer between its loading and the reference counter increment.
+        // Writers wait for it to be zero before dropping the replaced snapshot.
+        let counter = &self.readers[current_stripe()][self.phase.load(Ordering::SeqCst) & 1];
+        counter.fetch_add(1, Ordering::SeqCst);
+        let ptr = self.current.load(Ordering::SeqCst);
+        let snapshot = unsafe {
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+        counter.fetch_sub(1, Ordering::Release);
+        snapshot
+    }
+
+    /// Acquires the lock for are write, cloning the current snapshot.
+    ///
+    /// Returns a guard with the copy of the data, which commits it as the new version when dropped.
+    /// The guard, which is dropped while panicking, discards the copy, so the partial change isn't published.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::snapshot_lock::SnapshotLock;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let lock = SnapshotLock::new(vec![1, 2]);
+    ///     let mut guard = lock.write().await;
+    ///     guard.push(3);
+    ///     assert_eq!(*lock.load(), vec![1, 2]);
+    ///     drop(guard);
+    ///     assert_eq!(*lock.load(), vec![1, 2, 3]);
+    /// }
+    /// ```
+    #[inline]
+    pub fn write(&self) -> SnapshotLockWriteGuardFuture<'_, T> {
+        SnapshotLockWriteGuardFuture { mutex: self }
+    }
+
+    /// Acquires the lock for are write, cloning the current snapshot.
+    ///
+    /// Returns a guard with the copy of the data, which commits it as the new version when dropped.
+    /// The guard, which is dropped while panicking, discards the copy, so the partial change isn't published.
+    /// `SnapshotLockWriteOwnedGuard` have a `'static` lifetime, but requires the `Arc<SnapshotLock<T>>` type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::snapshot_lock::SnapshotLock;
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let lock = Arc::new(SnapshotLock::new(10));
+    ///     let mut guard = lock.write_owned().await;
+    ///     *guard += 1;
+    ///     drop(guard);
+    ///     assert_eq!(*lock.load(), 11);
+    /// }
+    /// ```
+    #[inline]
+    pub fn write_owned(self: &Arc<Self>) -> SnapshotLockWriteOwnedGuardFuture<T> {
+        SnapshotLockWriteOwnedGuardFuture {
+            mutex: self.clone(),
+        }
+    }
+
+    /// Replaces the data with the new version, waiting for the other writers.
+    pub async fn store(&self, data: T) {
+        WriterFuture { mutex: self }.await;
+        self.commit(Arc::new(data));
+        self.inner.unlock();
+    }
+
+    /// Replaces the current snapshot. The writer lock must be acquired.
+    #[inline]
+    fn commit(&self, data: Arc<T>) {
+        let old = self
+            .current
+            .swap(Arc::into_raw(data) as *mut T, Ordering::SeqCst);
+
+        // Readers, which loaded the old pointer, may not have incremented its reference counter yet.
+        // The new readers count in the other phase, so the writer waits only for the readers, which are loading now,
+        // and it isn't starved by the steady stream of loads.
+        let phase = self.phase.load(Ordering::Relaxed) & 1;
+        self.wait_readers(phase ^ 1);
+        self.phase.store(phase ^ 1, Ordering::SeqCst);
+        self.wait_readers(phase);
+
+        drop(unsafe { Arc::from_raw(old) });
+    }
+
+    /// Waits for the readers of the `phase`, which don't get new readers.
+    fn wait_readers(&self, phase: usize) {
+        let mut spins = 0;
+        while self
+            .readers
+            .iter()
+            .any(|stripe| stripe[phase].load(Ordering::SeqCst) != 0)
+        {
+            if spins < DRAIN_SPINS {
+                spins += 1;
+                std::hint::spin_loop();
+            } else {
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    /// Commits the copy of the writer and releases the lock. The copy of the panicked writer is dropped after the release.
+    #[inline]
+    fn unlock_writer(&self, data: T) {
+        if !std::thread::panicking() {
+            self.commit(Arc::new(data));
+        }
+        self.inner.unlock()
+    }
+
+    /// Returns the current data. The writer lock must be acquired.
+    #[inline]
+    unsafe fn current(&self) -> &T {
+        &*self.current.load(Ordering::Acquire)
+    }
+}
+
+#[inline]
+fn current_stripe() -> usize {
+    thread_id() % READER_STRIPES
+}
+
+impl<T> Drop for SnapshotLock<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Arc::from_raw(*self.current.get_mut()) });
+    }
+}
+
+impl<T: Debug> Debug for SnapshotLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotLock")
+            .field("data", &self.load())
+            .finish()
+    }
+}
+
+unsafe impl<T> Send for SnapshotLock<T> where T: Send + Sync {}
+unsafe impl<T> Sync for SnapshotLock<T> where T: Send + Sync {}
+
+/// The Snapshot Lock Write Guard
+/// As long as you have this guard, you have exclusive access to the copy of the underlying `T`. The guard internally borrows the `SnapshotLock`, so the lock will not be dropped while a guard exists.
+/// The copy is committed as the new version and the lock is released whenever the guard is dropped.
+#[derive(Debug)]
+pub struct SnapshotLockWriteGuard<'a, T> {
+    mutex: &'a SnapshotLock<T>,
+    data: Option<T>,
+}
+
+#[derive(Debug)]
+pub struct SnapshotLockWriteGuardFuture<'a, T> {
+    mutex: &'a SnapshotLock<T>,
+}
+
+/// An owned handle to a held SnapshotLock.
+/// This guard is only available from a SnapshotLock that is wrapped in an `Arc`. It is identical to `SnapshotLockWriteGuard`, except that rather than borrowing the `SnapshotLock`, it clones the `Arc`, incrementing the reference count. This means that unlike `SnapshotLockWriteGuard`, it will have the `'static` lifetime.
+/// The copy is committed as the new version and the lock is released whenever the guard is dropped.
+#[derive(Debug)]
+pub struct SnapshotLockWriteOwnedGuard<T> {
+    mutex: Arc<SnapshotLock<T>>,
+    data: Option<T>,
+}
+
+#[derive(Debug)]
+pub struct SnapshotLockWriteOwnedGuardFuture<T> {
+    mutex: Arc<SnapshotLock<T>>,
+}
+
+impl<'a, T: Clone> Future for SnapshotLockWriteGuardFuture<'a, T> {
+    type Output = SnapshotLockWriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.mutex.inner.try_acquire() {
+            Poll::Ready(SnapshotLockWriteGuard {
+                mutex: self.mutex,
+                data: Some(unsafe { self.mutex.current() }.clone()),
+            })
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: Clone> Future for SnapshotLockWriteOwnedGuardFuture<T> {
+    type Output = SnapshotLockWriteOwnedGuard<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.mutex.inner.try_acquire() {
+            Poll::Ready(SnapshotLockWriteOwnedGuard {
+                mutex: self.mutex.clone(),
+                data: Some(unsafe { self.mutex.current() }.clone()),
+            })
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+/// Acquires the writer lock without cloning the data.
+struct WriterFuture<'a, T> {
+    mutex: &'a SnapshotLock<T>,
+}
+
+impl<T> Future for WriterFuture<'_, T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.mutex.inner.try_acquire() {
+            Poll::Ready(())
+        } else {
+            WakeImmediately::wait(0, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Deref for SnapshotLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.data.as_ref().expect("data exists until drop")
+    }
+}
+
+impl<T> DerefMut for SnapshotLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.data.as_mut().expect("data exists until drop")
+    }
+}
+
+impl<T> Deref for SnapshotLockWriteOwnedGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.data.as_ref().expect("data exists until drop")
+    }
+}
+
+impl<T> DerefMut for SnapshotLockWriteOwnedGuard<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.data.as_mut().expect("data exists until drop")
+    }
+}
+
+impl<T> Drop for SnapshotLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(data) = self.data.take() {
+            self.mutex.unlock_writer(data)
+        }
+    }
+}
+
+impl<T> Drop for SnapshotLockWriteOwnedGuard<T> {
+    fn drop(&mut self) {
+        if let Some(data) = self.data.take() {
+            self.mutex.unlock_writer(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::snapshot_lock::{SnapshotLock, SnapshotLockWriteGuard};
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_writes() {
+        let c = SnapshotLock::new(0);
+
+        futures::stream::iter(0..10000)
+            .for_each_concurrent(None, |_| async {
+                let mut co: SnapshotLockWriteGuard<i32> = c.write().await;
+                *co += 1;
+            })
+            .await;
+
+        assert_eq!(*c.load(), 10000)
+    }
+
+    #[tokio::test]
+    async fn test_readers_dont_wait() {
+        let c = SnapshotLock::new(String::from("lol"));
+
+        let mut co = c.write().await;
+        *co += "lol";
+        assert_eq!(*c.load(), "lol");
+
+        assert!(tokio::time::timeout(Duration::from_millis(1), c.write())
+            .await
+            .is_err());
+
+        let snapshot = c.load();
+        drop(co);
+
+        assert_eq!(*snapshot, "lol");
+        assert_eq!(*c.load(), "lollol");
+
+        c.store(String::from("kek")).await;
+        assert_eq!(*c.load(), "kek");
+    }
+
+    #[test]
+    fn test_snapshots_dropped() {
+        let value = Arc::new(0);
+        let c = SnapshotLock::from_arc(value.clone());
+
+        futures::executor::block_on(c.store(1));
+        assert_eq!(Arc::strong_count(&value), 1);
+
+        let snapshot = c.load();
+        assert_eq!(Arc::strong_count(&snapshot), 2);
+        drop(c);
+        assert_eq!(Arc::strong_count(&snapshot), 1);
+    }
+
+    #[test]
+    fn test_writer_not_starved_by_loads() {
+        let lock = Arc::new(SnapshotLock::new(0usize));
+        let is_done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let lock = lock.clone();
+                let is_done = is_done.clone();
+                std::thread::spawn(move || {
+                    while !is_done.load(Ordering::Relaxed) {
+                        for _ in 0..100 {
+                            drop(lock.load());
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        futures::executor::block_on(async {
+            for i in 1..=1000 {
+                lock.store(i).await;
+            }
+        });
+        is_done.store(true, Ordering::Relaxed);
+        for thread in readers {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(*lock.load(), 1000);
+    }
+
+    #[test]
+    fn test_panicked_write() {
+        let lock = Arc::new(SnapshotLock::new(vec![1, 2]));
+
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            block_on(async {
+                let mut guard = lock.write().await;
+                guard.push(3);
+                panic!("the writer panics");
+            })
+        }));
+        assert!(panic.is_err());
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            block_on(async {
+                let mut guard = lock.write_owned().await;
+                guard.push(3);
+                panic!("the writer panics");
+            })
+        }));
+        assert!(panic.is_err());
+
+        // The partial changes aren't published, and the lock is released.
+        assert_eq!(*lock.load(), vec![1, 2]);
+        block_on(async { lock.write().await.push(4) });
+        assert_eq!(*lock.load(), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn multithreading_test() {
+        let lock = Arc::new(SnapshotLock::new(vec![0usize]));
+        let is_done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                let is_done = is_done.clone();
+                std::thread::spawn(move || {
+                    while !is_done.load(Ordering::Relaxed) {
+                        let snapshot = lock.load();
+                        assert_eq!(*snapshot.last().unwrap() + 1, snapshot.len());
+                    }
+                })
+            })
+            .collect();
+
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    futures::executor::block_on(async {
+                        for _ in 0..100 {
+                            let mut guard = lock.write().await;
+                            let len = guard.len();
+                            guard.push(len);
+                        }
+                    })
+                })
+            })
+            .collect();
+
+        for thread in writers {
+            thread.join().unwrap();
+        }
+        is_done.store(true, Ordering::Relaxed);
+        for thread in readers {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(lock.load().len(), 401);
+    }
+}
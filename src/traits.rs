@@ -0,0 +1,571 @@
+use crate::mutex::{Mutex, MutexGuard, MutexGuardFuture, MutexOwnedGuard, MutexOwnedGuardFuture};
+use crate::mutex_ordered::{
+    OrderedMutex, OrderedMutexGuard, OrderedMutexGuardFuture, OrderedMutexOwnedGuard,
+    OrderedMutexOwnedGuardFuture,
+};
+use crate::raw::{RawAsyncMutex, RawAsyncRwLock};
+use crate::rwlock::{
+    RwLock, RwLockReadGuard, RwLockReadGuardFuture, RwLockReadOwnedGuard,
+    RwLockReadOwnedGuardFuture, RwLockWriteGuard, RwLockWriteGuardFuture, RwLockWriteOwnedGuard,
+    RwLockWriteOwnedGuardFuture,
+};
+use crate::rwlock_ordered::{
+    OrderedRwLock, OrderedRwLockReadGuard, OrderedRwLockReadGuardFuture,
+    OrderedRwLockReadOwnedGuard, OrderedRwLockReadOwnedGuardFuture, OrderedRwLockWriteGuard,
+    OrderedRwLockWriteGuardFuture, OrderedRwLockWriteOwnedGuard,
+    OrderedRwLockWriteOwnedGuardFuture,
+};
+use crate::wait_strategy::WaitStrategy;
+use crate::{raw_mutex, raw_rwlock};
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// The asynchronous mutex, so the code may be generic over the type of the mutex,
+/// like switching between the `Mutex` and the `OrderedMutex`.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::mutex::Mutex;
+/// use fast_async_mutex::mutex_ordered::OrderedMutex;
+/// use fast_async_mutex::traits::AsyncMutexLike;
+///
+/// async fn increment<M: AsyncMutexLike<Target = i32>>(mutex: &M) -> i32 {
+///     let mut guard = mutex.lock().await;
+///     *guard += 1;
+///     *guard
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     assert_eq!(increment(&Mutex::new(10)).await, 11);
+///     assert_eq!(increment(&OrderedMutex::new(10)).await, 11);
+/// }
+/// ```
+pub trait AsyncMutexLike {
+    /// The type of the data, which is protected by the mutex.
+    type Target: ?Sized;
+
+    type Guard<'a>: DerefMut<Target = Self::Target>
+    where
+        Self: 'a;
+
+    type GuardFuture<'a>: Future<Output = Self::Guard<'a>>
+    where
+        Self: 'a;
+
+    type OwnedGuard: DerefMut<Target = Self::Target>;
+
+    type OwnedGuardFuture: Future<Output = Self::OwnedGuard>;
+
+    /// Create a new mutex
+    fn new(data: Self::Target) -> Self
+    where
+        Self: Sized,
+        Self::Target: Sized;
+
+    /// Acquires the mutex.
+    fn lock(&self) -> Self::GuardFuture<'_>;
+
+    /// Acquires the mutex by the guard with the `'static` lifetime.
+    fn lock_owned(self: &Arc<Self>) -> Self::OwnedGuardFuture;
+}
+
+/// The asynchronous RW Lock, so the code may be generic over the type of the lock,
+/// like switching between the `RwLock` and the `OrderedRwLock`.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::rwlock::RwLock;
+/// use fast_async_mutex::rwlock_ordered::OrderedRwLock;
+/// use fast_async_mutex::traits::AsyncRwLockLike;
+///
+/// async fn increment<L: AsyncRwLockLike<Target = i32>>(lock: &L) -> i32 {
+///     *lock.write().await += 1;
+///     let guard = lock.read().await;
+///     *guard
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     assert_eq!(increment(&RwLock::new(10)).await, 11);
+///     assert_eq!(increment(&OrderedRwLock::new(10)).await, 11);
+/// }
+/// ```
+pub trait AsyncRwLockLike {
+    /// The type of the data, which is protected by the lock.
+    type Target: ?Sized;
+
+    type ReadGuard<'a>: Deref<Target = Self::Target>
+    where
+        Self: 'a;
+
+    type ReadGuardFuture<'a>: Future<Output = Self::ReadGuard<'a>>
+    where
+        Self: 'a;
+
+    type WriteGuard<'a>: DerefMut<Target = Self::Target>
+    where
+        Self: 'a;
+
+    type WriteGuardFuture<'a>: Future<Output = Self::WriteGuard<'a>>
+    where
+        Self: 'a;
+
+    type ReadOwnedGuard: Deref<Target = Self::Target>;
+
+    type ReadOwnedGuardFuture: Future<Output = Self::ReadOwnedGuard>;
+
+    type WriteOwnedGuard: DerefMut<Target = Self::Target>;
+
+    type WriteOwnedGuardFuture: Future<Output = Self::WriteOwnedGuard>;
+
+    /// Create a new lock
+    fn new(data: Self::Target) -> Self
+    where
+        Self: Sized,
+        Self::Target: Sized;
+
+    /// Acquires the lock for are read.
+    fn read(&self) -> Self::ReadGuardFuture<'_>;
+
+    /// Acquires the lock for are write.
+    fn write(&self) -> Self::WriteGuardFuture<'_>;
+
+    /// Acquires the lock for are read by the guard with the `'static` lifetime.
+    fn read_owned(self: &Arc<Self>) -> Self::ReadOwnedGuardFuture;
+
+    /// Acquires the lock for are write by the guard with the `'static` lifetime.
+    fn write_owned(self: &Arc<Self>) -> Self::WriteOwnedGuardFuture;
+}
+
+impl<T: ?Sized, W: WaitStrategy> AsyncMutexLike for Mutex<T, W> {
+    type Target = T;
+    type Guard<'a>
+        = MutexGuard<'a, T, W>
+    where
+        Self: 'a;
+    type GuardFuture<'a>
+        = MutexGuardFuture<'a, T, W>
+    where
+        Self: 'a;
+    type OwnedGuard = MutexOwnedGuard<T, W>;
+    type OwnedGuardFuture = MutexOwnedGuardFuture<T, W>;
+
+    #[inline]
+    fn new(data: T) -> Self
+    where
+        T: Sized,
+    {
+        Mutex::with_strategy(data)
+    }
+
+    #[inline]
+    fn lock(&self) -> Self::GuardFuture<'_> {
+        Mutex::lock(self)
+    }
+
+    #[inline]
+    fn lock_owned(self: &Arc<Self>) -> Self::OwnedGuardFuture {
+        Mutex::lock_owned(self)
+    }
+}
+
+impl<T: ?Sized, W: WaitStrategy> AsyncMutexLike for OrderedMutex<T, W> {
+    type Target = T;
+    type Guard<'a>
+        = OrderedMutexGuard<'a, T, W>
+    where
+        Self: 'a;
+    type GuardFuture<'a>
+        = OrderedMutexGuardFuture<'a, T, W>
+    where
+        Self: 'a;
+    type OwnedGuard = OrderedMutexOwnedGuard<T, W>;
+    type OwnedGuardFuture = OrderedMutexOwnedGuardFuture<T, W>;
+
+    #[inline]
+    fn new(data: T) -> Self
+    where
+        T: Sized,
+    {
+        OrderedMutex::with_strategy(data)
+    }
+
+    #[inline]
+    fn lock(&self) -> Self::GuardFuture<'_> {
+        OrderedMutex::lock(self)
+    }
+
+    #[inline]
+    fn lock_owned(self: &Arc<Self>) -> Self::OwnedGuardFuture {
+        OrderedMutex::lock_owned(self)
+    }
+}
+
+impl<T: ?Sized, W: WaitStrategy> AsyncRwLockLike for RwLock<T, W> {
+    type Target = T;
+    type ReadGuard<'a>
+        = RwLockReadGuard<'a, T, W>
+    where
+        Self: 'a;
+    type ReadGuardFuture<'a>
+        = RwLockReadGuardFuture<'a, T, W>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = RwLockWriteGuard<'a, T, W>
+    where
+        Self: 'a;
+    type WriteGuardFuture<'a>
+        = RwLockWriteGuardFuture<'a, T, W>
+    where
+        Self: 'a;
+    type ReadOwnedGuard = RwLockReadOwnedGuard<T, W>;
+    type ReadOwnedGuardFuture = RwLockReadOwnedGuardFuture<T, W>;
+    type WriteOwnedGuard = RwLockWriteOwnedGuard<T, W>;
+    type WriteOwnedGuardFuture = RwLockWriteOwnedGuardFuture<T, W>;
+
+    #[inline]
+    fn new(data: T) -> Self
+    where
+        T: Sized,
+    {
+        RwLock::with_strategy(data)
+    }
+
+    #[inline]
+    fn read(&self) -> Self::ReadGuardFuture<'_> {
+        RwLock::read(self)
+    }
+
+    #[inline]
+    fn write(&self) -> Self::WriteGuardFuture<'_> {
+        RwLock::write(self)
+    }
+
+    #[inline]
+    fn read_owned(self: &Arc<Self>) -> Self::ReadOwnedGuardFuture {
+        RwLock::read_owned(self)
+    }
+
+    #[inline]
+    fn write_owned(self: &Arc<Self>) -> Self::WriteOwnedGuardFuture {
+        RwLock::write_owned(self)
+    }
+}
+
+impl<T: ?Sized, W: WaitStrategy> AsyncRwLockLike for OrderedRwLock<T, W> {
+    type Target = T;
+    type ReadGuard<'a>
+        = OrderedRwLockReadGuard<'a, T, W>
+    where
+        Self: 'a;
+    type ReadGuardFuture<'a>
+        = OrderedRwLockReadGuardFuture<'a, T, W>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = OrderedRwLockWriteGuard<'a, T, W>
+    where
+        Self: 'a;
+    type WriteGuardFuture<'a>
+        = OrderedRwLockWriteGuardFuture<'a, T, W>
+    where
+        Self: 'a;
+    type ReadOwnedGuard = OrderedRwLockReadOwnedGuard<T, W>;
+    type ReadOwnedGuardFuture = OrderedRwLockReadOwnedGuardFuture<T, W>;
+    type WriteOwnedGuard = OrderedRwLockWriteOwnedGuard<T, W>;
+    type WriteOwnedGuardFuture = OrderedRwLockWriteOwnedGuardFuture<T, W>;
+
+    #[inline]
+    fn new(data: T) -> Self
+    where
+        T: Sized,
+    {
+        OrderedRwLock::with_strategy(data)
+    }
+
+    #[inline]
+    fn read(&self) -> Self::ReadGuardFuture<'_> {
+        OrderedRwLock::read(self)
+    }
+
+    #[inline]
+    fn write(&self) -> Self::WriteGuardFuture<'_> {
+        OrderedRwLock::write(self)
+    }
+
+    #[inline]
+    fn read_owned(self: &Arc<Self>) -> Self::ReadOwnedGuardFuture {
+        OrderedRwLock::read_owned(self)
+    }
+
+    #[inline]
+    fn write_owned(self: &Arc<Self>) -> Self::WriteOwnedGuardFuture {
+        OrderedRwLock::write_owned(self)
+    }
+}
+
+impl<R: RawAsyncMutex, T: ?Sized> AsyncMutexLike for raw_mutex::Mutex<R, T> {
+    type Target = T;
+    type Guard<'a>
+        = raw_mutex::MutexGuard<'a, R, T>
+    where
+        Self: 'a;
+    type GuardFuture<'a>
+        = raw_mutex::MutexGuardFuture<'a, R, T>
+    where
+        Self: 'a;
+    type OwnedGuard = raw_mutex::MutexOwnedGuard<R, T>;
+    type OwnedGuardFuture = raw_mutex::MutexOwnedGuardFuture<R, T>;
+
+    #[inline]
+    fn new(data: T) -> Self
+    where
+        T: Sized,
+    {
+        raw_mutex::Mutex::new(data)
+    }
+
+    #[inline]
+    fn lock(&self) -> Self::GuardFuture<'_> {
+        raw_mutex::Mutex::lock(self)
+    }
+
+    #[inline]
+    fn lock_owned(self: &Arc<Self>) -> Self::OwnedGuardFuture {
+        raw_mutex::Mutex::lock_owned(self)
+    }
+}
+
+impl<R: RawAsyncRwLock, T: ?Sized> AsyncRwLockLike for raw_rwlock::RwLock<R, T> {
+    type Target = T;
+    type ReadGuard<'a>
+        = raw_rwlock::RwLockReadGuard<'a, R, T>
+    where
+        Self: 'a;
+    type ReadGuardFuture<'a>
+        = raw_rwlock::RwLockReadGuardFuture<'a, R, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = raw_rwlock::RwLockWriteGuard<'a, R, T>
+    where
+        Self: 'a;
+    type WriteGuardFuture<'a>
+        = raw_rwlock::RwLockWriteGuardFuture<'a, R, T>
+    where
+        Self: 'a;
+    type ReadOwnedGuard = raw_rwlock::RwLockReadOwnedGuard<R, T>;
+    type ReadOwnedGuardFuture = raw_rwlock::RwLockReadOwnedGuardFuture<R, T>;
+    type WriteOwnedGuard = raw_rwlock::RwLockWriteOwnedGuard<R, T>;
+    type WriteOwnedGuardFuture = raw_rwlock::RwLockWriteOwnedGuardFuture<R, T>;
+
+    #[inline]
+    fn new(data: T) -> Self
+    where
+        T: Sized,
+    {
+        raw_rwlock::RwLock::new(data)
+    }
+
+    #[inline]
+    fn read(&self) -> Self::ReadGuardFuture<'_> {
+        raw_rwlock::RwLock::read(self)
+    }
+
+    #[inline]
+    fn write(&self) -> Self::WriteGuardFuture<'_> {
+        raw_rwlock::RwLock::write(self)
+    }
+
+    #[inline]
+    fn read_owned(self: &Arc<Self>) -> Self::ReadOwnedGuardFuture {
+        raw_rwlock::RwLock::read_owned(self)
+    }
+
+    #[inline]
+    fn write_owned(self: &Arc<Self>) -> Self::WriteOwnedGuardFuture {
+        raw_rwlock::RwLock::write_owned(self)
+    }
+}
+
+/// The behaviour suites, which are run against every implementation of the lock traits.
+/// They are exported by the `test-suite` feature, so the implementors of the traits and of the raw lock algorithms
+/// can check their locks the same way. The suites run the lockers on their own threads by `futures::executor`,
+/// so they don't depend on the async runtime and are called from the plain tests.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::mutex::Mutex;
+/// use fast_async_mutex::rwlock::RwLock;
+/// use fast_async_mutex::traits::suite::{check_mutex, check_rwlock};
+///
+/// check_mutex::<Mutex<usize>>();
+/// check_rwlock::<RwLock<usize>>();
+/// ```
+#[cfg(any(test, feature = "test-suite"))]
+pub mod suite {
+    use crate::traits::{AsyncMutexLike, AsyncRwLockLike};
+    use futures::executor::block_on;
+    use futures::{FutureExt, StreamExt};
+    use std::future::poll_fn;
+    use std::sync::Arc;
+    use std::task::Poll;
+    use std::thread;
+
+    /// The number of threads, which run the lockers concurrently.
+    const THREADS: usize = 4;
+    /// The number of lockers of every thread.
+    const LOCKERS: usize = 256;
+
+    /// Returns the control to the executor once, so other lockers of the thread run, while the guard is held.
+    async fn yield_now() {
+        let mut is_yielded = false;
+        poll_fn(|cx| {
+            if is_yielded {
+                return Poll::Ready(());
+            }
+            is_yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Runs `run` on every thread, and waits for them.
+    fn run_concurrently<C: Send + Sync + 'static>(c: &Arc<C>, run: fn(&Arc<C>)) {
+        let threads: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let c = c.clone();
+                thread::spawn(move || run(&c))
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    /// Checks the mutual exclusion of the borrowed and owned guards and the release of the mutex by the cancelled locker.
+    pub fn check_mutex<M>()
+    where
+        M: AsyncMutexLike<Target = usize> + Send + Sync + 'static,
+        M::OwnedGuardFuture: Send,
+        M::OwnedGuard: Send,
+    {
+        let c = Arc::new(M::new(0));
+
+        run_concurrently(&c, |c| {
+            block_on(
+                futures::stream::iter(0..LOCKERS).for_each_concurrent(None, |i| async move {
+                    if i % 2 == 0 {
+                        *c.lock().await += 1;
+                    } else {
+                        let mut co = c.lock_owned().await;
+                        let before = *co;
+                        yield_now().await;
+                        *co = before + 1;
+                    }
+                }),
+            )
+        });
+        let total = THREADS * LOCKERS;
+        assert_eq!(*block_on(c.lock()), total);
+
+        let co = block_on(c.lock());
+        assert!(c.lock().now_or_never().is_none());
+        drop(co);
+        assert_eq!(*block_on(c.lock()), total);
+
+        // The owned guard and its future are moved between the threads.
+        let owned = c.lock_owned();
+        let mut co = thread::spawn(move || block_on(owned)).join().unwrap();
+        *co += 1;
+        drop(co);
+        assert_eq!(*block_on(c.lock()), total + 1);
+    }
+
+    /// Checks the shared reads, the exclusive writes and the release of the lock by the cancelled lockers.
+    pub fn check_rwlock<L>()
+    where
+        L: AsyncRwLockLike<Target = usize> + Send + Sync + 'static,
+        L::ReadOwnedGuardFuture: Send,
+        L::WriteOwnedGuardFuture: Send,
+        L::ReadOwnedGuard: Send,
+        L::WriteOwnedGuard: Send,
+    {
+        let c = Arc::new(L::new(0));
+
+        run_concurrently(&c, |c| {
+            block_on(
+                futures::stream::iter(0..LOCKERS).for_each_concurrent(None, |i| async move {
+                    match i % 4 {
+                        0 => assert_eq!(*c.read().await % 2, 0),
+                        1 => assert_eq!(*c.read_owned().await % 2, 0),
+                        2 => *c.write().await += 2,
+                        _ => {
+                            let mut co = c.write_owned().await;
+                            let before = *co;
+                            yield_now().await;
+                            *co = before + 2;
+                        }
+                    }
+                }),
+            )
+        });
+        let total = THREADS * LOCKERS;
+        assert_eq!(*block_on(c.read()), total);
+
+        // The readers share the lock, and the writer waits for them.
+        let first = block_on(c.read());
+        let second = block_on(c.read_owned());
+        assert_eq!(*first, *second);
+        assert!(c.write().now_or_never().is_none());
+        drop(first);
+        drop(second);
+
+        let co = block_on(c.write_owned());
+        assert!(c.read().now_or_never().is_none());
+        drop(co);
+        assert_eq!(*block_on(c.write()), total);
+
+        // The owned guards and their futures are moved between the threads.
+        let owned = c.write_owned();
+        let mut co = thread::spawn(move || block_on(owned)).join().unwrap();
+        *co += 2;
+        drop(co);
+        let owned = c.read_owned();
+        let co = thread::spawn(move || block_on(owned)).join().unwrap();
+        assert_eq!(*co, total + 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mutex::Mutex;
+    use crate::mutex_ordered::OrderedMutex;
+    use crate::rwlock::RwLock;
+    use crate::rwlock_ordered::OrderedRwLock;
+    use crate::traits::suite::{check_mutex, check_rwlock};
+    use crate::wait_strategy::{ImmediatePark, WakeImmediately};
+
+    #[test]
+    fn test_mutexes() {
+        check_mutex::<Mutex<usize>>();
+        check_mutex::<Mutex<usize, ImmediatePark>>();
+        check_mutex::<OrderedMutex<usize>>();
+        check_mutex::<OrderedMutex<usize, WakeImmediately>>();
+    }
+
+    #[test]
+    fn test_rwlocks() {
+        check_rwlock::<RwLock<usize>>();
+        check_rwlock::<RwLock<usize, ImmediatePark>>();
+        check_rwlock::<OrderedRwLock<usize>>();
+        check_rwlock::<OrderedRwLock<usize, WakeImmediately>>();
+    }
+}
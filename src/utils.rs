@@ -1,7 +1,23 @@
-#[macro_use]
 mod deref {
-    #[macro_export]
     macro_rules! impl_deref_mut {
+        ($struct_name:ident<$strategy:ident>) => {
+            $crate::impl_deref!($struct_name<$strategy>);
+            impl<T: ?Sized, $strategy> std::ops::DerefMut for $struct_name<T, $strategy> {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    unsafe { &mut *self.mutex.inner.data.get() }
+                }
+            }
+        };
+        ($struct_name:ident<$strategy:ident>, $lifetime:lifetime) => {
+            $crate::impl_deref!($struct_name<$strategy>, $lifetime);
+            impl<$lifetime, T: ?Sized, $strategy> std::ops::DerefMut
+                for $struct_name<$lifetime, T, $strategy>
+            {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    unsafe { &mut *self.mutex.inner.data.get() }
+                }
+            }
+        };
         ($struct_name:ident) => {
             $crate::impl_deref!($struct_name);
             impl<T: ?Sized> std::ops::DerefMut for $struct_name<T> {
@@ -20,8 +36,27 @@ mod deref {
         };
     }
 
-    #[macro_export]
     macro_rules! impl_deref {
+        ($struct_name:ident<$strategy:ident>) => {
+            impl<T: ?Sized, $strategy> std::ops::Deref for $struct_name<T, $strategy> {
+                type Target = T;
+
+                fn deref(&self) -> &Self::Target {
+                    unsafe { &*self.mutex.inner.data.get() }
+                }
+            }
+        };
+        ($struct_name:ident<$strategy:ident>, $lifetime:lifetime) => {
+            impl<$lifetime, T: ?Sized, $strategy> std::ops::Deref
+                for $struct_name<$lifetime, T, $strategy>
+            {
+                type Target = T;
+
+                fn deref(&self) -> &Self::Target {
+                    unsafe { &*self.mutex.inner.data.get() }
+                }
+            }
+        };
         ($struct_name:ident) => {
             impl<T: ?Sized> std::ops::Deref for $struct_name<T> {
                 type Target = T;
@@ -41,12 +76,26 @@ mod deref {
             }
         };
     }
+
+    pub(crate) use {impl_deref, impl_deref_mut};
 }
 
-#[macro_use]
 mod drop {
-    #[macro_export]
     macro_rules! impl_drop_guard {
+        ($struct_name:ident<$strategy:ident>, $unlock_fn:ident) => {
+            impl<T: ?Sized, $strategy> Drop for $struct_name<T, $strategy> {
+                fn drop(&mut self) {
+                    self.mutex.inner.$unlock_fn()
+                }
+            }
+        };
+        ($struct_name:ident<$strategy:ident>, $lifetime:lifetime, $unlock_fn:ident) => {
+            impl<$lifetime, T: ?Sized, $strategy> Drop for $struct_name<$lifetime, T, $strategy> {
+                fn drop(&mut self) {
+                    self.mutex.inner.$unlock_fn()
+                }
+            }
+        };
         ($struct_name:ident, $unlock_fn:ident) => {
             impl<T: ?Sized> Drop for $struct_name<T> {
                 fn drop(&mut self) {
@@ -62,51 +111,58 @@ mod drop {
             }
         };
     }
-    #[macro_export]
     macro_rules! impl_drop_guard_self {
-        ($struct_name:ident, $unlock_fn:ident) => {
-            impl<T: ?Sized> Drop for $struct_name<T> {
+        ($struct_name:ident<$strategy:ident>, $unlock_fn:ident) => {
+            impl<T: ?Sized, $strategy> Drop for $struct_name<T, $strategy> {
                 fn drop(&mut self) {
                     self.mutex.$unlock_fn()
                 }
             }
         };
-        ($struct_name:ident, $lifetime:lifetime, $unlock_fn:ident) => {
-            impl<$lifetime, T: ?Sized> Drop for $struct_name<$lifetime, T> {
+        ($struct_name:ident<$strategy:ident>, $lifetime:lifetime, $unlock_fn:ident) => {
+            impl<$lifetime, T: ?Sized, $strategy> Drop for $struct_name<$lifetime, T, $strategy> {
                 fn drop(&mut self) {
                     self.mutex.$unlock_fn()
                 }
             }
         };
-    }
-
-    #[macro_export]
-    macro_rules! impl_drop_guard_future {
         ($struct_name:ident, $unlock_fn:ident) => {
             impl<T: ?Sized> Drop for $struct_name<T> {
                 fn drop(&mut self) {
-                    if !self.is_realized {
-                        self.mutex.inner.$unlock_fn()
-                    }
+                    self.mutex.$unlock_fn()
                 }
             }
         };
         ($struct_name:ident, $lifetime:lifetime, $unlock_fn:ident) => {
             impl<$lifetime, T: ?Sized> Drop for $struct_name<$lifetime, T> {
                 fn drop(&mut self) {
-                    if !self.is_realized {
-                        self.mutex.inner.$unlock_fn()
-                    }
+                    self.mutex.$unlock_fn()
                 }
             }
         };
     }
+
+    pub(crate) use {impl_drop_guard, impl_drop_guard_self};
 }
 
-#[macro_use]
 mod sync {
-    #[macro_export]
     macro_rules! impl_send_sync_rwlock {
+        ($mutex_name:ident<$strategy:ident>, $read_guard:ident, $read_guard_owned:ident, $write_guard:ident, $write_guard_owned:ident) => {
+            unsafe impl<T, $strategy> Send for $mutex_name<T, $strategy> where T: Send + ?Sized {}
+            unsafe impl<T, $strategy> Sync for $mutex_name<T, $strategy> where T: Send + Sync + ?Sized {}
+
+            unsafe impl<T, $strategy> Send for $read_guard<'_, T, $strategy> where T: ?Sized + Send {}
+            unsafe impl<T, $strategy> Sync for $read_guard<'_, T, $strategy> where T: Send + Sync + ?Sized {}
+
+            unsafe impl<T, $strategy> Send for $read_guard_owned<T, $strategy> where T: ?Sized + Send {}
+            unsafe impl<T, $strategy> Sync for $read_guard_owned<T, $strategy> where T: Send + Sync + ?Sized {}
+
+            unsafe impl<T, $strategy> Send for $write_guard<'_, T, $strategy> where T: ?Sized + Send {}
+            unsafe impl<T, $strategy> Sync for $write_guard<'_, T, $strategy> where T: Send + Sync + ?Sized {}
+
+            unsafe impl<T, $strategy> Send for $write_guard_owned<T, $strategy> where T: ?Sized + Send {}
+            unsafe impl<T, $strategy> Sync for $write_guard_owned<T, $strategy> where T: Send + Sync + ?Sized {}
+        };
         ($mutex_name:ident, $read_guard:ident, $read_guard_owned:ident, $write_guard:ident, $write_guard_owned:ident) => {
             unsafe impl<T> Send for $mutex_name<T> where T: Send + ?Sized {}
             unsafe impl<T> Sync for $mutex_name<T> where T: Send + Sync + ?Sized {}
@@ -125,8 +181,17 @@ mod sync {
         };
     }
 
-    #[macro_export]
     macro_rules! impl_send_sync_mutex {
+        ($mutex_name:ident<$strategy:ident>, $mutex_guard:ident, $mutex_guard_owned:ident) => {
+            unsafe impl<T, $strategy> Send for $mutex_name<T, $strategy> where T: Send + ?Sized {}
+            unsafe impl<T, $strategy> Sync for $mutex_name<T, $strategy> where T: Send + Sync + ?Sized {}
+
+            unsafe impl<T, $strategy> Send for $mutex_guard<'_, T, $strategy> where T: ?Sized + Send {}
+            unsafe impl<T, $strategy> Sync for $mutex_guard<'_, T, $strategy> where T: Send + Sync + ?Sized {}
+
+            unsafe impl<T, $strategy> Send for $mutex_guard_owned<T, $strategy> where T: ?Sized + Send {}
+            unsafe impl<T, $strategy> Sync for $mutex_guard_owned<T, $strategy> where T: Send + Sync + ?Sized {}
+        };
         ($mutex_name:ident, $mutex_guard:ident, $mutex_guard_owned:ident) => {
             unsafe impl<T> Send for $mutex_name<T> where T: Send + ?Sized {}
             unsafe impl<T> Sync for $mutex_name<T> where T: Send + Sync + ?Sized {}
@@ -138,4 +203,26 @@ mod sync {
             unsafe impl<T> Sync for $mutex_guard_owned<T> where T: Send + Sync + ?Sized {}
         };
     }
+
+    pub(crate) use {impl_send_sync_mutex, impl_send_sync_rwlock};
 }
+
+mod thread {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns the small sequential id of the current thread, by which the readers are spread over the shards and the stripes.
+    #[inline]
+    pub(crate) fn thread_id() -> usize {
+        static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
+        thread_local! {
+            static THREAD_ID: usize = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+        }
+
+        THREAD_ID.with(|id| *id)
+    }
+}
+
+pub(crate) use deref::{impl_deref, impl_deref_mut};
+pub(crate) use drop::{impl_drop_guard, impl_drop_guard_self};
+pub(crate) use sync::{impl_send_sync_mutex, impl_send_sync_rwlock};
+pub(crate) use thread::thread_id;
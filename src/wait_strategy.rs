@@ -0,0 +1,120 @@
+use std::fmt::Debug;
+use std::task::Waker;
+
+/// The behaviour of the contended locker, which failed to acquire the lock.
+///
+/// The locker may spin, retrying to acquire the lock, before it returns control to the async runtime.
+/// Then it's either parked until the lock wakes it, or it wakes itself to be polled again.
+pub trait WaitStrategy {
+    /// If `true`, the lock keeps the waker of the locker and wakes it, when the lock is released.
+    /// Otherwise the locker has to wake itself in `wait`.
+    const IS_PARKING: bool;
+
+    /// If `true`, the lock observes its hold times and decides by them, whether the locker spins before it's parked.
+    /// The `spin` isn't called in this case.
+    const IS_ADAPTIVE: bool = false;
+
+    /// Retries to acquire the lock before the locker returns control to the async runtime.
+    /// The `attempt` is the number of the previous polls of the locker, which didn't acquire the lock.
+    ///
+    /// Returns `true` if the lock was acquired.
+    #[inline]
+    fn spin<F: FnMut() -> bool>(attempt: usize, try_acquire: F) -> bool {
+        let _ = (attempt, try_acquire);
+        false
+    }
+
+    /// Returns control to the async runtime after the failed attempts to acquire the lock.
+    fn wait(attempt: usize, waker: &Waker);
+}
+
+/// Wakes the locker immediately, so the async runtime polls it again as soon as possible.
+/// It's the fastest strategy under a short contention, but it keeps the runtime busy while the lock is held.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WakeImmediately;
+
+impl WaitStrategy for WakeImmediately {
+    const IS_PARKING: bool = false;
+
+    #[inline]
+    fn wait(_attempt: usize, waker: &Waker) {
+        waker.wake_by_ref();
+    }
+}
+
+/// Parks the locker right away until the lock is released.
+/// It doesn't waste the runtime on polling, so it fits for the locks, which are held for a long time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImmediatePark;
+
+impl WaitStrategy for ImmediatePark {
+    const IS_PARKING: bool = true;
+
+    #[inline]
+    fn wait(_attempt: usize, _waker: &Waker) {}
+}
+
+/// The number of spin rounds of `SpinThenPark`, every round spins twice longer than the previous one.
+const SPIN_ROUNDS: u32 = 6;
+
+/// Spins for a short time, retrying to acquire the lock, and then parks the locker until the lock is released.
+/// It avoids the trip through the async runtime for the locks, which are held for a very short time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpinThenPark;
+
+impl WaitStrategy for SpinThenPark {
+    const IS_PARKING: bool = true;
+
+    #[inline]
+    fn spin<F: FnMut() -> bool>(_attempt: usize, mut try_acquire: F) -> bool {
+        (0..SPIN_ROUNDS).any(|round| {
+            for _ in 0..1 << round {
+                std::hint::spin_loop();
+            }
+            try_acquire()
+        })
+    }
+
+    #[inline]
+    fn wait(_attempt: usize, _waker: &Waker) {}
+}
+
+/// Spins or parks the locker right away by the hold times, which are observed by the lock.
+/// The locker spins only if the lock is held for a short time usually and the recent spins acquired it,
+/// so it's as fast as `SpinThenPark` for the short critical sections, and doesn't waste the CPU for the long ones.
+/// The ordered locks don't observe the hold times, so they park the locker right away.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Adaptive;
+
+impl WaitStrategy for Adaptive {
+    const IS_PARKING: bool = true;
+    const IS_ADAPTIVE: bool = true;
+
+    #[inline]
+    fn wait(_attempt: usize, _waker: &Waker) {}
+}
+
+/// The maximum power of two of the spins of `YieldBackoff`.
+const MAX_BACKOFF: usize = 10;
+
+/// Yields to the async runtime like `WakeImmediately`, but every failed poll spins twice longer before the next attempt.
+/// The locker is never parked, so the order of polls depends only on the runtime, which is useful for deterministic tests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct YieldBackoff;
+
+impl WaitStrategy for YieldBackoff {
+    const IS_PARKING: bool = false;
+
+    #[inline]
+    fn spin<F: FnMut() -> bool>(attempt: usize, mut try_acquire: F) -> bool {
+        for _ in 0..1 << attempt.min(MAX_BACKOFF) {
+            std::hint::spin_loop();
+        }
+        try_acquire()
+    }
+
+    #[inline]
+    fn wait(_attempt: usize, waker: &Waker) {
+        waker.wake_by_ref();
+    }
+}
@@ -0,0 +1,451 @@
+use crate::inner::WakerSet;
+use crate::rwlock::{RwLock, RwLockReadGuard, RwLockReadGuardFuture};
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Creates a new watch channel, returning the sender and the receiver halves.
+///
+/// The channel keeps only the latest value, which is stored in the `RwLock`.
+/// Every send takes the write guard and bumps the version of the value,
+/// so receivers may wait for the new versions with `Receiver::changed`.
+///
+/// # Examples
+///
+/// ```
+/// use fast_async_mutex::watch;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, mut rx) = watch::channel(10);
+///
+///     tokio::spawn(async move {
+///         tx.send(11).await.unwrap();
+///     });
+///
+///     rx.changed().await.unwrap();
+///     assert_eq!(*rx.borrow().await, 11);
+/// }
+/// ```
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(init),
+        version: AtomicUsize::new(0),
+        receivers: AtomicUsize::new(1),
+        is_closed: AtomicBool::new(false),
+        waiters: WakerSet::new(),
+    });
+
+    let rx = Receiver {
+        shared: shared.clone(),
+        version: 0,
+    };
+
+    (Sender { shared }, rx)
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    value: RwLock<T>,
+    version: AtomicUsize,
+    receivers: AtomicUsize,
+    is_closed: AtomicBool,
+    /// The wakers of the receivers, which wait for the new version or for the closed channel.
+    waiters: WakerSet,
+}
+
+/// The sending half of the watch channel.
+/// Sending takes the write guard of the internal `RwLock`, so it waits until all borrows are released.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of the watch channel.
+/// Receivers see only the latest value, and the intermediate values may be skipped.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    version: usize,
+}
+
+impl<T> Sender<T> {
+    /// Sends a new value and notifies all receivers.
+    ///
+    /// Returns the value back if there are no receivers left.
+    #[inline]
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.shared.receivers.load(Ordering::Acquire) == 0 {
+            return Err(SendError(value));
+        }
+
+        self.send_modify(|old| *old = value).await;
+        Ok(())
+    }
+
+    /// Modifies the value in place and notifies all receivers.
+    ///
+    /// Unlike `send`, the value is modified even if there are no receivers left.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::watch;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx) = watch::channel(10);
+    ///     tx.send_modify(|value| *value += 1).await;
+    ///     assert_eq!(*rx.borrow_and_update().await, 11);
+    /// }
+    /// ```
+    pub async fn send_modify<F>(&self, modify: F)
+    where
+        F: FnOnce(&mut T),
+    {
+        let mut guard = self.shared.value.write().await;
+        modify(&mut guard);
+        self.shared.version.fetch_add(1, Ordering::Release);
+        drop(guard);
+        self.shared.waiters.wake_all();
+    }
+
+    /// Acquires a read guard of the latest value.
+    ///
+    /// The borrow joins the held borrows even if a send waits for them, so the nested borrows of a task don't deadlock.
+    #[inline]
+    pub fn borrow(&self) -> RwLockReadGuardFuture<'_, T> {
+        self.shared.value.read_recursive()
+    }
+
+    /// Creates a new receiver, which sees the current value as already seen.
+    #[inline]
+    pub fn subscribe(&self) -> Receiver<T> {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: self.shared.clone(),
+            version: self.shared.version.load(Ordering::Acquire),
+        }
+    }
+
+    /// Returns the number of the alive receivers.
+    #[inline]
+    pub fn receiver_count(&self) -> usize {
+        self.shared.receivers.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if all receivers were dropped.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.receiver_count() == 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.is_closed.store(true, Ordering::Release);
+        self.shared.waiters.wake_all();
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Acquires a read guard of the latest value without marking it as seen.
+    ///
+    /// The borrow joins the held borrows even if a send waits for them, so the nested borrows of a task don't deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::watch;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, rx) = watch::channel(10);
+    ///     tx.send(11).await.unwrap();
+    ///     assert_eq!(*rx.borrow().await, 11);
+    ///     assert!(rx.has_changed().unwrap());
+    /// }
+    /// ```
+    #[inline]
+    pub fn borrow(&self) -> RwLockReadGuardFuture<'_, T> {
+        self.shared.value.read_recursive()
+    }
+
+    /// Acquires a read guard of the latest value and marks it as seen.
+    pub async fn borrow_and_update(&mut self) -> RwLockReadGuard<'_, T> {
+        let guard = self.shared.value.read_recursive().await;
+        self.version = self.shared.version.load(Ordering::Acquire);
+        guard
+    }
+
+    /// Returns `true` if there is a value which was not seen by this receiver.
+    ///
+    /// Returns an error if the sender was dropped.
+    #[inline]
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        if self.shared.is_closed.load(Ordering::Acquire) {
+            return Err(RecvError(()));
+        }
+        Ok(self.shared.version.load(Ordering::Acquire) != self.version)
+    }
+
+    /// Waits for a value which was not seen by this receiver, and marks it as seen.
+    ///
+    /// Returns an error if the sender was dropped and all values have been seen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fast_async_mutex::watch;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx) = watch::channel(10);
+    ///     tx.send(11).await.unwrap();
+    ///     drop(tx);
+    ///
+    ///     assert!(rx.changed().await.is_ok());
+    ///     assert!(rx.changed().await.is_err());
+    /// }
+    /// ```
+    #[inline]
+    pub fn changed(&mut self) -> ChangedFuture<'_, T> {
+        ChangedFuture {
+            receiver: self,
+            id: None,
+        }
+    }
+
+    #[inline]
+    fn try_update(&mut self) -> bool {
+        let version = self.shared.version.load(Ordering::Acquire);
+        if version != self.version {
+            self.version = version;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: self.shared.clone(),
+            version: self.version,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receivers.fetch_sub(1, Ordering::Release);
+    }
+}
+
+#[derive(Debug)]
+pub struct ChangedFuture<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    /// The id of the registered waker.
+    id: Option<usize>,
+}
+
+impl<T> Future for ChangedFuture<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            if this.receiver.try_update() {
+                return Poll::Ready(Ok(()));
+            } else if this.receiver.shared.is_closed.load(Ordering::Acquire) {
+                // The last value could be sent right before the sender was dropped.
+                return if this.receiver.try_update() {
+                    Poll::Ready(Ok(()))
+                } else {
+                    Poll::Ready(Err(RecvError(())))
+                };
+            }
+
+            // The version and the close are checked again under the lock of the waiters,
+            // so the sender, which wakes them afterwards, doesn't miss the waker.
+            let shared = &this.receiver.shared;
+            let version = this.receiver.version;
+            let is_registered = shared.waiters.register(&mut this.id, cx.waker(), || {
+                shared.version.load(Ordering::Acquire) == version
+                    && !shared.is_closed.load(Ordering::Acquire)
+            });
+            if is_registered {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl<T> Drop for ChangedFuture<'_, T> {
+    fn drop(&mut self) {
+        self.receiver.shared.waiters.remove(self.id);
+    }
+}
+
+/// Error returned by `Sender::send` when all receivers were dropped.
+/// It contains the value which was not sent.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish()
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by `Receiver::changed` when the sender was dropped.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError(());
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl Error for RecvError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::inner::MAX_BYPASS;
+    use crate::rwlock::RwLockReadGuard;
+    use crate::watch::{channel, SendError};
+    use futures::StreamExt;
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_latest_value() {
+        let (tx, mut rx) = channel(0);
+
+        let sender = tokio::spawn(async move {
+            for i in 1..=1000 {
+                tx.send(i).await.unwrap();
+            }
+        });
+
+        let mut last = 0;
+        while rx.changed().await.is_ok() {
+            let value: RwLockReadGuard<i32> = rx.borrow().await;
+            assert!(*value >= last);
+            last = *value;
+        }
+
+        sender.await.unwrap();
+        assert_eq!(last, 1000);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_many_receivers() {
+        let (tx, rx) = channel(String::from("lol"));
+
+        let receivers = futures::stream::iter(0..100)
+            .map(|_| {
+                let mut rx = rx.clone();
+                tokio::spawn(async move {
+                    rx.changed().await.unwrap();
+                    rx.borrow().await.clone()
+                })
+            })
+            .collect::<Vec<_>>()
+            .await;
+
+        tx.send_modify(|value| *value += "lol").await;
+
+        for receiver in receivers {
+            assert_eq!(receiver.await.unwrap(), "lollol");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 12)]
+    async fn test_idle_receiver_is_parked() {
+        let (tx, mut rx) = channel(0);
+        let polls = Arc::new(AtomicUsize::new(0));
+
+        let receiver = {
+            let polls = polls.clone();
+            tokio::spawn(async move {
+                let mut changed = Box::pin(rx.changed());
+                futures::future::poll_fn(|cx| {
+                    polls.fetch_add(1, Ordering::SeqCst);
+                    changed.as_mut().poll(cx)
+                })
+                .await
+            })
+        };
+
+        sleep(Duration::from_millis(50)).await;
+        tx.send(1).await.unwrap();
+        assert!(receiver.await.unwrap().is_ok());
+        assert!(polls.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_nested_borrow() {
+        let (tx, rx) = channel(0);
+
+        let value = rx.borrow().await;
+        let send = tokio::spawn(async move { tx.send(1).await });
+
+        // The nested borrows join the held one, while the send waits for it.
+        sleep(Duration::from_millis(10)).await;
+        for _ in 0..=MAX_BYPASS {
+            let nested = tokio::time::timeout(Duration::from_millis(100), rx.borrow())
+                .await
+                .expect("the nested borrow must join the held one");
+            assert_eq!(*nested, *value);
+        }
+        drop(value);
+
+        send.await.unwrap().unwrap();
+        assert_eq!(*rx.borrow().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_changes() {
+        let (tx, mut rx) = channel(0);
+
+        assert!(!rx.has_changed().unwrap());
+        assert!(tokio::time::timeout(Duration::from_millis(1), rx.changed())
+            .await
+            .is_err());
+
+        drop(tx);
+        assert!(rx.has_changed().is_err());
+        assert!(rx.changed().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_closed_receivers() {
+        let (tx, rx) = channel(0);
+        let rx2 = tx.subscribe();
+        assert_eq!(tx.receiver_count(), 2);
+
+        drop(rx);
+        drop(rx2);
+
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(1).await, Err(SendError(1)));
+    }
+}
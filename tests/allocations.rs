@@ -0,0 +1,103 @@
+//! The lock futures don't allocate, while they wait in the queue, which already has enough nodes,
+//! so these tests count the allocations by the global allocator.
+//! They live in their own test binary, so the counting allocator doesn't run under the unit tests.
+
+use fast_async_mutex::mutex::Mutex;
+use fast_async_mutex::wait_strategy::ImmediatePark;
+use futures::executor::block_on;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::future::Future;
+use std::task::{Context, Poll};
+
+/// Counts the allocations of the current thread, so the test can check that waiting doesn't allocate.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_waiting_without_allocations() {
+    let c: Mutex<_, ImmediatePark> = Mutex::with_strategy(0);
+    let waker = futures::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    // The nodes of the queue are allocated by the first round and reused by the second one.
+    wait_in_queue(&c, &mut cx);
+    assert_eq!(wait_in_queue(&c, &mut cx), 0);
+    assert_eq!(*block_on(c.lock()), 200);
+}
+
+/// Queues 100 lockers, cancels every second of them and acquires the lock by all of them.
+/// Returns the number of the allocations, while the lockers wait.
+fn wait_in_queue(c: &Mutex<usize, ImmediatePark>, cx: &mut Context<'_>) -> usize {
+    let co = block_on(c.lock());
+    let mut lockers: Vec<_> = (0..100).map(|_| Box::pin(c.lock())).collect();
+    let before = ALLOCATIONS.with(Cell::get);
+
+    for locker in lockers.iter_mut() {
+        assert!(locker.as_mut().poll(cx).is_pending());
+    }
+    // The waiters are unlinked from the middle of the queue.
+    for locker in lockers.iter_mut().step_by(2) {
+        locker.set(c.lock());
+    }
+    drop(co);
+    while !lockers.is_empty() {
+        lockers.retain_mut(|locker| match locker.as_mut().poll(cx) {
+            Poll::Ready(mut co) => {
+                *co += 1;
+                false
+            }
+            Poll::Pending => true,
+        });
+    }
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_delegation_without_allocations() {
+    let c = Mutex::new(0);
+    let waker = futures::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    // The queue of the mutex is allocated by the first round only.
+    delegate_in_queue(&c, &mut cx);
+    assert_eq!(delegate_in_queue(&c, &mut cx), 0);
+    assert_eq!(*block_on(c.lock()), 9);
+}
+
+/// Queues 10 delegations behind the held lock, drops one of them and runs the rest.
+/// Returns the number of the allocations, while the delegations wait.
+fn delegate_in_queue(c: &Mutex<usize>, cx: &mut Context<'_>) -> usize {
+    let co = block_on(c.lock());
+    let mut delegations: Vec<_> = (0..10)
+        .map(|i| Box::pin(c.delegate(move |co| std::mem::replace(co, i))))
+        .collect();
+    let before = ALLOCATIONS.with(Cell::get);
+    for delegation in delegations.iter_mut() {
+        assert!(delegation.as_mut().poll(cx).is_pending());
+    }
+    delegations.remove(5);
+    drop(co);
+
+    for delegation in delegations.iter_mut() {
+        assert!(delegation.as_mut().poll(cx).is_ready());
+    }
+    ALLOCATIONS.with(Cell::get) - before
+}